@@ -7,10 +7,25 @@ use teller::{cli, tracing};
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = cli::Cli::parse();
+    args.color.apply();
 
-    tracing(args.verbose);
+    let telemetry = match tracing(&args.logs, args.color) {
+        Ok(telemetry) => telemetry,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            exit(teller::exit_code(&err));
+        }
+    };
 
-    let resp = cli::run(&args).await?;
+    let resp = cli::run(&args).await;
+    telemetry.shutdown();
+    let resp = match resp {
+        Ok(resp) => resp,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            exit(teller::exit_code(&err));
+        }
+    };
 
     if let Some(msg) = resp.message {
         println!("{msg}");
@@ -1,29 +1,72 @@
 use std::{
     env,
+    io::{IsTerminal, Read, Write},
     path::{Path, PathBuf},
 };
 
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use eyre::{eyre, OptionExt};
-use teller_core::{exec, export, teller::Teller};
-use teller_providers::{config::KV, providers::ProviderKind};
+#[cfg(unix)]
+use teller_core::agent;
+use teller_core::{
+    ci::Ci,
+    config::Config,
+    copy, credentials, docker_credential, encrypted, example, exec, export, generate,
+    git_credential,
+    history::{Journal, Undo},
+    migrate, provision, redact, search,
+    snapshot::{Decryption, Encryption, Snapshot},
+    teller::Teller,
+    template,
+};
+use teller_providers::{
+    config::{Generation, PathMap, KV},
+    providers::{dotenv::Dotenv, ProviderKind},
+    Provider,
+};
 
 use crate::{
-    io::{self, or_stdin, or_stdout},
-    new, scan, Response,
+    child_code,
+    color::ColorChoice,
+    drift, exit,
+    io::{self, or_stdin, or_stdout, or_stdout_streaming, OutputArgs},
+    k8s,
+    login::{self, LoginArgs},
+    logs::{self, LogArgs},
+    mcp, new, progress, provider_code, scan, serve, shell_hook, ui, ConfigError, Response,
 };
 
 #[derive(Debug, Clone, Parser)] // requires `derive` feature
 #[command(name = "teller")]
 #[command(about = "A multi provider secret management tool", version, long_about = None)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Cli {
     /// Path to your teller.yml config
     #[arg(short, long)]
     pub config: Option<String>,
 
-    /// Path to your teller.yml config
-    #[arg(long)]
-    pub verbose: bool,
+    #[command(flatten)]
+    pub logs: LogArgs,
+
+    /// Skip providers and maps that fail to load with a warning, instead of failing
+    #[arg(long, global = true)]
+    pub ignore_errors: bool,
+
+    /// Disable core dumps and lock memory before loading secrets
+    #[arg(long, global = true)]
+    pub harden: bool,
+
+    /// Keep the state of maps before changing them, so `teller undo` can put it back
+    #[arg(long, global = true)]
+    pub undo: bool,
+
+    /// Do not show progress of provider calls and scans
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// When to color output: `auto` colors terminals unless `NO_COLOR` is set
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t, global = true)]
+    pub color: ColorChoice,
 
     /// A teller command
     #[command(subcommand)]
@@ -39,7 +82,81 @@ pub enum Commands {
         /// Run command as shell command
         #[arg(short, long)]
         shell: bool,
-        /// The command to run
+        /// Pipe key-values to the command's stdin in this format, instead of
+        /// setting environment variables
+        #[arg(long, value_enum)]
+        stdin_format: Option<Format>,
+        /// Do not set key-values as environment variables, e.g. when passing
+        /// them as `{{key:NAME}}` arguments
+        #[arg(long)]
+        no_env: bool,
+        /// Keep this variable when resetting the environment, a name or glob
+        /// such as `CARGO_*` (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        keep_env: Vec<String>,
+        /// Never pass this variable on to the command, a name or glob (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        drop_env: Vec<String>,
+        /// Run the command in this directory
+        #[arg(long, value_name = "DIR")]
+        cwd: Option<PathBuf>,
+        /// Also set the variables of this env file, collected key-values take
+        /// precedence (repeatable)
+        #[arg(long, value_name = "PATH")]
+        also_env_file: Vec<PathBuf>,
+        /// Key of an SSH private key, loaded into an ephemeral ssh-agent for
+        /// the command instead of set as a variable. A certificate is taken
+        /// from `<KEY>_CERT`. (repeatable)
+        #[arg(long, value_name = "KEY")]
+        ssh_key: Vec<String>,
+        /// Write SSH keys to temporary identity files, used through
+        /// `GIT_SSH_COMMAND`, instead of an agent
+        #[arg(long)]
+        ssh_identity_files: bool,
+        /// Do not have the CI mask collected values in its logs, when running in
+        /// GitHub Actions or Azure Pipelines
+        #[arg(long)]
+        no_ci_mask: bool,
+        /// The command to run, or the name of a command from the configuration
+        /// followed by extra arguments. `{{key:NAME}}` placeholders are replaced
+        /// with values.
+        #[arg(value_name = "COMMAND", raw = true)]
+        command: Vec<String>,
+    },
+
+    /// Run `docker compose` with collected key-values in a temporary env file,
+    /// passed as `--env-file` and removed once compose exits
+    Compose {
+        /// Run compose in this directory
+        #[arg(long, value_name = "DIR")]
+        cwd: Option<PathBuf>,
+        /// Arguments to `docker compose`, e.g. `up -d`
+        #[arg(value_name = "ARGS", raw = true)]
+        args: Vec<String>,
+    },
+
+    /// Write a tool's credential file from collected key-values, readable by
+    /// you only. With a command, run it and put the file back as it was once
+    /// it exits.
+    Provision {
+        /// The tool to write credentials for
+        #[arg(value_enum)]
+        tool: ProvisionTool,
+        /// Registry URL (npm, yarn, pip) or registry name (cargo), instead of
+        /// the tool's default registry
+        #[arg(long)]
+        registry: Option<String>,
+        /// Key of the token or password, instead of the tool's, e.g.
+        /// `NPM_TOKEN`
+        #[arg(long)]
+        key: Option<String>,
+        /// Credential file to write, instead of where the tool looks for it
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+        /// Overwrite an existing file when not running a command
+        #[arg(long)]
+        force: bool,
+        /// Command to run with the file, e.g. `npm publish`
         #[arg(value_name = "COMMAND", raw = true)]
         command: Vec<String>,
     },
@@ -51,15 +168,43 @@ pub enum Commands {
         /// The format to export to
         #[arg(value_enum, index = 1)]
         format: Format,
+        /// Output file (stdout if none given)
+        #[arg(short, long)]
+        out: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
     },
     /// Redact text using fetched secrets
     Redact {
         /// Input file (stdin if none given)
-        #[arg(name = "in", short, long)]
+        #[arg(name = "in", short, long, conflicts_with = "watch")]
         in_file: Option<String>,
+        /// Follow log files (across rotations) and redact what gets appended to them
+        #[arg(long, value_name = "FILE")]
+        watch: Vec<PathBuf>,
+        /// When watching, start from the beginning of each file instead of its end
+        #[arg(long, requires = "watch")]
+        from_start: bool,
         /// Output file (stdout if none given)
         #[arg(short, long)]
         out: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// How to replace found secrets
+        #[arg(long, value_enum, default_value_t = RedactMode::Fixed)]
+        mode: RedactMode,
+        /// Key used to derive stable tokens in `token` mode
+        #[arg(long, env = "TELLER_REDACT_KEY", hide_env_values = true)]
+        token_key: Option<String>,
+        /// Do not redact encoded forms of secrets (base64, URL-encoded, JSON-escaped)
+        #[arg(long)]
+        skip_encoded: bool,
+        /// Treat input as JSON lines, redacting inside each JSON document
+        #[arg(long)]
+        json: bool,
+        /// Field name globs always redacted in JSON mode (e.g. `password,*_token`)
+        #[arg(long, value_delimiter = ',', requires = "json")]
+        fields: Option<Vec<String>>,
     },
 
     /// Render a key-value aware template
@@ -70,13 +215,58 @@ pub enum Commands {
         /// Output destination (stdout if none given)
         #[arg(short, long)]
         out: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Render into the section of an existing file between `teller:begin` and `teller:end`
+        /// marker lines, leaving the rest untouched
+        #[arg(long, conflicts_with = "out")]
+        in_place: Option<PathBuf>,
+        /// Render a directory: files ending with `.tera` are rendered, others are copied
+        #[arg(long, conflicts_with_all = ["in", "out", "in_place"], requires = "out_dir")]
+        in_dir: Option<PathBuf>,
+        /// Output directory, when rendering a directory
+        #[arg(long, requires = "in_dir")]
+        out_dir: Option<PathBuf>,
+        /// Fail listing all missing keys, instead of on the first one
+        #[arg(long, conflicts_with = "lenient")]
+        strict: bool,
+        /// Render missing keys as empty (or their default) with a warning, instead of failing
+        #[arg(long)]
+        lenient: bool,
+        /// Template engine (by default, from the input file extension: `.hbs` for handlebars)
+        #[arg(long, value_enum)]
+        engine: Option<TemplateEngine>,
     },
 
     /// Export compatible with ENV
-    Env {},
+    Env {
+        /// Print code loading the variables into the current shell, and unloading them when
+        /// leaving the configuration's folder, as run by `teller hook`
+        #[arg(long, value_name = "SHELL")]
+        export_to_parent: Option<shell_hook::Shell>,
+    },
+
+    /// Print a hook loading variables into the shell when entering a folder with a
+    /// configuration, to evaluate in its startup file: `eval "$(teller hook zsh)"`
+    Hook { shell: shell_hook::Shell },
 
     /// Print all currently accessible data
-    Show {},
+    Show {
+        /// Print values grouped by provider and map, with per-map errors, as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print per-provider counts of maps and keys, fetch latency, and when teller last
+    /// changed each map
+    Stats {
+        /// Print stats of every provider and map as JSON
+        #[arg(long)]
+        json: bool,
+        /// Also print a line per map
+        #[arg(long)]
+        maps: bool,
+    },
 
     /// Export as source-able shell script
     Sh {},
@@ -89,11 +279,41 @@ pub enum Commands {
         #[arg(long, short)]
         map_id: String,
 
+        /// Providers to put to: names, globs such as `ssm-*`, or `all` for every provider
+        /// with the map id
         #[arg(long, value_delimiter = ',')]
         providers: Vec<String>,
 
+        /// Key-values as `KEY=value`, or `KEY=@file` to put the content of a file (`@@`
+        /// for a value starting with `@`)
         #[clap(value_parser = parse_key_val::<String,String>)]
         kvs: Vec<(String, String)>,
+
+        /// Put the output of a command, as `KEY=COMMAND`, run without a shell, can be
+        /// repeated
+        #[arg(long, value_name = "KEY=COMMAND", value_parser = parse_key_val::<String, String>)]
+        from_cmd: Vec<(String, String)>,
+
+        /// Generate random values for these keys, written to providers without being
+        /// displayed
+        #[arg(long, value_name = "KEY", value_delimiter = ',')]
+        generate: Vec<String>,
+
+        /// How `--generate` values are made, e.g. `len=32,charset=alnum` (charsets: alnum,
+        /// alpha, digits, hex, urlsafe, ascii). Defaults to the map's `generate` policy of
+        /// each key, or 32 alphanumeric characters.
+        #[arg(long, requires = "generate", value_parser = parse_generation)]
+        policy: Option<Generation>,
+
+        /// Prompt for the value of these keys with hidden input, keeping values out of
+        /// shell history
+        #[arg(long, value_name = "KEY", value_delimiter = ',')]
+        interactive: Vec<String>,
+
+        /// Prompted values span several lines, such as pasted keys or certificates, and
+        /// end with an empty line
+        #[arg(long, requires = "interactive")]
+        multiline: bool,
     },
 
     /// Delete specific keys or complete paths
@@ -101,10 +321,37 @@ pub enum Commands {
         #[arg(long, short)]
         map_id: String,
 
+        /// Providers to delete from: names, globs such as `ssm-*`, or `all` for every provider
+        /// with the map id
         #[arg(long, value_delimiter = ',')]
         providers: Vec<String>,
 
         keys: Vec<String>,
+
+        /// Do not ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Find where keys are defined, fuzzy matching key names
+    Search {
+        /// What to look for
+        pattern: String,
+        /// Treat the pattern as a regex
+        #[arg(long)]
+        regex: bool,
+        /// Also match values (values are never printed)
+        #[arg(long)]
+        values: bool,
+        /// Print hits as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check that every declared key has a value, and values follow their map's
+    /// `validate` rules
+    Verify {
+        /// Print violations as JSON
+        #[arg(long)]
+        json: bool,
     },
     Copy {
         #[arg(long, short)]
@@ -113,11 +360,322 @@ pub enum Commands {
         #[arg(long, short, value_delimiter = ',')]
         to: Vec<String>,
 
+        /// Only add keys the target does not hold
+        #[arg(long, conflicts_with = "overwrite")]
+        merge: bool,
+
+        /// Add missing keys and update existing ones (the default)
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Delete target keys absent from the source
+        #[arg(long)]
+        prune: bool,
+
+        /// Make targets hold exactly the source's keys, same as `--overwrite --prune`
+        #[arg(long, short, conflicts_with = "merge")]
+        replace: bool,
+
+        /// Do not ask for confirmation of `--prune` or `--replace`
+        #[arg(long, short)]
+        yes: bool,
+
+        /// Print what was copied to each target as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Copy every map of a provider to another provider, verify the copies, and print the
+    /// configuration using the new provider
+    Migrate {
+        /// Provider to migrate from
+        #[arg(long)]
+        from: String,
+        /// Provider to migrate to, configured in teller.yml
+        #[arg(long)]
+        to: String,
+        /// Path of a map in the new provider, as `<map id>=<path>` (its current path if not
+        /// given), can be repeated
+        #[arg(long, value_name = "MAP_ID=PATH", value_parser = parse_key_val::<String, String>)]
+        path: Vec<(String, String)>,
+        /// Write the updated configuration to this file (stdout if none given)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Compare maps to a source map, listing keys that are missing, changed or extra
+    Drift {
+        #[command(flatten)]
+        maps: DriftArgs,
+        /// Print drift as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Check maps for drift from a source map periodically, notifying a webhook when it
+    /// appears
+    WatchDrift {
+        #[command(flatten)]
+        maps: DriftArgs,
+        /// Time between checks, e.g. `30s`, `10m` or `1h`
+        #[arg(long, default_value = "10m", value_parser = humantime::parse_duration)]
+        interval: std::time::Duration,
+        /// Slack compatible webhook to post to when drift appears
+        #[arg(long, env = "TELLER_DRIFT_WEBHOOK", hide_env_values = true)]
+        webhook: Option<String>,
+        /// Copy the source onto maps that drifted
+        #[arg(long)]
+        sync: bool,
+    },
+
+    /// Serve secrets over HTTP (read-only), e.g. from a sidecar container
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+        /// Bearer token requests must carry
+        #[arg(long, env = "TELLER_SERVE_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+        /// Serve values of keys matching this glob redacted, can be repeated
+        #[arg(long, value_name = "GLOB")]
+        redact: Vec<String>,
+    },
+
+    /// Serve a Kubernetes mutating webhook running annotated pods' containers through
+    /// `teller run`
+    K8sInject {
+        /// Address to listen on
+        #[arg(long, default_value = "0.0.0.0:8443")]
+        listen: std::net::SocketAddr,
+        /// TLS certificate (PEM), trusted by the webhook configuration
+        #[arg(long)]
+        tls_cert: PathBuf,
+        /// TLS private key (PEM)
+        #[arg(long)]
+        tls_key: PathBuf,
+        /// Image holding the teller binary, copied into pods
+        #[arg(long, default_value = "tellerops/teller:latest")]
+        image: String,
+        /// Path of the teller binary in the image
+        #[arg(long, default_value = "/usr/local/bin/teller")]
+        binary: String,
+    },
+
+    /// Browse providers, maps and keys in a terminal UI, and put, delete or copy
+    Ui {},
+
+    /// Serve a Model Context Protocol server over stdio, listing keys and masked values
+    /// without exposing secrets
+    Mcp {},
+
+    /// Save every map's key-values to an encrypted file, or restore them
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+
+    /// Store a provider's credentials in the system keyring, used when its
+    /// configuration has none: a token, or from signing in with `--oidc` (Vault) or
+    /// `--sso` (AWS)
+    Login(LoginArgs),
+
+    /// Encrypt values to commit in teller.yml, decrypted when it is loaded with the
+    /// identity file in `TELLER_IDENTITY`
+    #[command(subcommand)]
+    Config(ConfigCommand),
+
+    /// Generate files from the configuration
+    #[command(subcommand)]
+    Generate(GenerateCommand),
+
+    /// Show the changes teller made to providers with this configuration, from its
+    /// local journal
+    History {
+        /// Print entries as JSON lines
+        #[arg(long)]
+        json: bool,
+        /// Check that no entry was edited or removed
+        #[arg(long)]
+        verify: bool,
+    },
+
+    /// Put the maps changed by the last put, delete or copy made with this
+    /// configuration back to their state before it
+    Undo {
+        /// Undo without asking to confirm
         #[arg(long, short)]
+        yes: bool,
+    },
+
+    /// Act as a git credential helper, keeping HTTPS credentials in a map:
+    /// `git config --global credential.helper '!teller git-credential --map vault/git'`
+    GitCredential {
+        /// Map holding the credentials, as `<provider name>/<map id>`
+        #[arg(long)]
+        map: String,
+        /// Only give credentials, ignore git asking to store or erase them
+        #[arg(long)]
+        read_only: bool,
+        /// What git asks for
+        #[arg(value_enum)]
+        action: GitCredentialAction,
+    },
+
+    /// Act as a docker credential helper, keeping registry credentials in a
+    /// map. Run from a `docker-credential-teller` script on your PATH.
+    DockerCredentialHelper {
+        /// Map holding the credentials, as `<provider name>/<map id>`
+        #[arg(long)]
+        map: String,
+        /// What docker asks for
+        #[arg(value_enum)]
+        action: DockerCredentialAction,
+    },
+
+    /// Remove a provider's credentials from the system keyring
+    Logout {
+        /// Provider name
+        provider: String,
+    },
+
+    /// Serve collected secrets to local processes over a Unix socket
+    #[cfg(unix)]
+    #[command(subcommand)]
+    Agent(AgentCommand),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct DriftArgs {
+    /// Source map, as `<provider name>/<map id>`
+    #[arg(long, short)]
+    pub from: String,
+    /// Maps compared to the source, as `<provider name>/<map id>`
+    #[arg(long, short, value_delimiter = ',', required = true)]
+    pub to: Vec<String>,
+}
+
+impl DriftArgs {
+    fn locations(&self) -> eyre::Result<(drift::Location, Vec<drift::Location>)> {
+        let (provider, map_id) = location(&self.from, "--from")?;
+        let from = (provider.to_string(), map_id.to_string());
+        let to = self
+            .to
+            .iter()
+            .map(|to| {
+                location(to, "--to")
+                    .map(|(provider, map_id)| (provider.to_string(), map_id.to_string()))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        Ok((from, to))
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SnapshotCommand {
+    /// Save every map's key-values to an encrypted file
+    Save {
+        /// Snapshot file to write
+        file: PathBuf,
+        /// Encrypt to this age recipient (`age1...`) instead of a passphrase, can be repeated
+        #[arg(long, short)]
+        recipient: Vec<String>,
+    },
+    /// Write a snapshot's key-values back to their providers
+    Restore {
+        /// Snapshot file to read
+        file: PathBuf,
+        /// Decrypt with this age identity file instead of a passphrase
+        #[arg(long, short)]
+        identity: Option<PathBuf>,
+        /// Delete maps first, so they hold exactly the snapshot's keys
+        #[arg(long)]
         replace: bool,
     },
 }
 
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigCommand {
+    /// Encrypt a value read from stdin, printing it as a `!encrypted` value to paste
+    /// in teller.yml
+    Encrypt {
+        /// Encrypt to this age recipient (`age1...`), can be repeated
+        #[arg(long, short, required = true)]
+        recipient: Vec<String>,
+    },
+    /// Print the configuration with its encrypted values decrypted
+    Decrypt {
+        /// Decrypt with this age identity file
+        #[arg(long, short, env = "TELLER_IDENTITY")]
+        identity: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum GenerateCommand {
+    /// A `.env.example` listing every mapped key with a placeholder value and
+    /// where it comes from
+    ExampleEnv {
+        /// Value written for every key
+        #[arg(long, default_value = "")]
+        placeholder: String,
+        /// Do not fetch the keys of maps that map all of their keys, only list
+        /// keys named in the configuration
+        #[arg(long)]
+        offline: bool,
+        /// Output file (stdout if none given)
+        #[arg(short, long)]
+        out: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+#[cfg(unix)]
+#[derive(Debug, Clone, Subcommand)]
+pub enum AgentCommand {
+    /// Collect secrets and serve them until stopped
+    Serve {
+        /// Socket to listen on
+        #[arg(long, env = "TELLER_AGENT_SOCK")]
+        socket: PathBuf,
+        /// Seconds to serve collected secrets before collecting again
+        #[arg(long, default_value_t = 300)]
+        ttl: u64,
+        /// Where to write the token clients must send (`<socket>.token` if none given)
+        #[arg(long)]
+        token_file: Option<PathBuf>,
+    },
+    /// Get secrets from a running agent, as env variables
+    Get {
+        /// Socket the agent listens on
+        #[arg(long, env = "TELLER_AGENT_SOCK")]
+        socket: PathBuf,
+        /// Token of the agent (read from `<socket>.token` if none given)
+        #[arg(long, env = "TELLER_AGENT_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+        /// Collect again, even if the agent's secrets did not expire
+        #[arg(long)]
+        refresh: bool,
+        /// Keys to get (all if none given)
+        keys: Vec<String>,
+    },
+}
+
+/// Where snapshot passphrases are read from, before prompting for them
+const PASSPHRASE_ENV: &str = "TELLER_SNAPSHOT_PASSPHRASE";
+
+#[cfg(unix)]
+fn token_path(socket: &Path) -> PathBuf {
+    let mut path = socket.as_os_str().to_owned();
+    path.push(".token");
+    PathBuf::from(path)
+}
+
+/// Split a `<provider name>/<map id>` argument
+fn location<'a>(value: &'a str, arg: &str) -> eyre::Result<(&'a str, &'a str)> {
+    value.split_once('/').ok_or_else(|| {
+        eyre!("cannot parse '{arg}': '{value}', did you format it as: '<provider name>/<map id>' ?")
+    })
+}
+
 fn parse_key_val<T, U>(
     s: &str,
 ) -> std::result::Result<(T, U), Box<dyn std::error::Error + Send + Sync>>
@@ -133,6 +691,48 @@ where
     Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
 
+fn parse_generation(policy: &str) -> std::result::Result<Generation, String> {
+    generate::parse(policy).map_err(|err| err.to_string())
+}
+
+/// The value of a `KEY=value` to put: the content of a file for `@file`, and
+/// the value without its first `@` for `@@...`
+fn put_value(key: &str, value: &str) -> eyre::Result<String> {
+    match value.strip_prefix('@') {
+        Some(rest) if rest.starts_with('@') => Ok(rest.to_string()),
+        Some(file) => fs_err::read_to_string(file)
+            .map_err(|err| eyre!("cannot read the value of '{key}': {err}")),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// The output of a command, without trailing newlines, as `$(...)` would give.
+/// The command runs without a shell, its errors go to stderr.
+fn command_value(key: &str, command: &str) -> eyre::Result<String> {
+    let pwd = env::current_dir()?;
+    let output = exec::cmd(
+        command,
+        &[],
+        &exec::Opts {
+            pwd: pwd.as_path(),
+            capture: true,
+            sh: false,
+            reset_env: false,
+            stdin: None,
+            no_env: true,
+            keep_env: &[],
+            drop_env: &[],
+            extra_env: &[],
+            ssh_keys: &[],
+            ssh_identity_files: false,
+        },
+    )
+    .map_err(|err| eyre!("cannot get the value of '{key}' from `{command}`: {err}"))?;
+    Ok(String::from_utf8(output.stdout)?
+        .trim_end_matches(['\r', '\n'])
+        .to_string())
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum Format {
     /// Export as CSV
@@ -145,6 +745,108 @@ pub enum Format {
     ENV,
 }
 
+impl From<Format> for export::Format {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::CSV => Self::CSV,
+            Format::YAML => Self::YAML,
+            Format::JSON => Self::JSON,
+            Format::ENV => Self::ENV,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum RedactMode {
+    /// Replace with a fixed text (`[REDACTED]` or the map's `redact_with`)
+    Fixed,
+    /// Replace with a stable token derived from a keyed hash, e.g. `[REDACTED:a1b2c3]`
+    Token,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TemplateEngine {
+    Tera,
+    Handlebars,
+    /// `${VAR}` and `$VAR` placeholders, as with `envsubst`
+    Envsubst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Placeholder {
+    /// `[REDACTED]`
+    Redacted,
+    /// A template lookup such as `{{ key(name='DB_PASS') }}`, for use with `teller template`
+    Template,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GitCredentialAction {
+    Get,
+    Store,
+    Erase,
+}
+
+impl From<GitCredentialAction> for git_credential::Action {
+    fn from(action: GitCredentialAction) -> Self {
+        match action {
+            GitCredentialAction::Get => Self::Get,
+            GitCredentialAction::Store => Self::Store,
+            GitCredentialAction::Erase => Self::Erase,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DockerCredentialAction {
+    Get,
+    Store,
+    Erase,
+    List,
+}
+
+impl From<DockerCredentialAction> for docker_credential::Action {
+    fn from(action: DockerCredentialAction) -> Self {
+        match action {
+            DockerCredentialAction::Get => Self::Get,
+            DockerCredentialAction::Store => Self::Store,
+            DockerCredentialAction::Erase => Self::Erase,
+            DockerCredentialAction::List => Self::List,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProvisionTool {
+    /// `.npmrc`, from `NPM_TOKEN`
+    Npm,
+    /// `.yarnrc.yml`, from `NPM_TOKEN`
+    Yarn,
+    /// `$CARGO_HOME/credentials.toml`, from `CARGO_REGISTRY_TOKEN`
+    Cargo,
+    /// `pip.conf`, from `PIP_PASSWORD` and `PIP_USERNAME`
+    Pip,
+}
+
+impl From<ProvisionTool> for provision::Tool {
+    fn from(tool: ProvisionTool) -> Self {
+        match tool {
+            ProvisionTool::Npm => Self::Npm,
+            ProvisionTool::Yarn => Self::Yarn,
+            ProvisionTool::Cargo => Self::Cargo,
+            ProvisionTool::Pip => Self::Pip,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    /// SARIF 2.1.0, e.g. for GitHub code scanning
+    Sarif,
+    /// `JUnit` XML, for CI test dashboards
+    Junit,
+}
+
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Clone, Args)] // requires `derive` feature
 pub struct ScanArgs {
@@ -160,9 +862,48 @@ pub struct ScanArgs {
     /// Include binary files
     #[arg(short, long)]
     pub binary: bool,
+    /// Only scan paths (relative to root) matching this glob, can be repeated
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+    /// Skip paths (relative to root) matching this glob, can be repeated
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+    /// Skip files larger than this size in bytes
+    #[arg(long, value_name = "BYTES")]
+    pub max_file_size: Option<u64>,
+    /// Follow symbolic links
+    #[arg(long)]
+    pub follow_links: bool,
+    /// Scan every version of every file in the git history of root, instead of the files on disk
+    #[arg(long, conflicts_with = "staged")]
+    pub history: bool,
+    /// Scan the content staged in git for the next commit, instead of the files on disk
+    #[arg(long)]
+    pub staged: bool,
+    /// Replace findings in files with a placeholder, confirming each one
+    #[arg(long, conflicts_with_all = ["history", "staged", "update_baseline"])]
+    pub fix: bool,
+    /// What findings are replaced with when fixing
+    #[arg(long, value_enum, default_value_t = Placeholder::Redacted, requires = "fix")]
+    pub placeholder: Placeholder,
     /// Output matches as JSON
     #[arg(short, long)]
     pub json: bool,
+    /// Output matches as a report, for code scanning or CI dashboards
+    #[arg(long, value_enum, conflicts_with = "json")]
+    pub report: Option<ReportFormat>,
+    /// Also detect well known secret formats (AWS keys, GitHub tokens, private keys, ...)
+    #[arg(long)]
+    pub patterns: bool,
+    /// Also detect high entropy strings
+    #[arg(long)]
+    pub entropy: bool,
+    /// Baseline file of accepted findings, which are not reported
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
+    /// Write all current findings to the baseline file
+    #[arg(long, requires = "baseline")]
+    pub update_baseline: bool,
 }
 
 const DEFAULT_FILE_PATH: &str = ".teller.yml";
@@ -204,21 +945,139 @@ fn find_file_upwards(start_dir: &Path, config_filename: &str) -> eyre::Result<Op
     }
 }
 
-async fn load_teller(config: Option<String>) -> eyre::Result<Teller> {
-    let config_arg = if let Some(config) = config {
-        config
+fn config_path(args: &Cli) -> eyre::Result<PathBuf> {
+    if let Some(config) = args.config.clone() {
+        Ok(PathBuf::from(config))
     } else {
-        find_file_upwards(env::current_dir()?.as_path(), DEFAULT_FILE_PATH)?
-            .ok_or_eyre("cannot find configuration from current folder and up to root")?
-            .to_string_lossy()
-            .to_string()
-    };
+        find_file_upwards(env::current_dir()?.as_path(), DEFAULT_FILE_PATH)?.ok_or_else(|| {
+            ConfigError("cannot find configuration from current folder and up to root".to_string())
+                .into()
+        })
+    }
+}
+
+fn load_config(path: &Path) -> eyre::Result<Config> {
+    Config::from_path(path).map_err(|err| {
+        eyre::Report::new(err).wrap_err(ConfigError(format!(
+            "cannot load configuration {}",
+            path.display()
+        )))
+    })
+}
 
-    let config_path = Path::new(&config_arg);
-    let teller = Teller::from_yaml(config_path).await?;
+async fn load_teller(args: &Cli) -> eyre::Result<Teller> {
+    let config_path = config_path(args)?;
+    if args.harden {
+        teller_core::harden::harden()?;
+    }
+    let config = load_config(&config_path)?;
+    let mut teller = Teller::from_config(&config).await?;
+    if args.ignore_errors {
+        teller.set_ignore_errors(true);
+    }
+    teller.on_warning(Box::new(|warning| eprintln!("warning: {warning}")));
+    progress::install(&mut teller, args.quiet);
+    teller.on_secrets(Box::new(logs::keep_out));
+    let journal = Journal::for_config(&config_path);
+    // values are only kept, encrypted, when asked for
+    let undo = args.undo
+        || matches!(args.command, Commands::Undo { .. })
+        || config.history.as_ref().is_some_and(|history| history.undo);
+    if undo {
+        teller.set_undo(Undo::for_journal(&journal, credentials::undo_identity));
+    }
+    teller.set_history(journal);
     Ok(teller)
 }
 
+/// Load the variables of the nearest configuration into the shell running
+/// the hook, when it is not the one loaded already, see [`shell_hook`]
+async fn export_to_parent(args: &Cli, shell: shell_hook::Shell) -> eyre::Result<Response> {
+    let config = match &args.config {
+        Some(config) => Some(PathBuf::from(config)),
+        None => find_file_upwards(env::current_dir()?.as_path(), DEFAULT_FILE_PATH)?,
+    };
+    let config = config.map(|config| config.canonicalize().unwrap_or(config));
+    let stamp = config.as_deref().and_then(shell_hook::stamp);
+    let loaded = shell_hook::Loaded::from_env();
+    if stamp == loaded.stamp {
+        return Response::ok();
+    }
+
+    let mut out = String::new();
+    if loaded.stamp.is_some() {
+        out.push_str(&shell_hook::unload(shell, &loaded));
+        eprintln!("teller: unloaded {} variable(s)", loaded.keys.len());
+    }
+    if let (Some(config), Some(stamp)) = (config, stamp) {
+        let mut args = args.clone();
+        args.config = Some(config.to_string_lossy().to_string());
+        args.quiet = true;
+        let kvs = match load_teller(&args).await {
+            Ok(teller) => teller.exported(&shell.format()).await.map_err(Into::into),
+            Err(err) => Err(err),
+        };
+        // recorded as loaded even when failing, not to fail again on every
+        // prompt until the configuration changes
+        let kvs = kvs.unwrap_or_else(|err| {
+            eprintln!("teller: cannot load {}: {err}", config.display());
+            vec![]
+        });
+        let (code, invalid) = shell_hook::load(shell, &stamp, &kvs)?;
+        for key in &invalid {
+            eprintln!("teller: '{key}' is not a valid variable name, not loaded");
+        }
+        out.push_str(&code);
+        eprintln!(
+            "teller: loaded {} variable(s) from {}",
+            kvs.len() - invalid.len(),
+            config.display()
+        );
+    }
+    Response::ok_with_message(out)
+}
+
+/// Have the CI mask values in its logs, before the command can print them
+fn ci_mask(ci: Ci, kvs: &[KV]) {
+    let mut stdout = std::io::stdout().lock();
+    for kv in kvs {
+        for command in ci.mask(kv.value.expose()).unwrap_or_default() {
+            let _ = writeln!(stdout, "{command}");
+        }
+    }
+    let _ = stdout.flush();
+}
+
+/// Keys of a map, for confirming destructive operations
+async fn present_keys(provider: &(dyn Provider + Send + Sync), pm: &PathMap) -> String {
+    match provider.get(pm).await {
+        Ok(kvs) if kvs.is_empty() => "no keys".to_string(),
+        Ok(kvs) => kvs
+            .iter()
+            .map(|kv| kv.from_key.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        Err(err) => format!("keys unknown ({err})"),
+    }
+}
+
+/// Variables of env files, later files overriding earlier ones
+async fn load_env_files(paths: &[PathBuf]) -> eyre::Result<Vec<(String, String)>> {
+    let dotenv = Dotenv::new("env-file", None)?;
+    let mut env = vec![];
+    for path in paths {
+        let kvs = dotenv
+            .get(&PathMap::from_path(&path.to_string_lossy()))
+            .await
+            .map_err(|err| eyre!("cannot read env file {}: {err}", path.display()))?;
+        env.extend(
+            kvs.into_iter()
+                .map(|kv| (kv.key.clone(), kv.value.expose().to_string())),
+        );
+    }
+    Ok(env)
+}
+
 /// Run the CLI logic
 ///
 /// # Errors
@@ -231,74 +1090,369 @@ pub async fn run(args: &Cli) -> eyre::Result<Response> {
         Commands::Run {
             reset,
             shell,
+            stdin_format,
+            no_env,
+            keep_env,
+            drop_env,
+            cwd,
+            also_env_file,
+            ssh_key,
+            ssh_identity_files,
+            no_ci_mask,
             command,
         } => {
-            let teller = load_teller(args.config.clone()).await?;
-            let pwd = std::env::current_dir()?;
+            let mut teller = load_teller(args).await?;
+            match Ci::detect() {
+                Some(ci) if no_ci_mask => tracing::debug!(%ci, "not masking values"),
+                Some(ci) if ci.can_mask() => teller.on_secrets(Box::new(move |kvs| {
+                    logs::keep_out(kvs);
+                    ci_mask(ci, kvs);
+                })),
+                Some(ci) => eprintln!(
+                    "warning: {ci} cannot mask values while a job runs, mark variables as masked \
+                     in the project's settings or pipe output through `teller redact`"
+                ),
+                None => {}
+            }
+            let pwd = cwd.map_or_else(std::env::current_dir, Ok)?;
+            let extra_env = load_env_files(&also_env_file).await?;
             let opts = exec::Opts {
                 pwd: pwd.as_path(),
                 sh: shell,
                 reset_env: reset,
                 capture: false,
+                stdin: stdin_format.map(export::Format::from),
+                no_env: no_env || stdin_format.is_some(),
+                keep_env: &keep_env,
+                drop_env: &drop_env,
+                extra_env: &extra_env,
+                ssh_keys: &ssh_key,
+                ssh_identity_files,
             };
-            teller
-                .run(
-                    command
-                        .iter()
-                        .map(String::as_str)
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                    &opts,
-                )
-                .await?;
-            Response::ok()
+            let command = command.iter().map(String::as_str).collect::<Vec<_>>();
+            let res = match command.split_first() {
+                Some((name, args)) if teller.has_command(name) => {
+                    teller.run_command(name, args, &opts).await
+                }
+                _ => teller.run(&command, &opts).await,
+            };
+            match res {
+                Ok(_) => Response::ok(),
+                // the command told what went wrong, only its code is passed on
+                Err(teller_core::Error::Child(status)) => Response::fail_with(child_code(status)),
+                Err(err) => Err(err.into()),
+            }
         }
-        Commands::Scan(cmdargs) => {
-            let teller = load_teller(args.config.clone()).await?;
-            scan::run(&teller, &cmdargs).await
-        }
-        Commands::Export { format } => {
-            let teller_format = match format {
-                Format::CSV => export::Format::CSV,
-                Format::YAML => export::Format::YAML,
-                Format::JSON => export::Format::JSON,
-                Format::ENV => export::Format::ENV,
+        Commands::Compose {
+            cwd,
+            args: compose_args,
+        } => {
+            let teller = load_teller(args).await?;
+            let pwd = cwd.map_or_else(std::env::current_dir, Ok)?;
+            let opts = exec::Opts {
+                pwd: pwd.as_path(),
+                sh: false,
+                reset_env: false,
+                capture: false,
+                stdin: None,
+                // values only go to the env file
+                no_env: true,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
             };
-            let teller = load_teller(args.config.clone()).await?;
-            let out = teller.export(&teller_format).await?;
-            Response::ok_with_message(out)
+            let compose_args = compose_args.iter().map(String::as_str).collect::<Vec<_>>();
+            match teller.compose(&compose_args, &opts).await {
+                Ok(_) => Response::ok(),
+                Err(teller_core::Error::Child(status)) => Response::fail_with(child_code(status)),
+                Err(err) => Err(err.into()),
+            }
         }
-        Commands::Redact { in_file, out } => {
-            let teller = load_teller(args.config.clone()).await?;
-            teller
-                .redact(&mut or_stdin(in_file)?, &mut or_stdout(out)?)
-                .await?;
+        Commands::Provision {
+            tool,
+            registry,
+            key,
+            out,
+            force,
+            command,
+        } => {
+            let tool = provision::Tool::from(tool);
+            let path = out.unwrap_or_else(|| tool.default_path());
+            let provision_opts = provision::Opts { registry, key };
+            let teller = load_teller(args).await?;
+            if command.is_empty() {
+                if path.exists() && !force {
+                    return Err(eyre!(
+                        "{} exists, pass --force to overwrite it",
+                        path.display()
+                    ));
+                }
+                let content = teller.provision_file(tool, &provision_opts).await?;
+                provision::write(&path, &content)?;
+                eprintln!("wrote {}", path.display());
+                if let Some(name) = tool.config_env() {
+                    eprintln!("point {tool} at it with {name}={}", path.display());
+                }
+                return Response::ok();
+            }
+            let pwd = std::env::current_dir()?;
+            let opts = exec::Opts {
+                pwd: pwd.as_path(),
+                sh: false,
+                reset_env: false,
+                capture: false,
+                stdin: None,
+                // values only go to the credential file
+                no_env: true,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
+            };
+            let command = command.iter().map(String::as_str).collect::<Vec<_>>();
+            match teller
+                .provision(tool, &path, &provision_opts, &command, &opts)
+                .await
+            {
+                Ok(_) => Response::ok(),
+                Err(teller_core::Error::Child(status)) => Response::fail_with(child_code(status)),
+                Err(err) => Err(err.into()),
+            }
+        }
+        Commands::Scan(cmdargs) => {
+            let res = async {
+                let teller = load_teller(args).await?;
+                scan::run(&teller, &cmdargs, args.quiet).await
+            }
+            .await;
+            res.or_else(|err| scan::error(&err))
+        }
+        Commands::Export {
+            format,
+            out,
+            output,
+        } => {
+            let teller_format = export::Format::from(format);
+            let teller = load_teller(args).await?;
+            let exported = teller.export(&teller_format).await?;
+            if out.is_none() {
+                return Response::ok_with_message(exported);
+            }
+            let mut out = or_stdout(out, &output)?;
+            writeln!(out, "{exported}")?;
+            out.finish()?;
             Response::ok()
         }
-        Commands::Template { in_file, out } => {
-            let mut input = String::new();
-            or_stdin(in_file)?.read_to_string(&mut input)?;
-            let teller = load_teller(args.config.clone()).await?;
-            let rendered = teller.template(&input).await?;
-            let mut out = or_stdout(out)?;
-            out.write_all(rendered.as_bytes())?;
-            out.flush()?;
+        Commands::Redact {
+            in_file,
+            watch,
+            from_start,
+            out,
+            output,
+            mode,
+            token_key,
+            skip_encoded,
+            json,
+            fields,
+        } => {
+            let mode = match mode {
+                RedactMode::Fixed => redact::Mode::Fixed,
+                RedactMode::Token => redact::Mode::Token {
+                    key: token_key
+                        .ok_or_eyre(
+                            "token mode requires a key, set '--token-key' or TELLER_REDACT_KEY",
+                        )?
+                        .into_bytes(),
+                },
+            };
+            let opts = redact::Opts {
+                mode,
+                encoded: !skip_encoded,
+                json_lines: json,
+                fields,
+                ..redact::Opts::default()
+            };
+            let teller = load_teller(args).await?;
+            if watch.is_empty() {
+                let mut out = or_stdout(out, &output)?;
+                teller
+                    .redact(&mut or_stdin(in_file)?, &mut out, &opts)
+                    .await?;
+                out.finish()?;
+            } else {
+                let mut out = or_stdout_streaming(out, &output)?;
+                teller
+                    .redact_watch(&watch, &mut out, &opts, from_start)
+                    .await?;
+                out.finish()?;
+            }
+            Response::ok()
+        }
+        Commands::Template {
+            in_file,
+            out,
+            output,
+            in_place,
+            in_dir,
+            out_dir,
+            strict,
+            lenient,
+            engine,
+        } => {
+            let engine = match engine {
+                Some(TemplateEngine::Tera) => template::Engine::Tera,
+                Some(TemplateEngine::Handlebars) => template::Engine::Handlebars,
+                Some(TemplateEngine::Envsubst) => template::Engine::Envsubst,
+                None => in_file
+                    .as_deref()
+                    .and_then(|f| template::Engine::from_path(Path::new(f)))
+                    .unwrap_or_default(),
+            };
+            let opts = template::Opts {
+                engine,
+                mode: if strict {
+                    template::Mode::Strict
+                } else if lenient {
+                    template::Mode::Lenient
+                } else {
+                    template::Mode::Default
+                },
+            };
+            let teller = load_teller(args).await?;
+            let missing = if let (Some(in_dir), Some(out_dir)) = (in_dir, out_dir) {
+                let (rendered, missing) = teller.template_dir(&in_dir, &out_dir, &opts).await?;
+                eprintln!("rendered {rendered} template(s) into {}", out_dir.display());
+                missing
+            } else {
+                let mut input = String::new();
+                or_stdin(in_file)?.read_to_string(&mut input)?;
+                if let Some(path) = in_place {
+                    teller.template_in_place(&input, &path, &opts).await?
+                } else {
+                    let rendered = teller.template(&input, &opts).await?;
+                    let mut out = or_stdout(out, &output)?;
+                    out.write_all(rendered.output.as_bytes())?;
+                    out.finish()?;
+                    rendered.missing
+                }
+            };
+            for key in missing {
+                eprintln!("warning: key '{key}' not found, rendered as empty");
+            }
             Response::ok()
         }
-        Commands::Env {} => {
-            let teller = load_teller(args.config.clone()).await?;
+        Commands::Env {
+            export_to_parent: Some(shell),
+        } => export_to_parent(args, shell).await,
+        Commands::Env {
+            export_to_parent: None,
+        } => {
+            let teller = load_teller(args).await?;
             let out = teller.export(&export::Format::ENV).await?;
             Response::ok_with_message(out)
         }
+        Commands::Hook { shell } => Response::ok_with_message(shell.hook(&env::current_exe()?)),
         Commands::New(new_args) => new::run(&new_args),
-        Commands::Show {} => {
-            let teller = load_teller(args.config.clone()).await?;
-            let kvs = teller.collect().await?;
-            io::print_kvs(&kvs);
+        Commands::Show { json } => {
+            let teller = load_teller(args).await?;
+            let collected = teller.collect_detailed().await;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&collected)?);
+            } else {
+                io::print_kvs(&collected.kvs(), &teller.mask());
+            }
+            for (provider, map, warning) in collected.warnings() {
+                eprintln!("warning: [{provider}] {} ({}): {warning}", map.id, map.path);
+            }
+            for duplicate in &collected.duplicates {
+                eprintln!("warning: {duplicate}");
+            }
+            // the code of the first map that failed, as if it failed the command
+            let mut failed = None;
+            for (provider, map, error) in collected.errors() {
+                if map.optional || teller.ignores_errors() {
+                    eprintln!("warning: [{provider}] {} ({}): {error}", map.id, map.path);
+                } else {
+                    eprintln!("error: [{provider}] {} ({}): {error}", map.id, map.path);
+                    failed = failed.or_else(|| {
+                        Some(
+                            map.cause
+                                .as_deref()
+                                .and_then(provider_code)
+                                .unwrap_or(exit::FAILURE),
+                        )
+                    });
+                }
+            }
+            match failed {
+                None => Response::ok(),
+                Some(code) if collected.kvs().is_empty() => Response::fail_with(code),
+                Some(_) => Response::fail_with(exit::PARTIAL),
+            }
+        }
+        Commands::Stats { json, maps } => {
+            let teller = load_teller(args).await?;
+            let stats = teller.stats().await;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                for provider in &stats {
+                    println!("{}", provider.summary());
+                    if maps {
+                        for map in &provider.maps {
+                            println!("  {}", map.summary());
+                        }
+                    }
+                }
+            }
             Response::ok()
         }
+        Commands::Search {
+            pattern,
+            regex,
+            values,
+            json,
+        } => {
+            let teller = load_teller(args).await?;
+            let hits = teller
+                .search(&pattern, &search::Opts { regex, values })
+                .await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&hits)?);
+            } else {
+                for hit in &hits {
+                    println!("{hit}");
+                }
+            }
+            if hits.is_empty() {
+                eprintln!("no keys matching '{pattern}'");
+                Response::fail()
+            } else {
+                Response::ok()
+            }
+        }
+        Commands::Verify { json } => {
+            let teller = load_teller(args).await?;
+            let violations = teller.verify().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&violations)?);
+            } else {
+                for violation in &violations {
+                    println!("{violation}");
+                }
+            }
+            eprintln!("found {} violation(s)", violations.len());
+            if violations.is_empty() {
+                Response::ok()
+            } else {
+                Response::fail()
+            }
+        }
         Commands::Sh {} => {
-            let teller = load_teller(args.config.clone()).await?;
+            let teller = load_teller(args).await?;
             let out = teller.export(&export::Format::Shell).await?;
             Response::ok_with_message(out)
         }
@@ -306,59 +1460,488 @@ pub async fn run(args: &Cli) -> eyre::Result<Response> {
             kvs,
             map_id,
             providers,
+            from_cmd,
+            generate,
+            policy,
+            interactive,
+            multiline,
         } => {
-            let kvs = kvs
+            let mut kvs = kvs
                 .iter()
-                .map(|(k, v)| KV::from_kv(k, v))
-                .collect::<Vec<_>>();
-            let teller = load_teller(args.config.clone()).await?;
+                .map(|(k, v)| Ok(KV::from_kv(k, &put_value(k, v)?)))
+                .collect::<eyre::Result<Vec<_>>>()?;
+            for (key, command) in &from_cmd {
+                kvs.push(KV::from_kv(key, &command_value(key, command)?));
+            }
+            for key in &interactive {
+                kvs.push(KV::from_kv(key, &io::prompt_value(key, multiline)?));
+            }
+            let teller = load_teller(args).await?;
+            kvs.extend(teller.generate(&generate, policy.as_ref(), &map_id, &providers)?);
             teller
                 .put(kvs.as_slice(), map_id.as_str(), providers.as_slice())
                 .await?;
+            if !generate.is_empty() {
+                eprintln!("generated {}", generate.join(", "));
+            }
             Response::ok()
         }
         Commands::Delete {
             map_id,
             providers,
             keys,
+            yes,
         } => {
-            let teller = load_teller(args.config.clone()).await?;
+            let teller = load_teller(args).await?;
+            let providers = teller.resolve_providers(&providers, &map_id)?;
+            let mut summary = vec![];
+            for provider_name in &providers {
+                let (provider, pm) = teller.get_pathmap_on_provider(&map_id, provider_name)?;
+                let deleted = if keys.is_empty() {
+                    present_keys(provider.as_ref(), pm).await
+                } else {
+                    keys.join(", ")
+                };
+                summary.push(format!(
+                    "  {provider_name}/{map_id} ({}): {deleted}",
+                    pm.path
+                ));
+            }
+            let what = if keys.is_empty() { "all keys" } else { "keys" };
+            if !io::confirm(&format!("deleting {what} of:\n{}", summary.join("\n")), yes)? {
+                return Response::fail();
+            }
             teller
                 .delete(keys.as_slice(), &map_id, providers.as_slice())
                 .await?;
             Response::ok()
         }
-        Commands::Copy { from, to, replace } => {
-            // a copy report should state how many keys were copied and to where.
-            // invent a new kvrl (key-value resource location) format: kvurl://dotenv/?meta
-            // <provider>/<map-id> like server/resource-path
-            // <provider>?path=varbatim/path/to/location request specific path overriding resource routing
-            //
-            // dotenv/map-id -> foo/map-id: copied 4 key(s).
-            // dotenv/map-id -> f/map-id: copied 4 key(s).
-            // copied 4 key(s) [in replace mode] from `dotenv:path-id` to `foo:path-id`, `bar:path-id`
-            let teller = load_teller(args.config.clone()).await?;
-            let (from_provider, from_map_id) = from.split_once('/').ok_or_else(|| {
-                eyre!(
-                    "cannot parse '--from': '{}', did you format it as: '<provider name>/<map \
-                     id>' ?",
-                    from
-                )
-            })?;
-            for to_provider in to {
-                let (to_provider, to_map_id) = to_provider.split_once('/').ok_or_else(|| {
-                    eyre!(
-                        "cannot parse '--to': '{}', did you format it as: '<provider name>/<map \
-                         id>' ?",
-                        to_provider
-                    )
-                })?;
-                teller
-                    .copy(from_provider, from_map_id, to_provider, to_map_id, replace)
-                    .await?;
+        Commands::Copy {
+            from,
+            to,
+            merge,
+            overwrite: _,
+            prune,
+            replace,
+            yes,
+            json,
+        } => {
+            let teller = load_teller(args).await?;
+            let (from_provider, from_map_id) = location(&from, "--from")?;
+            let opts = copy::Opts::default()
+                .with_mode(if merge {
+                    copy::Mode::Merge
+                } else {
+                    copy::Mode::Overwrite
+                })
+                .with_prune(prune || replace);
+            if opts.prune {
+                let mut summary = vec![];
+                for to in &to {
+                    let (to_provider, to_map_id) = location(to, "--to")?;
+                    let plan = teller
+                        .copy(
+                            from_provider,
+                            from_map_id,
+                            to_provider,
+                            to_map_id,
+                            &opts.with_dry_run(true),
+                        )
+                        .await?;
+                    if !plan.pruned.is_empty() {
+                        let (_, pm) =
+                            teller.get_pathmap_on_provider(to_map_id, &to_provider.to_string())?;
+                        summary.push(format!("  {to} ({}): {}", pm.path, plan.pruned.join(", ")));
+                    }
+                }
+                if !summary.is_empty() {
+                    let summary = format!(
+                        "pruning keys absent from {from}, deleting:\n{}",
+                        summary.join("\n")
+                    );
+                    if !io::confirm(&summary, yes)? {
+                        return Response::fail();
+                    }
+                }
+            }
+            let mut reports = vec![];
+            for to in &to {
+                let (to_provider, to_map_id) = location(to, "--to")?;
+                reports.push(
+                    teller
+                        .copy(from_provider, from_map_id, to_provider, to_map_id, &opts)
+                        .await?,
+                );
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&reports)?);
+            } else {
+                for report in &reports {
+                    println!("{}", report.summary());
+                }
             }
 
             Response::ok()
         }
+        Commands::Migrate {
+            from,
+            to,
+            path,
+            out,
+        } => {
+            let teller = load_teller(args).await?;
+            let opts = migrate::Opts {
+                paths: path.into_iter().collect(),
+            };
+            let migration = teller.migrate(&from, &to, &opts).await?;
+            for map in &migration.maps {
+                eprintln!(
+                    "migrated map '{}' ({} -> {}): {} key(s)",
+                    map.map_id, map.from_path, map.to_path, map.keys
+                );
+            }
+            let config = serde_yaml::to_string(&migration.config)?;
+            if let Some(out) = out {
+                fs_err::write(&out, config)?;
+                eprintln!("wrote configuration to {}", out.display());
+                Response::ok()
+            } else {
+                Response::ok_with_message(config)
+            }
+        }
+        Commands::Drift { maps, json } => {
+            let (from, to) = maps.locations()?;
+            let teller = load_teller(args).await?;
+            let reports = drift::check(&teller, &from, &to).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&reports)?);
+            } else {
+                for report in &reports {
+                    println!("{}", report.summary());
+                }
+            }
+            if reports.iter().all(teller_core::drift::Report::is_empty) {
+                Response::ok()
+            } else {
+                Response::fail()
+            }
+        }
+        Commands::WatchDrift {
+            maps,
+            interval,
+            webhook,
+            sync,
+        } => {
+            let (from, to) = maps.locations()?;
+            let teller = load_teller(args).await?;
+            drift::watch(
+                &teller,
+                &drift::Opts {
+                    from,
+                    to,
+                    interval,
+                    webhook,
+                    sync,
+                },
+            )
+            .await?;
+            Response::ok()
+        }
+        Commands::Serve {
+            listen,
+            token,
+            redact,
+        } => {
+            let teller = load_teller(args).await?;
+            serve::serve(
+                teller,
+                serve::Opts {
+                    addr: listen,
+                    token,
+                    redact,
+                },
+            )
+            .await?;
+            Response::ok()
+        }
+        Commands::K8sInject {
+            listen,
+            tls_cert,
+            tls_key,
+            image,
+            binary,
+        } => {
+            k8s::serve(k8s::Opts {
+                addr: listen,
+                tls_cert,
+                tls_key,
+                injector: k8s::Injector { image, binary },
+            })
+            .await?;
+            Response::ok()
+        }
+        Commands::Ui {} => {
+            let teller = load_teller(args).await?;
+            ui::run(&teller).await?;
+            Response::ok()
+        }
+        Commands::Mcp {} => {
+            let teller = load_teller(args).await?;
+            mcp::Server::new(teller).serve_stdio().await?;
+            Response::ok()
+        }
+        Commands::Snapshot(SnapshotCommand::Save { file, recipient }) => {
+            let teller = load_teller(args).await?;
+            let encryption = if recipient.is_empty() {
+                Encryption::Passphrase(io::passphrase(PASSPHRASE_ENV, true)?)
+            } else {
+                Encryption::Recipients(recipient)
+            };
+            let snapshot = teller.snapshot().await?;
+            io::write_private(&file, &snapshot.encrypt(&encryption)?)?;
+            eprintln!(
+                "saved {} key(s) from {} map(s) to {}",
+                snapshot.len(),
+                snapshot.maps.len(),
+                file.display()
+            );
+            Response::ok()
+        }
+        Commands::Snapshot(SnapshotCommand::Restore {
+            file,
+            identity,
+            replace,
+        }) => {
+            let teller = load_teller(args).await?;
+            let decryption = match identity {
+                Some(identity) => Decryption::IdentityFile(identity),
+                None => Decryption::Passphrase(io::passphrase(PASSPHRASE_ENV, false)?),
+            };
+            let snapshot = Snapshot::decrypt(&fs_err::read(&file)?, &decryption)?;
+            teller.restore(&snapshot, replace).await?;
+            eprintln!(
+                "restored {} key(s) to {} map(s) from {}",
+                snapshot.len(),
+                snapshot.maps.len(),
+                file.display()
+            );
+            Response::ok()
+        }
+        Commands::Config(ConfigCommand::Encrypt { recipient }) => {
+            let value = if std::io::stdin().is_terminal() {
+                dialoguer::Password::new().with_prompt("Value").interact()?
+            } else {
+                let mut value = String::new();
+                std::io::stdin().read_to_string(&mut value)?;
+                value.trim_end_matches('\n').to_string()
+            };
+            let encrypted = encrypted::encrypt(&value, &recipient)?;
+            Response::ok_with_message(format!("!{} {encrypted}", encrypted::TAG))
+        }
+        Commands::Config(ConfigCommand::Decrypt { identity }) => {
+            let text = fs_err::read_to_string(config_path(args)?)?;
+            let mut value: serde_yaml::Value = serde_yaml::from_str(&text)?;
+            encrypted::decrypt_tagged(&mut value, &encrypted::identity_file(&identity)?)?;
+            Response::ok_with_message(serde_yaml::to_string(&value)?)
+        }
+        Commands::Generate(GenerateCommand::ExampleEnv {
+            placeholder,
+            offline,
+            out,
+            output,
+        }) => {
+            let teller = load_teller(args).await?;
+            let example = teller
+                .example_env(&example::Opts {
+                    placeholder,
+                    fetch: !offline,
+                })
+                .await?;
+            let mut out = or_stdout(out, &output)?;
+            write!(out, "{example}")?;
+            out.finish()?;
+            Response::ok()
+        }
+        Commands::GitCredential {
+            map,
+            read_only,
+            action,
+        } => {
+            if read_only && action != GitCredentialAction::Get {
+                return Response::ok();
+            }
+            let (provider, map_id) = location(&map, "--map")?;
+            let mut request = String::new();
+            std::io::stdin().read_to_string(&mut request)?;
+            let teller = load_teller(args).await?;
+            let found = teller
+                .git_credential(
+                    action.into(),
+                    provider,
+                    map_id,
+                    &git_credential::Credential::parse(&request),
+                )
+                .await?;
+            if let Some(found) = found {
+                print!("{}", found.to_text());
+            }
+            Response::ok()
+        }
+        Commands::DockerCredentialHelper { map, action } => {
+            let (provider, map_id) = location(&map, "--map")?;
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            let teller = load_teller(args).await?;
+            match teller
+                .docker_credential(action.into(), provider, map_id, &input)
+                .await
+            {
+                Ok(output) if output.is_empty() => Response::ok(),
+                Ok(output) => Response::ok_with_message(output),
+                // docker reads this on stdout
+                Err(teller_core::Error::NotFound(message)) => {
+                    println!("{message}");
+                    Response::fail()
+                }
+                Err(err) => Err(err.into()),
+            }
+        }
+        Commands::Login(login_args) => {
+            let config = load_config(&config_path(args)?)?;
+            eprintln!("{}", login::run(&config, &login_args).await?);
+            Response::ok()
+        }
+        Commands::History { json, verify } => {
+            let journal = Journal::for_config(&config_path(args)?);
+            if verify {
+                let count = journal.verify()?;
+                eprintln!("{count} entries, history is intact");
+                return Response::ok();
+            }
+            let entries = journal.entries()?;
+            if entries.is_empty() && !json {
+                eprintln!("no changes recorded in {}", journal.path().display());
+                return Response::ok();
+            }
+            let lines = entries
+                .iter()
+                .map(|entry| {
+                    if json {
+                        return Ok(serde_json::to_string(entry)?);
+                    }
+                    let record = &entry.record;
+                    let mut line = format!(
+                        "{:>4}  {}  {:<8} {}",
+                        entry.seq,
+                        humantime::format_rfc3339_seconds(
+                            std::time::UNIX_EPOCH + std::time::Duration::from_secs(entry.time)
+                        ),
+                        record.operation.to_string(),
+                        record.maps.join(", ")
+                    );
+                    if !record.keys.is_empty() {
+                        line = format!("{line}: {}", record.keys.join(", "));
+                    }
+                    if let Some(source) = &record.source {
+                        line = format!("{line} (from {source})");
+                    }
+                    Ok(line)
+                })
+                .collect::<eyre::Result<Vec<_>>>()?;
+            Response::ok_with_message(lines.join("\n"))
+        }
+        Commands::Undo { yes } => {
+            let teller = load_teller(args).await?;
+            let Some(snapshot) = teller.undoable()? else {
+                eprintln!("nothing to undo");
+                return Response::ok();
+            };
+            let mut summary = "undoing the last change, putting back:".to_string();
+            for map in &snapshot.maps {
+                let keys = if map.kvs.is_empty() {
+                    "no keys".to_string()
+                } else {
+                    map.kvs.keys().cloned().collect::<Vec<_>>().join(", ")
+                };
+                summary = format!(
+                    "{summary}\n  {}/{} ({}): {keys}",
+                    map.provider, map.map_id, map.path
+                );
+            }
+            if !io::confirm(&summary, yes)? {
+                return Response::fail();
+            }
+            teller.undo(&snapshot).await?;
+            eprintln!(
+                "undone, {} key(s) of {} map(s) put back",
+                snapshot.len(),
+                snapshot.maps.len()
+            );
+            Response::ok()
+        }
+        Commands::Logout { provider } => {
+            if credentials::remove(&provider)? {
+                eprintln!("credentials for {provider} removed");
+            } else {
+                eprintln!("no credentials stored for {provider}");
+            }
+            Response::ok()
+        }
+        #[cfg(unix)]
+        Commands::Agent(AgentCommand::Serve {
+            socket,
+            ttl,
+            token_file,
+        }) => {
+            let teller = load_teller(args).await?;
+            let token = agent::generate_token()?;
+            let token_file = token_file.unwrap_or_else(|| token_path(&socket));
+            io::write_private(&token_file, token.as_bytes())?;
+            eprintln!(
+                "serving on {} (token in {})",
+                socket.display(),
+                token_file.display()
+            );
+            let agent = agent::Agent::new(
+                teller,
+                agent::Opts {
+                    socket: socket.clone(),
+                    token,
+                    ttl: std::time::Duration::from_secs(ttl),
+                },
+            );
+            let res = tokio::select! {
+                res = agent.serve() => res,
+                _ = tokio::signal::ctrl_c() => Ok(()),
+            };
+            let _ = fs_err::remove_file(&socket);
+            let _ = fs_err::remove_file(&token_file);
+            res?;
+            Response::ok()
+        }
+        #[cfg(unix)]
+        Commands::Agent(AgentCommand::Get {
+            socket,
+            token,
+            refresh,
+            keys,
+        }) => {
+            let token = match token {
+                Some(token) => token,
+                None => fs_err::read_to_string(token_path(&socket))?
+                    .trim()
+                    .to_string(),
+            };
+            let kvs = agent::request(
+                &socket,
+                &agent::Request {
+                    token,
+                    keys,
+                    refresh,
+                },
+            )
+            .await?;
+            Response::ok_with_message(export::Format::ENV.export(&kvs)?)
+        }
     }
 }
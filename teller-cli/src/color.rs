@@ -0,0 +1,76 @@
+//! Whether output is colored, as asked by `--color`.
+//!
+//! By default only terminals get colors, and never when
+//! [`NO_COLOR`](https://no-color.org) is set or `TERM` is `dumb`, so CI logs
+//! and files stay free of ANSI codes.
+
+use std::io::{self, IsTerminal};
+
+use clap::ValueEnum;
+
+/// Disables colors when set to anything but an empty string
+pub const NO_COLOR: &str = "NO_COLOR";
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color terminals, unless `NO_COLOR` is set or `TERM` is `dumb`
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color stdout
+    #[must_use]
+    pub fn stdout(self) -> bool {
+        self.enabled(io::stdout().is_terminal(), |name| std::env::var(name).ok())
+    }
+
+    /// Whether to color stderr, where prompts, spinners and logs go
+    #[must_use]
+    pub fn stderr(self) -> bool {
+        self.enabled(io::stderr().is_terminal(), |name| std::env::var(name).ok())
+    }
+
+    /// Color prompts, spinners and styled text accordingly
+    pub fn apply(self) {
+        console::set_colors_enabled(self.stdout());
+        console::set_colors_enabled_stderr(self.stderr());
+    }
+
+    fn enabled(self, is_terminal: bool, var: impl Fn(&str) -> Option<String>) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                is_terminal
+                    && var(NO_COLOR).is_none_or(|value| value.is_empty())
+                    && var("TERM").is_none_or(|term| term != "dumb")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choices() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| (*value).to_string())
+            }
+        };
+        assert!(ColorChoice::Auto.enabled(true, env(&[("TERM", "xterm")])));
+        assert!(!ColorChoice::Auto.enabled(false, env(&[])));
+        assert!(!ColorChoice::Auto.enabled(true, env(&[(NO_COLOR, "1")])));
+        assert!(ColorChoice::Auto.enabled(true, env(&[(NO_COLOR, "")])));
+        assert!(!ColorChoice::Auto.enabled(true, env(&[("TERM", "dumb")])));
+        assert!(ColorChoice::Always.enabled(false, env(&[(NO_COLOR, "1")])));
+        assert!(!ColorChoice::Never.enabled(true, env(&[])));
+    }
+}
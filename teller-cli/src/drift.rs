@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use eyre::Result;
+use teller_core::{copy, drift, teller::Teller};
+use teller_providers::Error as ProviderError;
+
+/// A map, by provider name and map id
+pub type Location = (String, String);
+
+pub struct Opts {
+    pub from: Location,
+    pub to: Vec<Location>,
+    /// time between checks
+    pub interval: Duration,
+    /// Slack compatible webhook notified when drift appears
+    pub webhook: Option<String>,
+    /// copy the source onto targets that drifted
+    pub sync: bool,
+}
+
+/// Compare every target map to the source
+///
+/// # Errors
+///
+/// This function will return an error if a map cannot be fetched
+pub async fn check(
+    teller: &Teller,
+    from: &Location,
+    to: &[Location],
+) -> Result<Vec<drift::Report>> {
+    let mut reports = vec![];
+    for (to_provider, to_map_id) in to {
+        reports.push(
+            teller
+                .drift(&from.0, &from.1, to_provider, to_map_id)
+                .await?,
+        );
+    }
+    Ok(reports)
+}
+
+/// Check for drift every interval until the process is stopped, and right
+/// away when the source changes if its provider can watch for changes
+///
+/// The webhook is notified when drift appears or changes, not again while it
+/// stays the same. Failing checks are printed and retried at the next interval.
+///
+/// # Errors
+///
+/// This function will return an error if waiting for a signal fails
+pub async fn watch(teller: &Teller, opts: &Opts) -> Result<()> {
+    let mut notified: Vec<drift::Report> = vec![];
+    loop {
+        match watch_once(teller, opts).await {
+            Ok(drifted) => {
+                if !drifted.is_empty() && drifted != notified {
+                    if let Some(url) = &opts.webhook {
+                        if let Err(err) = drift::notify(url, &drifted, opts.sync) {
+                            eprintln!("warning: {err}");
+                        }
+                    }
+                }
+                notified = drifted;
+            }
+            Err(err) => eprintln!("error: {err}"),
+        }
+        tokio::select! {
+            () = tokio::time::sleep(opts.interval) => {}
+            () = source_changed(teller, &opts.from) => eprintln!("source changed"),
+            res = tokio::signal::ctrl_c() => return Ok(res?),
+        }
+    }
+}
+
+/// Resolves when the source map changes, never if its provider cannot watch
+async fn source_changed(teller: &Teller, from: &Location) {
+    match teller.watch(&from.0, &from.1).await {
+        Ok(()) => return,
+        Err(teller_core::Error::Provider(ProviderError::Unsupported(_))) => {}
+        Err(err) => eprintln!("warning: cannot watch {}/{}: {err}", from.0, from.1),
+    }
+    std::future::pending::<()>().await;
+}
+
+/// Check once, syncing drifted targets if asked to, and return the reports
+/// with drift
+async fn watch_once(teller: &Teller, opts: &Opts) -> Result<Vec<drift::Report>> {
+    let reports = check(teller, &opts.from, &opts.to).await?;
+    let mut drifted = vec![];
+    for ((to_provider, to_map_id), report) in opts.to.iter().zip(reports) {
+        if report.is_empty() {
+            continue;
+        }
+        eprintln!("drift: {}", report.summary());
+        // syncing does not prune extra keys, only sync when it fixes something
+        if opts.sync && report.drifts.iter().any(|d| d.kind != drift::Kind::Extra) {
+            teller
+                .copy(
+                    &opts.from.0,
+                    &opts.from.1,
+                    to_provider,
+                    to_map_id,
+                    &copy::Opts::default(),
+                )
+                .await?;
+            eprintln!("synced {to_provider}/{to_map_id}");
+        }
+        drifted.push(report);
+    }
+    if drifted.is_empty() {
+        eprintln!("no drift");
+    }
+    Ok(drifted)
+}
@@ -1,8 +1,12 @@
-use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
+use clap::{Args, ValueEnum};
 use eyre::Result;
 use fs_err::File;
+use teller_core::mask::Mask;
 use teller_providers::config::KV;
+use tempfile::NamedTempFile;
 
 /// Read from a file or stdin
 ///
@@ -18,28 +22,312 @@ pub fn or_stdin(file: Option<String>) -> Result<Box<dyn BufRead>> {
     Ok(out)
 }
 
-/// Write to a file or stdout
+/// How an output file is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum WriteMode {
+    /// Replace the file, or create it
+    #[default]
+    Truncate,
+    /// Create the file, failing if it exists
+    Create,
+    /// Append to the file, or create it
+    Append,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct OutputArgs {
+    /// How to write the output file
+    #[arg(long, value_enum, default_value_t = WriteMode::Truncate)]
+    pub write_mode: WriteMode,
+    /// Permissions of the output file, in octal (on Unix)
+    #[arg(long, default_value = "600", value_parser = parse_permissions)]
+    pub permissions: u32,
+}
+
+impl Default for OutputArgs {
+    fn default() -> Self {
+        Self {
+            write_mode: WriteMode::Truncate,
+            permissions: 0o600,
+        }
+    }
+}
+
+fn parse_permissions(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("'{mode}' is not an octal mode, such as 600"))
+}
+
+/// Where output goes, see [`or_stdout`]
+pub enum Output {
+    Stdout(BufWriter<io::Stdout>),
+    /// written as it goes
+    File(BufWriter<fs_err::File>),
+    /// written to a temporary file, renamed over the destination once finished
+    Atomic {
+        file: BufWriter<NamedTempFile>,
+        path: PathBuf,
+        no_clobber: bool,
+    },
+}
+
+impl Output {
+    /// Flush, and move an atomically written file in place. Dropping the output
+    /// instead leaves the destination untouched.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if IO fails, or in `create` mode the
+    /// file was created meanwhile
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Self::Stdout(mut out) => out.flush()?,
+            Self::File(mut out) => out.flush()?,
+            Self::Atomic {
+                file,
+                path,
+                no_clobber,
+            } => {
+                let file = file.into_inner().map_err(io::IntoInnerError::into_error)?;
+                file.as_file().sync_all()?;
+                if no_clobber {
+                    file.persist_noclobber(&path)
+                } else {
+                    file.persist(&path)
+                }
+                .map_err(|err| eyre::eyre!("cannot write {}: {}", path.display(), err.error))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(out) => out.write(buf),
+            Self::File(out) => out.write(buf),
+            Self::Atomic { file, .. } => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(out) => out.flush(),
+            Self::File(out) => out.flush(),
+            Self::Atomic { file, .. } => file.flush(),
+        }
+    }
+}
+
+/// Write to a file or stdout. Replaced or created files are written to a
+/// temporary file first, and only take its place with [`Output::finish`].
+///
+/// # Errors
+///
+/// This function will return an error if IO fails, or in `create` mode the
+/// file exists
+pub fn or_stdout(file: Option<String>, opts: &OutputArgs) -> Result<Output> {
+    let Some(path) = file.map(PathBuf::from) else {
+        return Ok(Output::Stdout(BufWriter::new(io::stdout())));
+    };
+    if opts.write_mode == WriteMode::Append {
+        return open_direct(&path, opts);
+    }
+    if opts.write_mode == WriteMode::Create && path.exists() {
+        return Err(eyre::eyre!("{} already exists", path.display()));
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file = tempfile::Builder::new()
+        .prefix(".teller")
+        .tempfile_in(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.as_file()
+            .set_permissions(std::fs::Permissions::from_mode(opts.permissions))?;
+    }
+    Ok(Output::Atomic {
+        file: BufWriter::new(file),
+        path,
+        no_clobber: opts.write_mode == WriteMode::Create,
+    })
+}
+
+/// Write to a file or stdout as output is produced, for output that has no
+/// end, such as following logs
+///
+/// # Errors
+///
+/// This function will return an error if IO fails, or in `create` mode the
+/// file exists
+pub fn or_stdout_streaming(file: Option<String>, opts: &OutputArgs) -> Result<Output> {
+    file.map_or_else(
+        || Ok(Output::Stdout(BufWriter::new(io::stdout()))),
+        |path| open_direct(Path::new(&path), opts),
+    )
+}
+
+fn open_direct(path: &Path, opts: &OutputArgs) -> Result<Output> {
+    let mut options = fs_err::OpenOptions::new();
+    match opts.write_mode {
+        WriteMode::Truncate => options.write(true).create(true).truncate(true),
+        WriteMode::Create => options.write(true).create_new(true),
+        WriteMode::Append => options.append(true).create(true),
+    };
+    #[cfg(unix)]
+    {
+        use fs_err::os::unix::fs::OpenOptionsExt;
+        options.mode(opts.permissions);
+    }
+    Ok(Output::File(BufWriter::new(options.open(path)?)))
+}
+
+/// Show what a destructive operation is about to do, and ask to go on, unless
+/// `yes`. Fails when there is no terminal to ask on.
+///
+/// # Errors
+///
+/// This function will return an error if not confirmed with `--yes` and stdin
+/// is not a terminal, or prompting fails
+pub fn confirm(summary: &str, yes: bool) -> Result<bool> {
+    eprintln!("{summary}");
+    if yes {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(eyre::eyre!(
+            "not a terminal to confirm on, pass --yes to go on"
+        ));
+    }
+    Ok(dialoguer::Confirm::new()
+        .with_prompt("Continue?")
+        .default(false)
+        .interact()?)
+}
+
+/// Write a file only the current user can read (on Unix)
 ///
 /// # Errors
 ///
 /// This function will return an error if IO fails
-pub fn or_stdout(file: Option<String>) -> Result<Box<dyn Write>> {
-    let out = file.map_or_else(
-        || Ok(Box::new(BufWriter::new(std::io::stdout())) as Box<dyn Write>),
-        |file_path| File::open(file_path).map(|f| Box::new(BufWriter::new(f)) as Box<dyn Write>),
-    )?;
-    Ok(out)
+pub fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    let mut options = fs_err::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use fs_err::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(data)?;
+    Ok(())
+}
+
+/// A passphrase from an environment variable, or prompted for
+///
+/// # Errors
+///
+/// This function will return an error if prompting fails
+pub fn passphrase(env: &str, confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(env) {
+        return Ok(passphrase);
+    }
+    let mut prompt = dialoguer::Password::new().with_prompt("Passphrase");
+    if confirm {
+        prompt = prompt.with_confirmation("Confirm passphrase", "Passphrases do not match");
+    }
+    Ok(prompt.interact()?)
+}
+
+/// A value typed or pasted on the terminal, without echoing it. Multi-line
+/// values end with an empty line.
+///
+/// # Errors
+///
+/// This function will return an error if stdin is not a terminal, or prompting
+/// fails
+pub fn prompt_value(key: &str, multiline: bool) -> Result<String> {
+    if !io::stdin().is_terminal() {
+        return Err(eyre::eyre!("not a terminal to prompt for '{key}' on"));
+    }
+    if !multiline {
+        return Ok(dialoguer::Password::new().with_prompt(key).interact()?);
+    }
+    eprintln!("{key} (hidden, end with an empty line):");
+    let term = console::Term::stderr();
+    let mut lines = vec![];
+    loop {
+        let line = term.read_secure_line()?;
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    Ok(lines.join("\n"))
 }
 
-pub fn print_kvs(kvs: &[KV]) {
+pub fn print_kvs(kvs: &[KV], mask: &Mask) {
     for kv in kvs {
         println!(
-            "[{}]: {} = {}***",
+            "[{}]: {} = {}",
             kv.provider
                 .as_ref()
                 .map_or_else(|| "n/a".to_string(), |p| format!("{} ({})", p.name, p.kind)),
             kv.key,
-            kv.value.get(0..2).unwrap_or_default()
+            mask.apply(kv.value.expose())
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, text: &str, write_mode: WriteMode) -> Result<()> {
+        let mut out = or_stdout(
+            Some(path.to_string_lossy().to_string()),
+            &OutputArgs {
+                write_mode,
+                ..OutputArgs::default()
+            },
+        )?;
+        out.write_all(text.as_bytes())?;
+        out.finish()
+    }
+
+    #[test]
+    fn output_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.env");
+
+        write(&path, "A=1\n", WriteMode::Create).unwrap();
+        assert!(write(&path, "A=2\n", WriteMode::Create).is_err());
+        write(&path, "B=2\n", WriteMode::Append).unwrap();
+        assert_eq!(fs_err::read_to_string(&path).unwrap(), "A=1\nB=2\n");
+
+        // the file is only replaced once finished
+        let mut out = or_stdout(
+            Some(path.to_string_lossy().to_string()),
+            &OutputArgs::default(),
+        )
+        .unwrap();
+        out.write_all(b"C=3\n").unwrap();
+        assert_eq!(fs_err::read_to_string(&path).unwrap(), "A=1\nB=2\n");
+        out.finish().unwrap();
+        assert_eq!(fs_err::read_to_string(&path).unwrap(), "C=3\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs_err::metadata(&path).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
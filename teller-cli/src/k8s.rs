@@ -0,0 +1,278 @@
+//! A Kubernetes mutating admission webhook injecting teller into pods.
+//!
+//! Pods annotated with `teller.sh/inject: "true"` get an init container copying
+//! the teller binary into a shared volume, the teller config mounted from the
+//! secret named by `teller.sh/config-secret`, and their containers' commands
+//! wrapped with `teller run`. Secrets are fetched in the pod, by its own
+//! identity, so any provider teller supports works.
+
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+use axum::{extract::State, routing::get, routing::post, Json, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use base64::Engine;
+use eyre::Result;
+use serde_json::{json, Value};
+
+pub const INJECT_ANNOTATION: &str = "teller.sh/inject";
+pub const CONFIG_SECRET_ANNOTATION: &str = "teller.sh/config-secret";
+/// comma separated names of the containers to inject (all if not given)
+pub const CONTAINERS_ANNOTATION: &str = "teller.sh/containers";
+pub const STATUS_ANNOTATION: &str = "teller.sh/status";
+
+const BIN_VOLUME: &str = "teller-bin";
+const CONFIG_VOLUME: &str = "teller-config";
+const BIN_DIR: &str = "/teller/bin";
+const CONFIG_DIR: &str = "/teller/config";
+
+pub struct Opts {
+    pub addr: SocketAddr,
+    pub tls_cert: PathBuf,
+    pub tls_key: PathBuf,
+    pub injector: Injector,
+}
+
+#[derive(Debug, Clone)]
+pub struct Injector {
+    /// image holding the teller binary, copied into pods by an init container
+    pub image: String,
+    /// path of the teller binary in `image`
+    pub binary: String,
+}
+
+/// A pod mutation: JSON patch operations, and warnings for the user
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Mutation {
+    pub patch: Vec<Value>,
+    pub warnings: Vec<String>,
+}
+
+impl Injector {
+    /// Patch a pod to run its containers through teller, if it asks to be
+    #[must_use]
+    pub fn mutate(&self, pod: &Value) -> Mutation {
+        let mut mutation = Mutation::default();
+        let annotations = &pod["metadata"]["annotations"];
+        if annotations[INJECT_ANNOTATION].as_str() != Some("true")
+            || annotations.get(STATUS_ANNOTATION).is_some()
+        {
+            return mutation;
+        }
+        let Some(secret) = annotations[CONFIG_SECRET_ANNOTATION].as_str() else {
+            mutation.warnings.push(format!(
+                "teller: not injected, `{CONFIG_SECRET_ANNOTATION}` is missing"
+            ));
+            return mutation;
+        };
+        let only = annotations[CONTAINERS_ANNOTATION]
+            .as_str()
+            .map(|names| names.split(',').map(str::trim).collect::<Vec<_>>());
+
+        let spec = &pod["spec"];
+        add_items(
+            &mut mutation.patch,
+            spec,
+            "/spec/volumes",
+            "volumes",
+            vec![
+                json!({ "name": BIN_VOLUME, "emptyDir": { "medium": "Memory" } }),
+                json!({ "name": CONFIG_VOLUME, "secret": { "secretName": secret } }),
+            ],
+        );
+        add_items(
+            &mut mutation.patch,
+            spec,
+            "/spec/initContainers",
+            "initContainers",
+            vec![json!({
+                "name": "teller-init",
+                "image": self.image,
+                "command": ["cp", self.binary, format!("{BIN_DIR}/teller")],
+                "volumeMounts": [{ "name": BIN_VOLUME, "mountPath": BIN_DIR }],
+            })],
+        );
+
+        let containers = spec["containers"].as_array().cloned().unwrap_or_default();
+        for (i, container) in containers.iter().enumerate() {
+            let name = container["name"].as_str().unwrap_or_default();
+            if only.as_ref().is_some_and(|only| !only.contains(&name)) {
+                continue;
+            }
+            let Some(command) = container["command"].as_array() else {
+                mutation.warnings.push(format!(
+                    "teller: container '{name}' not injected, it has no `command` to wrap"
+                ));
+                continue;
+            };
+            let mut wrapped = vec![
+                json!(format!("{BIN_DIR}/teller")),
+                json!("--config"),
+                json!(format!("{CONFIG_DIR}/.teller.yml")),
+                json!("run"),
+                json!("--"),
+            ];
+            wrapped.extend(command.iter().cloned());
+            if let Some(args) = container["args"].as_array() {
+                wrapped.extend(args.iter().cloned());
+                mutation
+                    .patch
+                    .push(json!({ "op": "remove", "path": format!("/spec/containers/{i}/args") }));
+            }
+            mutation.patch.push(json!({
+                "op": "replace",
+                "path": format!("/spec/containers/{i}/command"),
+                "value": wrapped,
+            }));
+            add_items(
+                &mut mutation.patch,
+                container,
+                &format!("/spec/containers/{i}/volumeMounts"),
+                "volumeMounts",
+                vec![
+                    json!({ "name": BIN_VOLUME, "mountPath": BIN_DIR, "readOnly": true }),
+                    json!({ "name": CONFIG_VOLUME, "mountPath": CONFIG_DIR, "readOnly": true }),
+                ],
+            );
+        }
+
+        if annotations.is_null() {
+            mutation.patch.push(json!({
+                "op": "add",
+                "path": "/metadata/annotations",
+                "value": { "teller.sh/status": "injected" },
+            }));
+        } else {
+            mutation.patch.push(json!({
+                "op": "add",
+                "path": format!("/metadata/annotations/{}", STATUS_ANNOTATION.replace('/', "~1")),
+                "value": "injected",
+            }));
+        }
+        mutation
+    }
+
+    /// Answer an `AdmissionReview` request
+    #[must_use]
+    pub fn review(&self, review: &Value) -> Value {
+        let request = &review["request"];
+        let mutation = self.mutate(&request["object"]);
+        let mut response = json!({
+            "uid": request["uid"],
+            "allowed": true,
+        });
+        if !mutation.patch.is_empty() {
+            response["patchType"] = json!("JSONPatch");
+            response["patch"] = json!(base64::engine::general_purpose::STANDARD
+                .encode(Value::Array(mutation.patch).to_string()));
+        }
+        if !mutation.warnings.is_empty() {
+            response["warnings"] = json!(mutation.warnings);
+        }
+        json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "response": response,
+        })
+    }
+}
+
+/// Add `items` to the array at `field` of `parent`, creating it if needed
+fn add_items(ops: &mut Vec<Value>, parent: &Value, path: &str, field: &str, items: Vec<Value>) {
+    if parent[field].is_array() {
+        ops.extend(
+            items
+                .into_iter()
+                .map(|item| json!({ "op": "add", "path": format!("{path}/-"), "value": item })),
+        );
+    } else {
+        ops.push(json!({ "op": "add", "path": path, "value": items }));
+    }
+}
+
+/// Serve the webhook over HTTPS (`POST /mutate`, and `GET /healthz`) until the
+/// process is stopped
+///
+/// # Errors
+///
+/// This function will return an error if the certificate cannot be loaded or
+/// the address cannot be bound to
+pub async fn serve(opts: Opts) -> Result<()> {
+    let tls = RustlsConfig::from_pem_file(&opts.tls_cert, &opts.tls_key).await?;
+    let app = Router::new()
+        .route("/mutate", post(mutate))
+        .route("/healthz", get(|| async { "ok" }))
+        .with_state(Arc::new(opts.injector));
+
+    eprintln!("serving webhook on https://{}", opts.addr);
+    axum_server::bind_rustls(opts.addr, tls)
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+async fn mutate(State(injector): State<Arc<Injector>>, Json(review): Json<Value>) -> Json<Value> {
+    Json(injector.review(&review))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn injector() -> Injector {
+        Injector {
+            image: "tellerops/teller:latest".to_string(),
+            binary: "/usr/local/bin/teller".to_string(),
+        }
+    }
+
+    #[test]
+    fn inject_pod() {
+        let pod = json!({
+            "metadata": {
+                "annotations": {
+                    "teller.sh/inject": "true",
+                    "teller.sh/config-secret": "app-teller",
+                    "teller.sh/containers": "app, sidecar",
+                },
+            },
+            "spec": {
+                "volumes": [{ "name": "data", "emptyDir": {} }],
+                "containers": [
+                    { "name": "app", "command": ["./server"], "args": ["--port", "80"] },
+                    { "name": "sidecar", "image": "envoy" },
+                    { "name": "other", "command": ["./other"] },
+                ],
+            },
+        });
+        let mutation = injector().mutate(&pod);
+        insta::assert_yaml_snapshot!(mutation.patch);
+        assert_eq!(
+            mutation.warnings,
+            vec!["teller: container 'sidecar' not injected, it has no `command` to wrap"]
+        );
+    }
+
+    #[test]
+    fn skip_pods() {
+        let injector = injector();
+        let pod = json!({ "spec": { "containers": [{ "name": "app", "command": ["./server"] }] } });
+        assert_eq!(injector.mutate(&pod), Mutation::default());
+
+        let pod = json!({
+            "metadata": { "annotations": { "teller.sh/inject": "true", "teller.sh/status": "injected" } },
+        });
+        assert_eq!(injector.mutate(&pod), Mutation::default());
+
+        let review = injector.review(&json!({
+            "request": { "uid": "1", "object": { "metadata": { "annotations": { "teller.sh/inject": "true" } } } },
+        }));
+        assert_eq!(
+            review["response"],
+            json!({
+                "uid": "1",
+                "allowed": true,
+                "warnings": ["teller: not injected, `teller.sh/config-secret` is missing"],
+            })
+        );
+    }
+}
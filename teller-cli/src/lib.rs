@@ -1,11 +1,114 @@
 pub mod cli;
+pub mod color;
+pub mod drift;
 pub mod io;
+pub mod k8s;
+pub mod login;
+pub mod logs;
+pub mod mcp;
 pub mod new;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod progress;
 pub mod scan;
+pub mod serve;
+pub mod shell_hook;
+pub mod ui;
 pub mod wizard;
 use eyre::Result;
-use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
+use tracing_subscriber::{
+    layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+/// Exit codes, for scripts telling failures apart. `teller run` exits with the
+/// code of the command it ran, and `teller scan` with `2` when scanning fails.
+pub mod exit {
+    use exitcode::ExitCode;
+
+    /// Findings (drift, violations, secrets found) and declined confirmations,
+    /// and failures not told apart below
+    pub const FAILURE: ExitCode = 1;
+    /// Some maps failed to load, the others were used
+    pub const PARTIAL: ExitCode = 3;
+    /// A provider, map or key was not found
+    pub const NOT_FOUND: ExitCode = exitcode::NOINPUT;
+    /// A provider rejected the credentials, or the policy denied the operation
+    pub const AUTH: ExitCode = exitcode::NOPERM;
+    /// The configuration is missing or not valid
+    pub const CONFIG: ExitCode = exitcode::CONFIG;
+    /// The command to run was found but cannot be run
+    pub const CANNOT_EXECUTE: ExitCode = 126;
+    /// The command to run was not found
+    pub const COMMAND_NOT_FOUND: ExitCode = 127;
+}
+
+/// The configuration is missing or not valid, exits with [`exit::CONFIG`]
+#[derive(thiserror::Error, Debug)]
+#[error("{0}")]
+pub struct ConfigError(pub String);
+
+/// The exit code of an error, see [`exit`]
+#[must_use]
+pub fn exit_code(err: &eyre::Report) -> exitcode::ExitCode {
+    if err.downcast_ref::<ConfigError>().is_some() {
+        return exit::CONFIG;
+    }
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<teller_core::Error>() {
+            match err {
+                teller_core::Error::Denied(_) => return exit::AUTH,
+                teller_core::Error::NotFound(_) => return exit::NOT_FOUND,
+                teller_core::Error::Exec(err) => {
+                    return if err.kind() == std::io::ErrorKind::NotFound {
+                        exit::COMMAND_NOT_FOUND
+                    } else {
+                        exit::CANNOT_EXECUTE
+                    }
+                }
+                teller_core::Error::Child(status) => return child_code(*status),
+                // transparent, so not in the chain
+                teller_core::Error::Provider(err) => {
+                    if let Some(code) = provider_code(err) {
+                        return code;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(code) = cause
+            .downcast_ref::<teller_providers::Error>()
+            .and_then(provider_code)
+        {
+            return code;
+        }
+    }
+    exit::FAILURE
+}
+
+/// The exit code of a provider error, when it is one told apart, see [`exit`]
+#[must_use]
+pub const fn provider_code(err: &teller_providers::Error) -> Option<exitcode::ExitCode> {
+    match err {
+        teller_providers::Error::NotFound { .. } => Some(exit::NOT_FOUND),
+        teller_providers::Error::Auth { .. } => Some(exit::AUTH),
+        teller_providers::Error::CreateProviderError(_) => Some(exit::CONFIG),
+        _ => None,
+    }
+}
+
+/// The code of a failed command, `128 + signal` when it was killed, as shells
+/// do
+#[must_use]
+pub fn child_code(status: std::process::ExitStatus) -> exitcode::ExitCode {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(exit::FAILURE)
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub struct Response {
     pub code: exitcode::ExitCode,
@@ -22,6 +125,14 @@ impl Response {
     }
     #[allow(clippy::missing_const_for_fn)]
     #[allow(clippy::unnecessary_wraps)]
+    fn fail_with(code: exitcode::ExitCode) -> Result<Self> {
+        Ok(Self {
+            code,
+            message: None,
+        })
+    }
+    #[allow(clippy::missing_const_for_fn)]
+    #[allow(clippy::unnecessary_wraps)]
     fn ok() -> Result<Self> {
         Ok(Self {
             code: exitcode::OK,
@@ -39,19 +150,102 @@ impl Response {
     }
 }
 
-pub fn tracing(verbose: bool) {
-    let level = if verbose {
-        LevelFilter::INFO
-    } else {
-        LevelFilter::OFF
+/// What has to be flushed before exiting
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    exporters: Option<otel::Exporters>,
+}
+
+impl Telemetry {
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn shutdown(self) {
+        #[cfg(feature = "otel")]
+        if let Some(exporters) = self.exporters {
+            exporters.shutdown();
+        }
+    }
+}
+
+/// Log spans as asked by `-v`, `--log-format` and `--log-file` (or `LOG`), and
+/// with the `otel` feature, export them and provider metrics over OTLP when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+///
+/// Logs are colored as `color` allows.
+///
+/// # Errors
+///
+/// This function will return an error if the log file cannot be opened
+pub fn tracing(args: &logs::LogArgs, color: color::ColorChoice) -> Result<Telemetry> {
+    let filter = EnvFilter::builder()
+        .with_default_directive(args.level().into())
+        .with_env_var("LOG")
+        .from_env_lossy();
+    let writer = args.writer()?;
+    let logs = match args.log_format {
+        logs::LogFormat::Tree => tracing_tree::HierarchicalLayer::new(2)
+            .with_ansi(args.ansi(color))
+            .with_writer(writer)
+            .with_filter(filter)
+            .boxed(),
+        logs::LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_writer(writer)
+            .with_filter(filter)
+            .boxed(),
     };
+
+    #[cfg(feature = "otel")]
+    let (otel, exporters) = otel::layer()
+        .unwrap_or_else(|err| {
+            eprintln!("warning: cannot export over OTLP: {err}");
+            None
+        })
+        .unzip();
+    #[cfg(not(feature = "otel"))]
+    let otel: Option<Box<dyn Layer<Registry> + Send + Sync>> = None;
+
     Registry::default()
-        .with(tracing_tree::HierarchicalLayer::new(2))
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(level.into())
-                .with_env_var("LOG")
-                .from_env_lossy(),
-        )
+        .with(otel.into_iter().chain([logs]).collect::<Vec<_>>())
         .init();
+    Ok(Telemetry {
+        #[cfg(feature = "otel")]
+        exporters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let code = |err: eyre::Report| exit_code(&err);
+        assert_eq!(code(eyre::eyre!("oops")), exit::FAILURE);
+        assert_eq!(
+            code(
+                eyre::Report::new(std::io::Error::other("bad yaml"))
+                    .wrap_err(ConfigError("cannot load configuration".to_string()))
+            ),
+            exit::CONFIG
+        );
+        assert_eq!(
+            code(teller_core::Error::Denied("delete".to_string()).into()),
+            exit::AUTH
+        );
+        assert_eq!(
+            code(
+                teller_core::Error::Provider(teller_providers::Error::NotFound {
+                    path: "app/dev".to_string(),
+                    msg: "not found".to_string(),
+                })
+                .into()
+            ),
+            exit::NOT_FOUND
+        );
+        assert_eq!(
+            code(teller_core::Error::Exec(std::io::ErrorKind::NotFound.into()).into()),
+            exit::COMMAND_NOT_FOUND
+        );
+    }
 }
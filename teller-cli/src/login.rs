@@ -0,0 +1,246 @@
+//! `teller login`: provider credentials saved in the system keyring, from a
+//! pasted token or an interactive sign in.
+use std::io::BufRead;
+
+use clap::Args;
+use eyre::eyre;
+use teller_core::{
+    config::Config,
+    credentials::{self, Credential},
+};
+use teller_providers::config::ProviderCfg;
+
+#[derive(Debug, Clone, Args)]
+pub struct LoginArgs {
+    /// Provider name
+    pub provider: String,
+
+    /// Read the token from stdin instead of prompting for it
+    #[arg(long)]
+    pub token_stdin: bool,
+
+    /// Sign in to Vault in the browser, with its OIDC auth method
+    #[arg(long, conflicts_with_all = ["token_stdin", "sso"])]
+    pub oidc: bool,
+
+    /// Mount of Vault's OIDC auth method
+    #[arg(long, default_value = "oidc")]
+    pub mount: String,
+
+    /// Sign in to AWS IAM Identity Center with a device code
+    #[arg(long, conflicts_with = "token_stdin", requires_all = ["start_url", "account_id", "role"])]
+    pub sso: bool,
+
+    /// AWS access portal URL, e.g. `https://my-org.awsapps.com/start`
+    #[arg(long)]
+    pub start_url: Option<String>,
+
+    /// Region of IAM Identity Center, the provider's `region` if not set
+    #[arg(long)]
+    pub sso_region: Option<String>,
+
+    /// AWS account of the role
+    #[arg(long)]
+    pub account_id: Option<String>,
+
+    /// Vault OIDC role (the auth method's default role if not set), or AWS role name
+    #[arg(long)]
+    pub role: Option<String>,
+}
+
+/// Sign in and store the provider's credential, returning what was done
+///
+/// # Errors
+///
+/// This function will return an error if the provider is not found, the sign
+/// in fails, or the keyring cannot be written
+pub async fn run(config: &Config, args: &LoginArgs) -> eyre::Result<String> {
+    let provider = &args.provider;
+    let provider_cfg = config
+        .providers
+        .get(provider)
+        .ok_or_else(|| eyre!("provider '{provider}' not found"))?;
+
+    let credential = if args.oidc {
+        oidc(provider_cfg, args).await?
+    } else if args.sso {
+        sso(provider_cfg, args).await?
+    } else {
+        if !credentials::takes_token(provider_cfg) {
+            return Err(eyre!(
+                "provider '{provider}' ({}) does not authenticate with a token",
+                provider_cfg.kind
+            ));
+        }
+        Credential::token(&read_token(provider, args.token_stdin)?)
+    };
+    credentials::store(provider, &credential)?;
+    Ok(format!(
+        "credentials for {provider} saved in the system keyring"
+    ))
+}
+
+fn read_token(provider: &str, stdin: bool) -> eyre::Result<String> {
+    let token = if stdin {
+        let mut token = String::new();
+        std::io::stdin().lock().read_line(&mut token)?;
+        token.trim().to_string()
+    } else {
+        dialoguer::Password::new()
+            .with_prompt(format!("Token for {provider}"))
+            .interact()?
+    };
+    if token.is_empty() {
+        return Err(eyre!("empty token"));
+    }
+    Ok(token)
+}
+
+#[cfg(any(feature = "oidc", feature = "sso"))]
+fn expect_kind(provider_cfg: &ProviderCfg, provider: &str, kinds: &[&str]) -> eyre::Result<()> {
+    let kind = provider_cfg.kind.to_string();
+    if kinds.contains(&kind.as_str()) {
+        Ok(())
+    } else {
+        Err(eyre!(
+            "provider '{provider}' ({kind}) cannot sign in this way, only {}",
+            kinds.join(", ")
+        ))
+    }
+}
+
+#[cfg(feature = "oidc")]
+async fn oidc(provider_cfg: &ProviderCfg, args: &LoginArgs) -> eyre::Result<Credential> {
+    use teller_providers::providers::hashicorp_vault::{self, HashivaultOptions};
+
+    expect_kind(provider_cfg, &args.provider, &["hashicorp"])?;
+    let mut opts = provider_cfg
+        .options
+        .clone()
+        .map(serde_json::from_value::<HashivaultOptions>)
+        .transpose()?;
+    // the token being replaced must not be sent along
+    if let Some(opts) = &mut opts {
+        opts.token = None;
+    }
+    let (token, lease) =
+        hashicorp_vault::oidc_login(opts, &args.mount, args.role.clone(), oidc_callback).await?;
+    let credential = Credential::token(&token);
+    Ok(match lease {
+        Some(lease) => credential.with_expires_at(credentials::now() + lease.as_secs()),
+        None => credential,
+    })
+}
+
+#[cfg(not(feature = "oidc"))]
+#[allow(clippy::unused_async)]
+async fn oidc(_provider_cfg: &ProviderCfg, _args: &LoginArgs) -> eyre::Result<Credential> {
+    Err(eyre!("teller was built without OIDC sign in"))
+}
+
+/// How long the browser sign in is waited for
+#[cfg(feature = "oidc")]
+const OIDC_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Open the identity provider's page and wait for the browser to come back
+/// to the redirect URI with the `state` and `code`
+#[cfg(feature = "oidc")]
+async fn oidc_callback(auth_url: String) -> teller_providers::Result<(String, String)> {
+    use axum::{extract::Query, routing::get, Router};
+    use serde_derive::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Callback {
+        state: Option<String>,
+        code: Option<String>,
+        error: Option<String>,
+    }
+
+    let login_err = |err: String| teller_providers::Error::Message(format!("oidc login: {err}"));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:8250")
+        .await
+        .map_err(|err| login_err(format!("cannot listen for the redirect: {err}")))?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Callback>(1);
+    let app = Router::new().route(
+        "/oidc/callback",
+        get(move |Query(callback): Query<Callback>| async move {
+            let _ = tx.send(callback).await;
+            "Signed in, you can close this window."
+        }),
+    );
+
+    eprintln!("Complete the sign in in your browser:\n\n  {auth_url}\n");
+    let _ = open::that(&auth_url);
+
+    let callback = tokio::select! {
+        callback = rx.recv() => callback,
+        res = axum::serve(listener, app) => {
+            return Err(login_err(format!("redirect listener stopped: {res:?}")));
+        }
+        () = tokio::time::sleep(OIDC_TIMEOUT) => {
+            return Err(login_err("timed out waiting for the browser".to_string()));
+        }
+    }
+    .ok_or_else(|| login_err("no redirect received".to_string()))?;
+    match callback {
+        Callback {
+            state: Some(state),
+            code: Some(code),
+            ..
+        } => Ok((state, code)),
+        Callback { error, .. } => Err(login_err(
+            error.unwrap_or_else(|| "redirect without a code".to_string()),
+        )),
+    }
+}
+
+#[cfg(feature = "sso")]
+async fn sso(provider_cfg: &ProviderCfg, args: &LoginArgs) -> eyre::Result<Credential> {
+    use teller_providers::providers::aws_sso::{self, SsoOptions};
+
+    expect_kind(provider_cfg, &args.provider, &["ssm", "aws_secretsmanager"])?;
+    let region = args
+        .sso_region
+        .clone()
+        .or_else(|| {
+            provider_cfg
+                .options
+                .as_ref()
+                .and_then(|options| options.get("region"))
+                .and_then(serde_json::Value::as_str)
+                .map(ToString::to_string)
+        })
+        .ok_or_else(|| eyre!("--sso-region is required when the provider has no `region`"))?;
+    let opts = SsoOptions {
+        start_url: args.start_url.clone().unwrap_or_default(),
+        region,
+        account_id: args.account_id.clone().unwrap_or_default(),
+        role_name: args.role.clone().unwrap_or_default(),
+    };
+    let role = aws_sso::login(&opts, |device| {
+        eprintln!(
+            "Approve the sign in with code {} in your browser:\n\n  {}\n",
+            device.user_code, device.verification_uri
+        );
+        let _ = open::that(&device.verification_uri);
+    })
+    .await?;
+
+    let mut options = serde_json::Map::new();
+    options.insert("access_key_id".to_string(), role.access_key_id.into());
+    options.insert(
+        "secret_access_key".to_string(),
+        role.secret_access_key.into(),
+    );
+    options.insert("session_token".to_string(), role.session_token.into());
+    Ok(Credential {
+        options,
+        expires_at: Some(role.expires_at),
+    })
+}
+
+#[cfg(not(feature = "sso"))]
+#[allow(clippy::unused_async)]
+async fn sso(_provider_cfg: &ProviderCfg, _args: &LoginArgs) -> eyre::Result<Credential> {
+    Err(eyre!("teller was built without AWS SSO sign in"))
+}
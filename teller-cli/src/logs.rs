@@ -0,0 +1,192 @@
+//! Where logs go and how they look, from the global `-v`, `--log-format` and
+//! `--log-file` flags.
+//!
+//! Every line is redacted before it is written: values teller read from or
+//! wrote to providers, and in JSON logs the values of sensitive fields
+//! (`token`, `password`, ...).
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use clap::{Args, ValueEnum};
+use eyre::Result;
+use fs_err as fs;
+use teller_core::redact::{self, Redactor};
+use teller_providers::config::KV;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::color::ColorChoice;
+
+/// Values redacted from logs, see [`keep_out`]
+static SECRETS: RwLock<Vec<KV>> = RwLock::new(Vec::new());
+
+/// Redact these values from logs written from now on
+pub fn keep_out(kvs: &[KV]) {
+    SECRETS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .extend_from_slice(kvs);
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Spans as an indented tree
+    #[default]
+    Tree,
+    /// One JSON object per event
+    Json,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct LogArgs {
+    /// Log provider calls, repeat for more detail (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Log format
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub log_format: LogFormat,
+
+    /// Append logs to this file instead of stderr, at info level unless -v asks for more
+    #[arg(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+}
+
+impl LogArgs {
+    /// The level logged when `LOG` does not say otherwise
+    #[must_use]
+    pub const fn level(&self) -> LevelFilter {
+        match (self.verbose, self.log_file.is_some()) {
+            (0, false) => LevelFilter::OFF,
+            (0 | 1, _) => LevelFilter::INFO,
+            (2, _) => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+
+    /// Whether logs can be colored
+    #[must_use]
+    pub fn ansi(&self, color: ColorChoice) -> bool {
+        self.log_file.is_none() && color.stderr()
+    }
+
+    /// Where logs are written
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the log file cannot be opened
+    pub fn writer(&self) -> Result<Redacting> {
+        let file = self
+            .log_file
+            .as_ref()
+            .map(|path| {
+                let mut options = fs::OpenOptions::new();
+                options.create(true).append(true);
+                #[cfg(unix)]
+                {
+                    use fs::os::unix::fs::OpenOptionsExt;
+                    options.mode(0o600);
+                }
+                options.open(path)
+            })
+            .transpose()?;
+        Ok(Redacting {
+            file: file.map(|file| Arc::new(Mutex::new(file))),
+            json: self.log_format == LogFormat::Json,
+        })
+    }
+}
+
+/// Writes log lines to stderr or a file, redacted
+#[derive(Clone)]
+pub struct Redacting {
+    file: Option<Arc<Mutex<fs::File>>>,
+    json: bool,
+}
+
+impl Redacting {
+    fn write_redacted(&self, out: impl Write, text: &[u8]) -> io::Result<()> {
+        let secrets = SECRETS.read().unwrap_or_else(PoisonError::into_inner);
+        Redactor::with_opts(redact::Opts {
+            json_lines: self.json,
+            ..redact::Opts::default()
+        })
+        .redact(text, out, &secrets)
+    }
+}
+
+/// An event being logged, redacted and written at once when dropped so
+/// secrets are matched across the writes formatting it took
+pub struct Event<'a> {
+    to: &'a Redacting,
+    buf: Vec<u8>,
+}
+
+impl Write for Event<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Event<'_> {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        // nowhere to report a failing log write
+        let _ = match &self.to.file {
+            Some(file) => self.to.write_redacted(
+                &mut *file.lock().unwrap_or_else(PoisonError::into_inner),
+                &self.buf,
+            ),
+            None => self.to.write_redacted(io::stderr().lock(), &self.buf),
+        };
+    }
+}
+
+impl<'a> MakeWriter<'a> for Redacting {
+    type Writer = Event<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        Event {
+            to: self,
+            buf: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_log_lines() {
+        keep_out(&[KV::from_kv("API_KEY", "hunter22")]);
+        let path = std::env::temp_dir().join(format!("teller-log-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let args = LogArgs {
+            verbose: 0,
+            log_format: LogFormat::Json,
+            log_file: Some(path.clone()),
+        };
+        assert_eq!(args.level(), LevelFilter::INFO);
+        let writer = args.writer().unwrap();
+        {
+            let mut event = writer.make_writer();
+            write!(event, r#"{{"message":"put","value":"hunter22","#).unwrap();
+            writeln!(event, r#""token":"abc"}}"#).unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"message\":\"put\",\"value\":\"[REDACTED]\",\"token\":\"[REDACTED]\"}\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
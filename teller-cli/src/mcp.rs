@@ -0,0 +1,228 @@
+//! A Model Context Protocol server over stdio, letting assistants and IDE agents
+//! look at which secrets exist and where, without ever seeing their values.
+//!
+//! Messages are JSON-RPC 2.0, one per line.
+
+use eyre::Result;
+use serde_json::{json, Value};
+use teller_core::teller::Teller;
+use teller_providers::config::KV;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+const DEFAULT_REDACT_WITH: &str = "[REDACTED]";
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub struct Server {
+    teller: Teller,
+}
+
+impl Server {
+    #[must_use]
+    pub const fn new(teller: Teller) -> Self {
+        Self { teller }
+    }
+
+    /// Answer requests from stdin on stdout, until stdin is closed
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if stdin or stdout fail
+    pub async fn serve_stdio(&self) -> Result<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle(&message).await,
+                Err(err) => Some(error(&Value::Null, PARSE_ERROR, &err.to_string())),
+            };
+            if let Some(response) = response {
+                let mut out = serde_json::to_vec(&response)?;
+                out.push(b'\n');
+                stdout.write_all(&out).await?;
+                stdout.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer one message, `None` for notifications
+    pub async fn handle(&self, message: &Value) -> Option<Value> {
+        let id = message.get("id")?;
+        let params = &message["params"];
+        let result = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "teller", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": tools() }),
+            "tools/call" => match self.call(params).await {
+                Ok(result) => result,
+                Err(message) => return Some(error(id, INVALID_PARAMS, &message)),
+            },
+            method => {
+                return Some(error(
+                    id,
+                    METHOD_NOT_FOUND,
+                    &format!("method not found: {method}"),
+                ))
+            }
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    async fn call(&self, params: &Value) -> Result<Value, String> {
+        let arguments = &params["arguments"];
+        let output = match params["name"].as_str().unwrap_or_default() {
+            "list_keys" => self.teller.collect().await.map(|kvs| {
+                kvs.iter()
+                    .filter(|kv| matches(kv, arguments))
+                    .map(location)
+                    .collect::<Vec<_>>()
+            }),
+            "get_masked_values" => self.teller.collect().await.map(|kvs| {
+                kvs.iter()
+                    .filter(|kv| matches(kv, arguments))
+                    .map(|kv| {
+                        let mut value = location(kv);
+                        value["value"] = json!(kv
+                            .meta
+                            .as_ref()
+                            .and_then(|meta| meta.redact_with.as_deref())
+                            .unwrap_or(DEFAULT_REDACT_WITH));
+                        value["empty"] = json!(kv.value.is_empty());
+                        value
+                    })
+                    .collect::<Vec<_>>()
+            }),
+            "verify" => {
+                return Ok(match self.teller.verify().await {
+                    Ok(violations) => text(&json!(violations), false),
+                    Err(err) => text(&json!(err.to_string()), true),
+                })
+            }
+            name => return Err(format!("unknown tool: {name}")),
+        };
+        Ok(match output {
+            Ok(output) => text(&json!(output), false),
+            Err(err) => text(&json!(err.to_string()), true),
+        })
+    }
+}
+
+fn tools() -> Value {
+    let filter = json!({
+        "type": "object",
+        "properties": {
+            "provider": { "type": "string", "description": "Only keys from this provider" },
+            "map_id": { "type": "string", "description": "Only keys from this map" },
+        },
+    });
+    json!([
+        {
+            "name": "list_keys",
+            "description": "List the keys teller collects, with the provider and map they come from. Never returns values.",
+            "inputSchema": filter,
+        },
+        {
+            "name": "get_masked_values",
+            "description": "Get keys with their values masked, and whether they are empty. Never returns raw values.",
+            "inputSchema": filter,
+        },
+        {
+            "name": "verify",
+            "description": "Check that keys declared in the configuration exist and have values following their validation rules, listing violations.",
+            "inputSchema": { "type": "object", "properties": {} },
+        },
+    ])
+}
+
+fn matches(kv: &KV, arguments: &Value) -> bool {
+    let provider = kv.provider.as_ref().map(|p| p.name.as_str());
+    let map_id = kv.path.as_ref().map(|p| p.id.as_str());
+    arguments["provider"]
+        .as_str()
+        .is_none_or(|name| provider == Some(name))
+        && arguments["map_id"]
+            .as_str()
+            .is_none_or(|id| map_id == Some(id))
+}
+
+fn location(kv: &KV) -> Value {
+    json!({
+        "key": kv.key,
+        "provider": kv.provider.as_ref().map(|p| &p.name),
+        "map_id": kv.path.as_ref().map(|p| &p.id),
+        "path": kv.path.as_ref().map(|p| &p.path),
+    })
+}
+
+fn text(value: &Value, is_error: bool) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": value.to_string() }],
+        "isError": is_error,
+    })
+}
+
+fn error(id: &Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use teller_providers::{config::PathMap, providers::ProviderKind};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn never_expose_values() {
+        let teller = Teller::builder()
+            .with_provider(
+                "mem",
+                ProviderKind::Inmem,
+                Some(json!({ "app/dev": { "DB_USER": "admin", "DB_PASS": "" } })),
+            )
+            .with_map("mem", PathMap::from_path("app/dev"))
+            .build()
+            .await
+            .unwrap();
+        let server = Server::new(teller);
+
+        assert_eq!(
+            server
+                .handle(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+                .await,
+            None
+        );
+        let response = server
+            .handle(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": "get_masked_values", "arguments": { "provider": "mem" } },
+            }))
+            .await
+            .unwrap();
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(!text.contains("admin"));
+        insta::assert_yaml_snapshot!(serde_json::from_str::<Value>(text).unwrap());
+
+        let response = server
+            .handle(&json!({ "jsonrpc": "2.0", "id": 2, "method": "nope" }))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], json!(METHOD_NOT_FOUND));
+    }
+}
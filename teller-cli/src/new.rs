@@ -65,6 +65,7 @@ pub fn run(args: &NewArgs) -> Result<Response> {
             fs::create_dir_all(folder)?;
         }
         fs::write(&file, template)?;
-        Response::ok_with_message(format!("Configuration saved in: {:?}", file.display()))
+        eprintln!("Configuration saved in: {:?}", file.display());
+        Response::ok()
     }
 }
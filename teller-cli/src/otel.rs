@@ -0,0 +1,67 @@
+use eyre::Result;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{MetricExporter, SpanExporter};
+use opentelemetry_sdk::{
+    metrics::{PeriodicReader, SdkMeterProvider},
+    runtime,
+    trace::TracerProvider,
+    Resource,
+};
+use tracing::{level_filters::LevelFilter, Subscriber};
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+pub type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync>;
+
+/// Exporters to flush before exiting
+pub struct Exporters {
+    tracer: TracerProvider,
+    meter: SdkMeterProvider,
+}
+
+impl Exporters {
+    pub fn shutdown(self) {
+        if let Err(err) = self.tracer.shutdown() {
+            eprintln!("warning: exporting spans: {err}");
+        }
+        if let Err(err) = self.meter.shutdown() {
+            eprintln!("warning: exporting metrics: {err}");
+        }
+    }
+}
+
+/// A layer exporting spans and metrics over OTLP (HTTP), configured with the
+/// standard `OTEL_EXPORTER_OTLP_*` environment variables. `None` when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is not set.
+///
+/// # Errors
+///
+/// This function will return an error if the exporters cannot be created
+pub fn layer<S>() -> Result<Option<(BoxedLayer<S>, Exporters)>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return Ok(None);
+    }
+    let resource = Resource::new(vec![KeyValue::new("service.name", "teller")]);
+    let tracer = TracerProvider::builder()
+        .with_batch_exporter(SpanExporter::builder().with_http().build()?, runtime::Tokio)
+        .with_resource(resource.clone())
+        .build();
+    let meter = SdkMeterProvider::builder()
+        .with_reader(
+            PeriodicReader::builder(
+                MetricExporter::builder().with_http().build()?,
+                runtime::Tokio,
+            )
+            .build(),
+        )
+        .with_resource(resource)
+        .build();
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer.tracer("teller"))
+        .and_then(tracing_opentelemetry::MetricsLayer::new(meter.clone()))
+        .with_filter(LevelFilter::INFO)
+        .boxed();
+    Ok(Some((layer, Exporters { tracer, meter })))
+}
@@ -0,0 +1,94 @@
+//! Spinners on stderr while teller calls providers, so collecting many maps or
+//! copying hundreds of keys does not look like a hang. Nothing is shown with
+//! `--quiet`, or when stderr is not a terminal.
+
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressFinish};
+use teller_core::progress::{Progress, Status, Step};
+use teller_core::teller::Teller;
+
+const TICK: Duration = Duration::from_millis(100);
+
+fn enabled(quiet: bool) -> bool {
+    !quiet && io::stderr().is_terminal()
+}
+
+fn map_name(progress: &Progress<'_>) -> String {
+    format!(
+        "{}/{} ({})",
+        progress.provider, progress.pm.id, progress.pm.path
+    )
+}
+
+fn keys(count: usize) -> String {
+    if count == 1 {
+        "1 key".to_string()
+    } else {
+        format!("{count} keys")
+    }
+}
+
+/// Show a spinner per running provider call. Writes end with a status line,
+/// reads are cleared once done.
+pub fn install(teller: &mut Teller, quiet: bool) {
+    if !enabled(quiet) {
+        return;
+    }
+    let bars = MultiProgress::new();
+    let running = Mutex::new(HashMap::<String, ProgressBar>::new());
+    teller.on_progress(Box::new(move |progress| {
+        let mut running = running.lock().unwrap_or_else(PoisonError::into_inner);
+        match &progress.status {
+            Status::Started { keys: count } => {
+                let message = count.map_or_else(
+                    || format!("{}: {}", map_name(progress), progress.step),
+                    |count| format!("{}: {} {}", map_name(progress), progress.step, keys(count)),
+                );
+                let bar = bars.add(ProgressBar::new_spinner().with_message(message));
+                bar.enable_steady_tick(TICK);
+                running.insert(progress.id(), bar);
+            }
+            Status::Done { keys: count } => {
+                let Some(bar) = running.remove(&progress.id()) else {
+                    return;
+                };
+                let done = match progress.step {
+                    Step::Get => None,
+                    Step::Put => Some("put"),
+                    Step::Delete => Some("deleted"),
+                };
+                match done {
+                    Some(done) => bar.finish_with_message(format!(
+                        "{}: {done} {}",
+                        map_name(progress),
+                        keys(*count)
+                    )),
+                    None => bar.finish_and_clear(),
+                }
+            }
+            // the error is reported by the command
+            Status::Failed(_) => {
+                if let Some(bar) = running.remove(&progress.id()) {
+                    bar.finish_and_clear();
+                }
+            }
+        }
+    }));
+}
+
+/// A spinner for work outside of providers, e.g. scanning files, none with
+/// `--quiet`. It is cleared when dropped.
+#[must_use]
+pub fn spinner(quiet: bool, message: &str) -> Option<ProgressBar> {
+    enabled(quiet).then(|| {
+        let bar = ProgressBar::new_spinner()
+            .with_message(message.to_string())
+            .with_finish(ProgressFinish::AndClear);
+        bar.enable_steady_tick(TICK);
+        bar
+    })
+}
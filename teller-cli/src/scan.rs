@@ -1,63 +1,170 @@
 use comfy_table::presets::NOTHING;
 use comfy_table::{Cell, Table};
+use dialoguer::{theme::ColorfulTheme, Confirm};
 use eyre::Result;
-use teller_core::{scan, teller::Teller};
+use teller_core::{config::Match, mask::Mask, scan, teller::Teller};
 
-use crate::cli::ScanArgs;
+use crate::cli::{Placeholder, ReportFormat, ScanArgs};
+use crate::progress;
 use crate::Response;
 
-fn hide_chars(s: &str) -> String {
-    let mut result = String::new();
-    let chars_to_display = s.chars().take(2).collect::<String>();
-    let asterisks = "*".repeat(3);
-    result.push_str(&chars_to_display);
-    result.push_str(&asterisks);
-    result
-}
+/// Exit code when the scan itself failed, as opposed to `1` for findings (with
+/// `--error-if-found`), so hooks and CI can tell the two apart.
+pub const ERROR_EXIT_CODE: i32 = 2;
 
 /// Scan a folder for secrets fetched from providers
 ///
 /// # Errors
 ///
 /// This function will return an error if the operation fails
-#[allow(clippy::future_not_send)]
-pub async fn run(teller: &Teller, args: &ScanArgs) -> Result<Response> {
+#[allow(clippy::future_not_send, clippy::too_many_lines)]
+pub async fn run(teller: &Teller, args: &ScanArgs, quiet: bool) -> Result<Response> {
     let opts = scan::Opts {
         include_all: args.all,
         include_binary: args.binary,
+        rules: if args.patterns {
+            scan::builtin_rules()
+        } else {
+            vec![]
+        },
+        entropy: args.entropy.then(scan::Entropy::default),
+        baseline: None,
+        include: args.include.clone(),
+        exclude: args.exclude.clone(),
+        max_file_size: args.max_file_size,
+        follow_links: args.follow_links,
     };
 
     let kvs = teller.collect().await?;
-    let res = teller.scan(&args.root, &kvs, &opts)?;
+    let scan = |opts: &scan::Opts| {
+        let _spinner = progress::spinner(quiet, &format!("scanning {}", args.root));
+        if args.history {
+            teller.scan_history(&args.root, &kvs, opts)
+        } else if args.staged {
+            teller.scan_staged(&args.root, &kvs, opts)
+        } else {
+            teller.scan(&args.root, &kvs, opts)
+        }
+    };
+    if let (Some(path), true) = (&args.baseline, args.update_baseline) {
+        let res = scan(&opts)?;
+        scan::Baseline::from_matches(&res).save(path)?;
+        eprintln!("wrote {} finding(s) to {}", res.len(), path.display());
+        return Response::ok();
+    }
+
+    let opts = scan::Opts {
+        baseline: args
+            .baseline
+            .as_deref()
+            .map(scan::Baseline::load)
+            .transpose()?,
+        ..opts
+    };
+    let res = scan(&opts)?;
     let count = res.len();
+    let mask = teller.mask();
     eprintln!("scanning for {} item(s) in {}", kvs.len(), args.root);
-    if args.json {
+    if let Some(format) = args.report {
+        let format = match format {
+            ReportFormat::Sarif => scan::ReportFormat::Sarif,
+            ReportFormat::Junit => scan::ReportFormat::JUnit,
+        };
+        println!("{}", format.report(&res, &opts.rules)?);
+    } else if args.json {
         println!("{}", serde_json::to_string_pretty(&res)?);
     } else {
         let mut table = Table::new();
         table.load_preset(NOTHING);
-        for m in res {
+        for m in &res {
             let pos = m.position.unwrap_or((0, 0));
+            let path = m.commit.as_ref().map_or_else(
+                || m.path.to_string_lossy().to_string(),
+                |commit| {
+                    format!(
+                        "{}:{} ({})",
+                        &commit.id[..8],
+                        m.path.to_string_lossy(),
+                        commit.author
+                    )
+                },
+            );
             table.add_row(vec![
                 Cell::new(format!("{}:{}", pos.0, pos.1)),
-                Cell::new(m.path.to_string_lossy()),
-                Cell::new(hide_chars(&m.query.value)),
+                Cell::new(path),
+                Cell::new(mask.apply(m.query.value.expose())),
+                Cell::new(m.rule.as_ref().map_or_else(
+                    || {
+                        m.query
+                            .provider
+                            .as_ref()
+                            .map_or_else(|| "n/a".to_string(), |p| p.kind.to_string())
+                    },
+                    |rule| format!("rule: {rule}"),
+                )),
                 Cell::new(
                     m.query
-                        .provider
-                        .map_or_else(|| "n/a".to_string(), |p| p.kind.to_string())
-                        .to_string(),
+                        .path
+                        .as_ref()
+                        .map_or_else(|| "n/a".to_string(), |p| p.path.clone()),
                 ),
-                Cell::new(m.query.path.map_or_else(|| "n/a".to_string(), |p| p.path)),
             ]);
         }
         println!("{table}");
     }
     eprintln!("found {count} result(s)");
 
+    if args.fix {
+        return fix(&res, args.placeholder, &mask);
+    }
+
     if args.error_if_found && count > 0 {
         Response::fail()
     } else {
         Response::ok()
     }
 }
+
+/// Confirm each finding and replace confirmed ones in their files
+fn fix(matches: &[Match], placeholder: Placeholder, mask: &Mask) -> Result<Response> {
+    let placeholder = match placeholder {
+        Placeholder::Redacted => scan::Placeholder::Redacted,
+        Placeholder::Template => scan::Placeholder::Template,
+    };
+    let mut confirmed = vec![];
+    for m in matches {
+        let pos = m.position.unwrap_or((0, 0));
+        let prompt = format!(
+            "{}:{}:{} replace {} with {}?",
+            m.path.display(),
+            pos.0,
+            pos.1,
+            mask.apply(m.query.value.expose()),
+            placeholder.for_match(m)
+        );
+        if Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?
+        {
+            confirmed.push(m.clone());
+        }
+    }
+    let fixed = scan::fix(&confirmed, placeholder)?;
+    eprintln!("fixed {fixed} result(s)");
+    Response::ok()
+}
+
+/// Report a failed scan with [`ERROR_EXIT_CODE`]
+///
+/// # Errors
+///
+/// This function does not fail, it returns a `Result` to be used with `or_else`
+#[allow(clippy::unnecessary_wraps)]
+pub fn error(err: &eyre::Report) -> Result<Response> {
+    eprintln!("error: {err:?}");
+    Ok(Response {
+        code: ERROR_EXIT_CODE,
+        message: None,
+    })
+}
@@ -0,0 +1,190 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use eyre::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use subtle::ConstantTimeEq;
+use teller_core::{export::Format, teller::Teller};
+use teller_providers::config::KV;
+
+const DEFAULT_REDACT_WITH: &str = "[REDACTED]";
+
+pub struct Opts {
+    pub addr: SocketAddr,
+    /// bearer token requests must carry, if any
+    pub token: Option<String>,
+    /// globs of keys whose values are replaced by their map's `redact_with`
+    pub redact: Vec<String>,
+}
+
+struct Server {
+    teller: Teller,
+    token: Option<String>,
+    redact: GlobSet,
+}
+
+impl Server {
+    /// Key-values to export as `format`, once policies allow it
+    async fn kvs(&self, format: &Format) -> Result<Vec<KV>, (StatusCode, String)> {
+        let mut kvs = self
+            .teller
+            .exported(format)
+            .await
+            .map_err(|err| match err {
+                teller_core::Error::Denied(_) => (StatusCode::FORBIDDEN, err.to_string()),
+                _ => (StatusCode::BAD_GATEWAY, err.to_string()),
+            })?;
+        for kv in &mut kvs {
+            if self.redact.is_match(&kv.key) {
+                kv.value = kv
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.redact_with.as_deref())
+                    .unwrap_or(DEFAULT_REDACT_WITH)
+                    .into();
+            }
+        }
+        Ok(kvs)
+    }
+}
+
+/// Serve collected secrets over HTTP until the process is stopped, read-only:
+///
+/// * `GET /healthz`: `ok`
+/// * `GET /env`: secrets as env variables
+/// * `GET /export/:format`: secrets in one of the export formats
+///
+/// Secrets are collected on every request, and policies denying their export
+/// answer `403 Forbidden`.
+///
+/// # Errors
+///
+/// This function will return an error if the address cannot be bound to
+pub async fn serve(teller: Teller, opts: Opts) -> Result<()> {
+    let mut redact = GlobSetBuilder::new();
+    for glob in &opts.redact {
+        redact.add(Glob::new(glob)?);
+    }
+    let server = Arc::new(Server {
+        teller,
+        token: opts.token,
+        redact: redact.build()?,
+    });
+
+    let keep_alive = server.clone();
+    tokio::spawn(async move { keep_alive.teller.keep_alive_loop().await });
+
+    let app = Router::new()
+        .route("/env", get(env))
+        .route("/export/:format", get(export))
+        .route_layer(middleware::from_fn_with_state(server.clone(), auth))
+        .route("/healthz", get(|| async { "ok" }))
+        .with_state(server);
+
+    let listener = tokio::net::TcpListener::bind(opts.addr).await?;
+    eprintln!("serving on http://{}", listener.local_addr()?);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+async fn auth(State(server): State<Arc<Server>>, request: Request, next: Next) -> Response {
+    if let Some(token) = &server.token {
+        let bearer = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        // in constant time, so timing does not tell how much of a guess was right
+        if !bearer.is_some_and(|bearer| bool::from(bearer.as_bytes().ct_eq(token.as_bytes()))) {
+            return (StatusCode::UNAUTHORIZED, "invalid token").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn env(State(server): State<Arc<Server>>) -> Result<String, (StatusCode, String)> {
+    let kvs = server.kvs(&Format::ENV).await?;
+    Format::ENV
+        .export(&kvs)
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
+
+async fn export(
+    State(server): State<Arc<Server>>,
+    Path(format): Path<String>,
+) -> Result<String, (StatusCode, String)> {
+    let format: Format = format.parse().map_err(|possible| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("unknown format, use one of: {possible}"),
+        )
+    })?;
+    let kvs = server.kvs(&format).await?;
+    format
+        .export(&kvs)
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use teller_core::config::Config;
+
+    use super::*;
+
+    async fn with_policy(policy: &str) -> Arc<Server> {
+        let config = Config::from_text(&format!(
+            r"
+providers:
+  mem:
+    kind: inmem
+    options:
+      prod/app: {{ API_KEY: s3cret }}
+    maps:
+      - id: app
+        path: prod/app
+{policy}
+"
+        ))
+        .unwrap();
+        Arc::new(Server {
+            teller: Teller::from_config(&config).await.unwrap(),
+            token: None,
+            redact: GlobSet::empty(),
+        })
+    }
+
+    #[tokio::test]
+    async fn policy_denies_export() {
+        let server = with_policy(
+            r"
+policy:
+  - deny: [export]
+    formats: [json]
+",
+        )
+        .await;
+        let denied = export(State(server.clone()), Path("json".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(denied.0, StatusCode::FORBIDDEN);
+        assert!(!denied.1.contains("s3cret"));
+        assert!(env(State(server)).await.unwrap().contains("API_KEY"));
+
+        let server = with_policy("policy: [{ deny: [export] }]").await;
+        assert_eq!(
+            env(State(server)).await.unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
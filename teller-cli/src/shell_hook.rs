@@ -0,0 +1,191 @@
+//! Loading collected variables into the current shell when entering a folder
+//! with a `.teller.yml`, and unloading them when leaving it, direnv-style.
+//!
+//! `teller hook <shell>` prints a hook running `teller env --export-to-parent
+//! <shell>` before each prompt. That prints code the hook evaluates, loading
+//! the variables of the nearest configuration when it is a new one or was
+//! changed, and nothing otherwise. What is loaded is tracked in
+//! [`STAMP_VAR`] and [`KEYS_VAR`].
+
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use clap::ValueEnum;
+use teller_core::export::Format;
+use teller_providers::config::KV;
+
+/// the configuration loaded, and when it was last changed
+pub const STAMP_VAR: &str = "TELLER_HOOK_STAMP";
+/// the keys loaded, separated by `:`
+pub const KEYS_VAR: &str = "TELLER_HOOK_KEYS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// The hook to evaluate in the shell's startup file, running `teller`
+    #[must_use]
+    pub fn hook(self, teller: &Path) -> String {
+        let teller = shell_words::quote(&teller.to_string_lossy()).to_string();
+        let hook = match self {
+            Self::Bash => {
+                r#"_teller_hook() {
+  local previous_exit_status=$?
+  eval "$(TELLER -q env --export-to-parent bash)"
+  return $previous_exit_status
+}
+if [[ ";${PROMPT_COMMAND[*]:-};" != *";_teller_hook;"* ]]; then
+  PROMPT_COMMAND="_teller_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+"#
+            }
+            Self::Zsh => {
+                r#"_teller_hook() {
+  eval "$(TELLER -q env --export-to-parent zsh)"
+}
+typeset -ag precmd_functions
+if (( ! ${precmd_functions[(I)_teller_hook]} )); then
+  precmd_functions=(_teller_hook $precmd_functions)
+fi
+typeset -ag chpwd_functions
+if (( ! ${chpwd_functions[(I)_teller_hook]} )); then
+  chpwd_functions=(_teller_hook $chpwd_functions)
+fi
+"#
+            }
+            Self::Fish => {
+                r"function __teller_hook --on-event fish_prompt --on-variable PWD
+    TELLER -q env --export-to-parent fish | source
+end
+"
+            }
+        };
+        hook.replace("TELLER", &teller)
+    }
+
+    #[must_use]
+    pub const fn format(self) -> Format {
+        match self {
+            Self::Bash | Self::Zsh => Format::Shell,
+            Self::Fish => Format::Fish,
+        }
+    }
+
+    fn unset(self, key: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => format!("unset {key}\n"),
+            Self::Fish => format!("set -e {key}\n"),
+        }
+    }
+}
+
+/// What the hook loaded, from the shell's environment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Loaded {
+    pub stamp: Option<String>,
+    pub keys: Vec<String>,
+}
+
+impl Loaded {
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self {
+            stamp: std::env::var(STAMP_VAR).ok(),
+            keys: std::env::var(KEYS_VAR)
+                .map(|keys| {
+                    keys.split(':')
+                        .filter(|key| is_name(key))
+                        .map(ToString::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A configuration as of its last change, none if it cannot be read
+#[must_use]
+pub fn stamp(config: &Path) -> Option<String> {
+    let modified = config.metadata().ok()?.modified().ok()?;
+    let nanos = modified.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    Some(format!("{}:{nanos}", config.display()))
+}
+
+/// Code unloading what was loaded
+#[must_use]
+pub fn unload(shell: Shell, loaded: &Loaded) -> String {
+    loaded
+        .keys
+        .iter()
+        .map(String::as_str)
+        .chain([STAMP_VAR, KEYS_VAR])
+        .map(|key| shell.unset(key))
+        .collect()
+}
+
+/// Code loading key-values, and recording them as loaded from the
+/// configuration of `stamp`. Keys that are not valid variable names are left
+/// out, and returned.
+///
+/// # Errors
+///
+/// This function will return an error if exporting fails
+pub fn load(shell: Shell, stamp: &str, kvs: &[KV]) -> teller_core::Result<(String, Vec<String>)> {
+    let (mut kvs, invalid): (Vec<_>, Vec<_>) = kvs.iter().cloned().partition(|kv| is_name(&kv.key));
+    let keys = kvs.iter().map(|kv| kv.key.clone()).collect::<Vec<_>>();
+    kvs.push(KV::from_kv(STAMP_VAR, stamp));
+    kvs.push(KV::from_kv(KEYS_VAR, &keys.join(":")));
+    Ok((
+        shell.format().export(&kvs)?,
+        invalid.into_iter().map(|kv| kv.key).collect(),
+    ))
+}
+
+/// Whether a key can be a shell variable, anything else could be code
+fn is_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_and_unload() {
+        let kvs = [
+            KV::from_kv("DB_PASS", "it's"),
+            KV::from_kv("bad; rm -rf ~", "x"),
+        ];
+        let (code, invalid) = load(Shell::Bash, "/app/.teller.yml:1", &kvs).unwrap();
+        assert_eq!(
+            code,
+            concat!(
+                "#!/bin/sh\n",
+                "export DB_PASS='it'\\''s'\n",
+                "export TELLER_HOOK_STAMP='/app/.teller.yml:1'\n",
+                "export TELLER_HOOK_KEYS='DB_PASS'\n"
+            )
+        );
+        assert_eq!(invalid, vec!["bad; rm -rf ~"]);
+
+        let loaded = Loaded {
+            stamp: Some("/app/.teller.yml:1".to_string()),
+            keys: vec!["DB_PASS".to_string()],
+        };
+        assert_eq!(
+            unload(Shell::Fish, &loaded),
+            "set -e DB_PASS\nset -e TELLER_HOOK_STAMP\nset -e TELLER_HOOK_KEYS\n"
+        );
+        assert!(Shell::Zsh
+            .hook(Path::new("/usr/local/bin/teller"))
+            .contains("eval \"$(/usr/local/bin/teller -q env --export-to-parent zsh)\""));
+    }
+}
@@ -0,0 +1,622 @@
+//! `teller ui`: a terminal UI to browse providers, their maps and keys with
+//! masked values, and to put, delete and copy with confirmation.
+
+use eyre::Result;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Clear, List, ListState, Paragraph, Row, Table, TableState},
+    Frame,
+};
+use teller_core::{
+    config::{Collected, MapKVs, ProviderKVs},
+    copy,
+    mask::Mask,
+    teller::Teller,
+};
+use teller_providers::config::KV;
+
+const HELP: &str =
+    "tab/arrows: move  a: add key  e: edit value  d: delete key  c: copy map  r: reload  q: quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Providers,
+    Maps,
+    Keys,
+}
+
+/// What an input prompt is for
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pending {
+    NewKey,
+    Value { key: String },
+    CopyTo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    Input {
+        prompt: String,
+        input: String,
+        pending: Pending,
+    },
+    Confirm(Action),
+}
+
+/// What the user asked for, changes are confirmed first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Reload,
+    Put {
+        provider: String,
+        map_id: String,
+        key: String,
+        value: String,
+    },
+    Delete {
+        provider: String,
+        map_id: String,
+        key: String,
+    },
+    Copy {
+        provider: String,
+        map_id: String,
+        to_provider: String,
+        to_map_id: String,
+    },
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Reload => write!(f, "reload"),
+            Self::Put {
+                provider,
+                map_id,
+                key,
+                ..
+            } => write!(f, "put {key} into {provider}/{map_id}"),
+            Self::Delete {
+                provider,
+                map_id,
+                key,
+            } => write!(f, "delete {key} from {provider}/{map_id}"),
+            Self::Copy {
+                provider,
+                map_id,
+                to_provider,
+                to_map_id,
+            } => write!(f, "copy {provider}/{map_id} to {to_provider}/{to_map_id}"),
+        }
+    }
+}
+
+impl Action {
+    /// Run against the providers
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the provider operation fails
+    pub async fn run(&self, teller: &Teller) -> Result<()> {
+        match self {
+            Self::Reload => {}
+            Self::Put {
+                provider,
+                map_id,
+                key,
+                value,
+            } => {
+                teller
+                    .put(
+                        &[KV::from_kv(key, value)],
+                        map_id,
+                        std::slice::from_ref(provider),
+                    )
+                    .await?;
+            }
+            Self::Delete {
+                provider,
+                map_id,
+                key,
+            } => {
+                teller
+                    .delete(
+                        std::slice::from_ref(key),
+                        map_id,
+                        std::slice::from_ref(provider),
+                    )
+                    .await?;
+            }
+            Self::Copy {
+                provider,
+                map_id,
+                to_provider,
+                to_map_id,
+            } => {
+                teller
+                    .copy(
+                        provider,
+                        map_id,
+                        to_provider,
+                        to_map_id,
+                        &copy::Opts::default(),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct App {
+    collected: Collected,
+    mask: Mask,
+    pane: Pane,
+    provider: usize,
+    map: usize,
+    key: usize,
+    mode: Mode,
+    status: String,
+    quit: bool,
+}
+
+impl App {
+    #[must_use]
+    pub fn new(collected: Collected) -> Self {
+        Self {
+            collected,
+            mask: Mask::default(),
+            pane: Pane::Providers,
+            provider: 0,
+            map: 0,
+            key: 0,
+            mode: Mode::Browse,
+            status: HELP.to_string(),
+            quit: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Show freshly collected data, keeping the selection where possible
+    pub fn reload(&mut self, collected: Collected) {
+        self.collected = collected;
+        self.provider = self
+            .provider
+            .min(self.collected.providers.len().saturating_sub(1));
+        self.map = self.map.min(self.maps().len().saturating_sub(1));
+        self.key = self.key.min(self.kvs().len().saturating_sub(1));
+    }
+
+    fn maps(&self) -> &[MapKVs] {
+        self.collected
+            .providers
+            .get(self.provider)
+            .map_or(&[], |p: &ProviderKVs| p.maps.as_slice())
+    }
+
+    fn kvs(&self) -> &[KV] {
+        self.maps().get(self.map).map_or(&[], |m| m.kvs.as_slice())
+    }
+
+    /// The selected provider and map
+    fn location(&self) -> Option<(String, String)> {
+        let provider = self.collected.providers.get(self.provider)?;
+        let map = provider.maps.get(self.map)?;
+        Some((provider.name.clone(), map.id.clone()))
+    }
+
+    fn select(&mut self, up: bool) {
+        let (index, len) = match self.pane {
+            Pane::Providers => (&mut self.provider, self.collected.providers.len()),
+            Pane::Maps => {
+                let len = self.maps().len();
+                (&mut self.map, len)
+            }
+            Pane::Keys => {
+                let len = self.kvs().len();
+                (&mut self.key, len)
+            }
+        };
+        *index = if up {
+            index.saturating_sub(1)
+        } else {
+            (*index + 1).min(len.saturating_sub(1))
+        };
+        match self.pane {
+            Pane::Providers => {
+                self.map = 0;
+                self.key = 0;
+            }
+            Pane::Maps => self.key = 0,
+            Pane::Keys => {}
+        }
+    }
+
+    fn prompt(&mut self, prompt: &str, pending: Pending) {
+        self.mode = Mode::Input {
+            prompt: prompt.to_string(),
+            input: String::new(),
+            pending,
+        };
+    }
+
+    /// Whether the app should exit
+    #[must_use]
+    pub const fn quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Show the outcome of an action
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    /// Handle a key press, returning an action once it is confirmed
+    pub fn on_key(&mut self, key: KeyEvent) -> Option<Action> {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.on_browse_key(key),
+            Mode::Input {
+                prompt,
+                mut input,
+                pending,
+            } => {
+                match key.code {
+                    KeyCode::Esc => {}
+                    KeyCode::Enter => self.on_input(input, pending),
+                    KeyCode::Backspace => {
+                        input.pop();
+                        self.mode = Mode::Input {
+                            prompt,
+                            input,
+                            pending,
+                        };
+                    }
+                    KeyCode::Char(c) => {
+                        input.push(c);
+                        self.mode = Mode::Input {
+                            prompt,
+                            input,
+                            pending,
+                        };
+                    }
+                    _ => {
+                        self.mode = Mode::Input {
+                            prompt,
+                            input,
+                            pending,
+                        };
+                    }
+                }
+                None
+            }
+            Mode::Confirm(action) => {
+                if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                    Some(action)
+                } else {
+                    self.status = "cancelled".to_string();
+                    None
+                }
+            }
+        }
+    }
+
+    fn on_browse_key(&mut self, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.pane = match self.pane {
+                    Pane::Providers => Pane::Maps,
+                    Pane::Maps | Pane::Keys => Pane::Keys,
+                };
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.pane = match self.pane {
+                    Pane::Providers | Pane::Maps => Pane::Providers,
+                    Pane::Keys => Pane::Maps,
+                };
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.select(true),
+            KeyCode::Down | KeyCode::Char('j') => self.select(false),
+            KeyCode::Char('r') => return Some(Action::Reload),
+            KeyCode::Char('a') if self.location().is_some() => {
+                self.prompt("New key", Pending::NewKey);
+            }
+            KeyCode::Char('e') => {
+                if let Some(kv) = self.kvs().get(self.key) {
+                    let key = kv.from_key.clone();
+                    self.prompt(&format!("New value of {key}"), Pending::Value { key });
+                }
+            }
+            KeyCode::Char('d') => {
+                if let (Some((provider, map_id)), Some(kv)) =
+                    (self.location(), self.kvs().get(self.key))
+                {
+                    self.mode = Mode::Confirm(Action::Delete {
+                        provider,
+                        map_id,
+                        key: kv.from_key.clone(),
+                    });
+                }
+            }
+            KeyCode::Char('c') if self.location().is_some() => {
+                self.prompt("Copy to (<provider name>/<map id>)", Pending::CopyTo);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn on_input(&mut self, input: String, pending: Pending) {
+        let Some((provider, map_id)) = self.location() else {
+            return;
+        };
+        match pending {
+            Pending::NewKey if !input.is_empty() => {
+                self.prompt(&format!("Value of {input}"), Pending::Value { key: input });
+            }
+            Pending::Value { key } => {
+                self.mode = Mode::Confirm(Action::Put {
+                    provider,
+                    map_id,
+                    key,
+                    value: input,
+                });
+            }
+            Pending::CopyTo => {
+                if let Some((to_provider, to_map_id)) = input.split_once('/') {
+                    self.mode = Mode::Confirm(Action::Copy {
+                        provider,
+                        map_id,
+                        to_provider: to_provider.to_string(),
+                        to_map_id: to_map_id.to_string(),
+                    });
+                } else {
+                    self.status =
+                        format!("cannot parse '{input}', expected <provider name>/<map id>");
+                }
+            }
+            Pending::NewKey => {}
+        }
+    }
+
+    fn block(&self, title: &str, pane: Pane) -> Block<'static> {
+        let block = Block::bordered().title(title.to_string());
+        if self.pane == pane {
+            block.border_style(Style::new().yellow())
+        } else {
+            block
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+        let [providers, maps, keys] = Layout::horizontal([
+            Constraint::Percentage(20),
+            Constraint::Percentage(30),
+            Constraint::Percentage(50),
+        ])
+        .areas(main);
+
+        let list = List::new(
+            self.collected
+                .providers
+                .iter()
+                .map(|p| format!("{} ({})", p.name, p.kind)),
+        )
+        .block(self.block("Providers", Pane::Providers))
+        .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(
+            list,
+            providers,
+            &mut ListState::default().with_selected(Some(self.provider)),
+        );
+
+        let list = List::new(self.maps().iter().map(|m| {
+            let line = format!("{} ({})", m.id, m.path);
+            if m.error.is_some() {
+                Line::from(line).red()
+            } else {
+                Line::from(line)
+            }
+        }))
+        .block(self.block("Maps", Pane::Maps))
+        .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(
+            list,
+            maps,
+            &mut ListState::default().with_selected(Some(self.map)),
+        );
+
+        if let Some(error) = self.maps().get(self.map).and_then(|m| m.error.as_deref()) {
+            frame.render_widget(
+                Paragraph::new(error.to_string())
+                    .red()
+                    .block(self.block("Keys", Pane::Keys)),
+                keys,
+            );
+        } else {
+            let table = Table::new(
+                self.kvs()
+                    .iter()
+                    .map(|kv| Row::new([kv.key.clone(), self.mask.apply(kv.value.expose())])),
+                [Constraint::Percentage(50), Constraint::Percentage(50)],
+            )
+            .header(Row::new(["Key", "Value"]).bold())
+            .block(self.block("Keys", Pane::Keys))
+            .row_highlight_style(Style::new().reversed());
+            let selected = (self.pane == Pane::Keys).then_some(self.key);
+            frame.render_stateful_widget(
+                table,
+                keys,
+                &mut TableState::default().with_selected(selected),
+            );
+        }
+
+        frame.render_widget(
+            Paragraph::new(self.status.as_str()).block(Block::bordered()),
+            status,
+        );
+
+        let popup = match &self.mode {
+            Mode::Browse => return,
+            Mode::Input {
+                prompt,
+                input,
+                pending,
+            } => {
+                // values are never shown, even while typing them
+                let shown = if matches!(pending, Pending::Value { .. }) {
+                    "*".repeat(input.chars().count())
+                } else {
+                    input.clone()
+                };
+                Paragraph::new(format!("{shown}_")).block(Block::bordered().title(prompt.clone()))
+            }
+            Mode::Confirm(action) => Paragraph::new(format!("{action}? (y/n)")).block(
+                Block::bordered()
+                    .title("Confirm")
+                    .border_style(Style::new().red()),
+            ),
+        };
+        let area = centered(frame.area());
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+}
+
+fn centered(area: Rect) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Percentage(60)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(3)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
+
+/// Run the UI until the user quits
+///
+/// # Errors
+///
+/// This function will return an error if the terminal cannot be drawn to or
+/// read from
+pub async fn run(teller: &Teller) -> Result<()> {
+    let mut app = App::new(teller.collect_detailed().await).with_mask(teller.mask());
+    let mut terminal = ratatui::init();
+    let res = async {
+        while !app.quit() {
+            terminal.draw(|frame| app.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(action) = app.on_key(key) {
+                let status = match action.run(teller).await {
+                    Ok(()) => format!("done: {action}"),
+                    Err(err) => format!("error: {action}: {err}"),
+                };
+                app.reload(teller.collect_detailed().await);
+                app.set_status(status);
+            }
+        }
+        Ok(())
+    }
+    .await;
+    ratatui::restore();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use teller_providers::{config::PathMap, providers::ProviderKind};
+
+    use super::*;
+
+    fn app() -> App {
+        let mut pm = PathMap::from_path("app.env");
+        pm.id = "dev".to_string();
+        App::new(Collected {
+            duplicates: vec![],
+            providers: vec![ProviderKVs {
+                name: "dot1".to_string(),
+                kind: ProviderKind::Dotenv,
+                maps: vec![MapKVs::fetched(
+                    &pm,
+                    vec![
+                        KV::from_kv("DB_USER", "admin"),
+                        KV::from_kv("DB_PASS", "s3cret"),
+                    ],
+                )],
+            }],
+        })
+    }
+
+    fn press(app: &mut App, keys: &str) -> Option<Action> {
+        keys.chars()
+            .map(|c| {
+                app.on_key(KeyEvent::from(match c {
+                    '\n' => KeyCode::Enter,
+                    '\t' => KeyCode::Tab,
+                    c => KeyCode::Char(c),
+                }))
+            })
+            .last()
+            .flatten()
+    }
+
+    #[test]
+    fn confirm_actions() {
+        let mut app = app();
+        assert_eq!(
+            press(&mut app, "\t\tje0pen\ny"),
+            Some(Action::Put {
+                provider: "dot1".to_string(),
+                map_id: "dev".to_string(),
+                key: "DB_PASS".to_string(),
+                value: "0pen".to_string(),
+            })
+        );
+        assert_eq!(press(&mut app, "dn"), None);
+        assert_eq!(app.mode, Mode::Browse);
+        assert_eq!(
+            press(&mut app, "aAPI_KEY\nk1\ny"),
+            Some(Action::Put {
+                provider: "dot1".to_string(),
+                map_id: "dev".to_string(),
+                key: "API_KEY".to_string(),
+                value: "k1".to_string(),
+            })
+        );
+        assert_eq!(
+            press(&mut app, "cdot2/prod\ny").map(|action| action.to_string()),
+            Some("copy dot1/dev to dot2/prod".to_string())
+        );
+        press(&mut app, "q");
+        assert!(app.quit());
+    }
+
+    #[test]
+    fn draw_masked() {
+        let app = app();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(100, 10)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("dot1 (dotenv)"));
+        assert!(screen.contains("DB_PASS"));
+        assert!(screen.contains("s3***"));
+        assert!(!screen.contains("s3cret"));
+    }
+}
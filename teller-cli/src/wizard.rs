@@ -90,6 +90,7 @@ impl AppConfig {
     /// When has a problem with prompt selection
     fn select_providers() -> Result<Vec<ProviderKind>> {
         let providers = ProviderKind::iter()
+            .filter(|provider| provider != &ProviderKind::Custom)
             .map(|provider| (provider.to_string(), provider))
             .collect::<HashMap<String, ProviderKind>>();
 
@@ -16,9 +16,19 @@ fn cli_tests() {
     prep_data_for_mutating_tests();
 
     let c = trycmd::TestCases::new();
+    // keep the journals of mutating tests out of the user's history
+    c.env(
+        "TELLER_HISTORY_DIR",
+        std::env::temp_dir()
+            .join("teller-cli-tests-history")
+            .to_string_lossy(),
+    );
+    // and their undo states out of the system keyring
+    c.env(
+        "TELLER_UNDO_IDENTITY",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/undo-identity.txt"),
+    );
     c.case("tests/cmd/*.trycmd");
-    #[cfg(windows)]
-    c.skip("tests/cmd/run.trycmd");
 
     c.run();
     prep_data_for_mutating_tests();
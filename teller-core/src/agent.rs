@@ -0,0 +1,301 @@
+//! A long-running agent that collects secrets once and serves them to local
+//! processes over a Unix domain socket, so they share one provider session.
+//!
+//! The protocol is one JSON [`Request`] per line, answered by one JSON
+//! [`Response`] per line. Every request carries the agent's token.
+
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_derive::{Deserialize, Serialize};
+use teller_providers::config::KV;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::Mutex;
+
+use crate::export::Format;
+use crate::teller::Teller;
+use crate::{Error, Result};
+
+/// How long collected secrets are served before collecting again
+pub const DEFAULT_TTL: Duration = Duration::from_mins(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Request {
+    pub token: String,
+    /// keys to get, all keys when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+    /// collect again before answering, even if the cache did not expire
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub refresh: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Response {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kvs: Vec<KV>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub struct Opts {
+    pub socket: PathBuf,
+    pub token: String,
+    pub ttl: Duration,
+}
+
+pub struct Agent {
+    teller: Teller,
+    opts: Opts,
+    cache: Mutex<Option<(Instant, Vec<KV>)>>,
+}
+
+impl Agent {
+    #[must_use]
+    pub fn new(teller: Teller, opts: Opts) -> Self {
+        Self {
+            teller,
+            opts,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Listen on the socket, readable by the current user only, until the
+    /// process is stopped
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the socket cannot be created
+    pub async fn serve(self) -> Result<()> {
+        if self.opts.socket.exists() {
+            fs_err::remove_file(&self.opts.socket)?;
+        }
+        let listener = UnixListener::bind(&self.opts.socket)?;
+        fs_err::set_permissions(&self.opts.socket, Permissions::from_mode(0o600))?;
+
+        let agent = Arc::new(self);
+        let keep_alive = agent.clone();
+        tokio::spawn(async move { keep_alive.teller.keep_alive_loop().await });
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let agent = agent.clone();
+            tokio::spawn(async move {
+                if let Err(err) = agent.connection(stream).await {
+                    tracing::warn!(error = %err, "agent connection failed");
+                }
+            });
+        }
+    }
+
+    async fn connection(&self, stream: UnixStream) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => self.answer(&request).await,
+                Err(err) => Response {
+                    error: Some(format!("invalid request: {err}")),
+                    ..Response::default()
+                },
+            };
+            let mut out = serde_json::to_vec(&response)?;
+            out.push(b'\n');
+            writer.write_all(&out).await?;
+        }
+        Ok(())
+    }
+
+    async fn answer(&self, request: &Request) -> Response {
+        if !token_eq(&request.token, &self.opts.token) {
+            return Response {
+                error: Some("invalid token".to_string()),
+                ..Response::default()
+            };
+        }
+        let mut cache = self.cache.lock().await;
+        let expired = cache
+            .as_ref()
+            .is_none_or(|(fetched, _)| fetched.elapsed() >= self.opts.ttl);
+        if expired || request.refresh {
+            match self.teller.exported(&Format::ENV).await {
+                Ok(kvs) => *cache = Some((Instant::now(), kvs)),
+                Err(err) => {
+                    return Response {
+                        error: Some(err.to_string()),
+                        ..Response::default()
+                    }
+                }
+            }
+        }
+        let kvs = cache
+            .as_ref()
+            .map(|(_, kvs)| {
+                kvs.iter()
+                    .filter(|kv| request.keys.is_empty() || request.keys.contains(&kv.key))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        drop(cache);
+        Response { kvs, error: None }
+    }
+}
+
+/// Send a request to an agent
+///
+/// # Errors
+///
+/// This function will return an error if the agent cannot be reached, or it
+/// answers with an error
+pub async fn request(socket: &Path, request: &Request) -> Result<Vec<KV>> {
+    let stream = UnixStream::connect(socket).await.map_err(|err| {
+        Error::Message(format!("cannot reach agent at {}: {err}", socket.display()))
+    })?;
+    let (reader, mut writer) = stream.into_split();
+    let mut out = serde_json::to_vec(request)?;
+    out.push(b'\n');
+    writer.write_all(&out).await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| Error::Message("agent closed the connection".to_string()))?;
+    let response: Response = serde_json::from_str(&line)?;
+    response
+        .error
+        .map_or(Ok(response.kvs), |err| Err(Error::Message(err)))
+}
+
+/// A random token, hex encoded
+///
+/// # Errors
+///
+/// This function will return an error if the system's random source cannot be
+/// read
+pub fn generate_token() -> Result<String> {
+    use std::fmt::Write;
+    use std::io::Read;
+
+    let mut bytes = [0u8; 32];
+    fs_err::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().fold(String::new(), |mut token, b| {
+        let _ = write!(token, "{b:02x}");
+        token
+    }))
+}
+
+/// Compare tokens in time independent of where they differ
+fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use teller_providers::{config::PathMap, providers::ProviderKind};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn serve_secrets() {
+        let teller = Teller::builder()
+            .with_provider(
+                "mem",
+                ProviderKind::Inmem,
+                Some(serde_json::json!({ "app/dev": { "DB_USER": "admin", "DB_PASS": "pass" } })),
+            )
+            .with_map("mem", PathMap::from_path("app/dev"))
+            .build()
+            .await
+            .unwrap();
+        let socket = std::env::temp_dir().join(format!("teller-agent-{}.sock", std::process::id()));
+        let token = generate_token().unwrap();
+        let agent = Agent::new(
+            teller,
+            Opts {
+                socket: socket.clone(),
+                token: token.clone(),
+                ttl: DEFAULT_TTL,
+            },
+        );
+        let server = tokio::spawn(agent.serve());
+        while !socket.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let kvs = request(
+            &socket,
+            &Request {
+                token: token.clone(),
+                keys: vec!["DB_USER".to_string()],
+                refresh: false,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            kvs.iter()
+                .map(|kv| (kv.key.as_str(), kv.value.expose()))
+                .collect::<Vec<_>>(),
+            vec![("DB_USER", "admin")]
+        );
+
+        let err = request(
+            &socket,
+            &Request {
+                token: "nope".to_string(),
+                ..Request::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "invalid token");
+
+        server.abort();
+        fs_err::remove_file(&socket).unwrap();
+    }
+
+    #[tokio::test]
+    async fn policy_denies_get() {
+        let config = crate::config::Config::from_text(
+            r"
+providers:
+  mem:
+    kind: inmem
+    options:
+      app/dev: { DB_USER: admin }
+    maps:
+      - id: dev
+        path: app/dev
+policy:
+  - deny: [export]
+    formats: [env]
+",
+        )
+        .unwrap();
+        let agent = Agent::new(
+            Teller::from_config(&config).await.unwrap(),
+            Opts {
+                socket: PathBuf::new(),
+                token: "t".to_string(),
+                ttl: DEFAULT_TTL,
+            },
+        );
+        let response = agent
+            .answer(&Request {
+                token: "t".to_string(),
+                ..Request::default()
+            })
+            .await;
+        assert!(response.kvs.is_empty());
+        assert!(response.error.unwrap().starts_with("denied by policy"));
+    }
+}
@@ -0,0 +1,200 @@
+//! A blocking facade over [`crate::teller::Teller`], for applications and build
+//! scripts that are not async. It runs operations on its own single threaded
+//! runtime.
+//!
+//! Do not use it from within an async runtime, blocking on a runtime from
+//! inside another one panics. Use [`crate::teller::Teller`] there.
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use teller_providers::config::KV;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{
+    config::Config,
+    copy::{self, CopyReport},
+    export, redact,
+    teller::{self, TellerBuilder},
+    template::{self, Rendered},
+    validate::Violation,
+    Error, Result,
+};
+
+pub struct Teller {
+    inner: teller::Teller,
+    runtime: Runtime,
+}
+
+impl Teller {
+    /// Load from a YAML configuration file
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if loading fails
+    pub fn from_yaml(file: &Path) -> Result<Self> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(teller::Teller::from_yaml(file))?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Build from a configuration
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if loading fails
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(teller::Teller::from_config(config))?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Build in code, see [`teller::Teller::builder`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if loading fails
+    pub fn build(builder: TellerBuilder) -> Result<Self> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(builder.build())?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// The async teller, e.g. to set options
+    pub const fn inner(&mut self) -> &mut teller::Teller {
+        &mut self.inner
+    }
+
+    /// See [`teller::Teller::collect`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if collecting fails
+    pub fn collect(&self) -> Result<Vec<KV>> {
+        Ok(self.runtime.block_on(self.inner.collect())?)
+    }
+
+    /// See [`teller::Teller::export`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if collecting or exporting fails
+    pub fn export(&self, format: &export::Format) -> Result<String> {
+        self.runtime.block_on(self.inner.export(format))
+    }
+
+    /// See [`teller::Teller::put`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing fails
+    pub fn put(&self, kvs: &[KV], map_id: &str, providers: &[String]) -> Result<()> {
+        self.runtime
+            .block_on(self.inner.put(kvs, map_id, providers))
+    }
+
+    /// See [`teller::Teller::delete`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if deleting fails
+    pub fn delete(&self, keys: &[String], map_id: &str, providers: &[String]) -> Result<()> {
+        self.runtime
+            .block_on(self.inner.delete(keys, map_id, providers))
+    }
+
+    /// See [`teller::Teller::copy`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if copying fails
+    pub fn copy(
+        &self,
+        from_provider: &str,
+        from_map_id: &str,
+        to_provider: &str,
+        to_map_id: &str,
+        opts: &copy::Opts,
+    ) -> Result<CopyReport> {
+        self.runtime.block_on(self.inner.copy(
+            from_provider,
+            from_map_id,
+            to_provider,
+            to_map_id,
+            opts,
+        ))
+    }
+
+    /// See [`teller::Teller::verify`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if collecting fails
+    pub fn verify(&self) -> Result<Vec<Violation>> {
+        self.runtime.block_on(self.inner.verify())
+    }
+
+    /// See [`teller::Teller::redact`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if IO or collecting fails
+    pub fn redact<R: BufRead, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        opts: &redact::Opts,
+    ) -> Result<()> {
+        self.runtime
+            .block_on(self.inner.redact(reader, writer, opts))
+    }
+
+    /// See [`teller::Teller::template`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if collecting or rendering fails
+    pub fn template(&self, template: &str, opts: &template::Opts) -> Result<Rendered> {
+        self.runtime.block_on(self.inner.template(template, opts))
+    }
+}
+
+fn runtime() -> Result<Runtime> {
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| Error::Message(format!("cannot start runtime: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use teller_providers::{config::PathMap, providers::ProviderKind};
+
+    use super::*;
+
+    #[test]
+    fn collect_without_runtime() {
+        let teller = Teller::build(
+            teller::Teller::builder()
+                .with_provider(
+                    "mem",
+                    ProviderKind::Inmem,
+                    Some(serde_json::json!({ "app/dev": { "DB_USER": "admin" } })),
+                )
+                .with_map("mem", PathMap::from_path("app/dev")),
+        )
+        .unwrap();
+        assert_eq!(
+            teller.export(&export::Format::ENV).unwrap(),
+            "DB_USER=admin\n"
+        );
+        teller
+            .put(
+                &[KV::from_kv("DB_PASS", "s3cret")],
+                "",
+                &["mem".to_string()],
+            )
+            .unwrap();
+        assert_eq!(teller.collect().unwrap().len(), 2);
+    }
+}
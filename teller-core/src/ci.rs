@@ -0,0 +1,123 @@
+//! CI environments, detected from the variables their runners set, and their
+//! secret-masking mechanisms, so values a build step echoes are masked by the
+//! CI's own logs too.
+
+use std::fmt;
+
+/// A CI environment teller knows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ci {
+    GithubActions,
+    Gitlab,
+    CircleCi,
+    AzurePipelines,
+}
+
+impl fmt::Display for Ci {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::GithubActions => "GitHub Actions",
+            Self::Gitlab => "GitLab CI",
+            Self::CircleCi => "CircleCI",
+            Self::AzurePipelines => "Azure Pipelines",
+        })
+    }
+}
+
+impl Ci {
+    /// The CI teller runs in, from its environment
+    #[must_use]
+    pub fn detect() -> Option<Self> {
+        Self::detect_with(|name| std::env::var(name).ok())
+    }
+
+    /// The CI teller runs in, from variables looked up with `var`
+    pub fn detect_with(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let is_true =
+            |name: &str| var(name).is_some_and(|value| value.eq_ignore_ascii_case("true"));
+        if is_true("GITHUB_ACTIONS") {
+            Some(Self::GithubActions)
+        } else if is_true("GITLAB_CI") {
+            Some(Self::Gitlab)
+        } else if is_true("CIRCLECI") {
+            Some(Self::CircleCi)
+        } else if is_true("TF_BUILD") {
+            Some(Self::AzurePipelines)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the CI can be told to mask values while a job runs.
+    /// [`Self::Gitlab`] and [`Self::CircleCi`] only mask variables set up in
+    /// their project settings.
+    #[must_use]
+    pub const fn can_mask(self) -> bool {
+        matches!(self, Self::GithubActions | Self::AzurePipelines)
+    }
+
+    /// Commands masking a value, to be written to stdout, one per line of the
+    /// value. None if the CI [cannot mask](Self::can_mask).
+    #[must_use]
+    pub fn mask(self, value: &str) -> Option<Vec<String>> {
+        let lines = value.lines().filter(|line| !line.trim().is_empty());
+        match self {
+            Self::GithubActions => Some(
+                lines
+                    .map(|line| format!("::add-mask::{}", escape(line, "%25")))
+                    .collect(),
+            ),
+            Self::AzurePipelines => Some(
+                lines
+                    .map(|line| format!("##vso[task.setsecret]{}", escape(line, "%AZP25")))
+                    .collect(),
+            ),
+            Self::Gitlab | Self::CircleCi => None,
+        }
+    }
+}
+
+/// Escape command data, `percent` being how the CI escapes `%`
+fn escape(data: &str, percent: &str) -> String {
+    data.replace('%', percent)
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_and_mask() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| (*v).to_string())
+            }
+        };
+        assert_eq!(
+            Ci::detect_with(env(&[("CI", "true"), ("GITHUB_ACTIONS", "true")])),
+            Some(Ci::GithubActions)
+        );
+        assert_eq!(
+            Ci::detect_with(env(&[("TF_BUILD", "True")])),
+            Some(Ci::AzurePipelines)
+        );
+        assert_eq!(Ci::detect_with(env(&[("CI", "true")])), None);
+
+        assert_eq!(
+            Ci::GithubActions.mask("50%off\n\nline two"),
+            Some(vec![
+                "::add-mask::50%25off".to_string(),
+                "::add-mask::line two".to_string()
+            ])
+        );
+        assert_eq!(
+            Ci::AzurePipelines.mask("50%off"),
+            Some(vec!["##vso[task.setsecret]50%AZP25off".to_string()])
+        );
+        assert_eq!(Ci::Gitlab.mask("s3cret"), None);
+    }
+}
@@ -0,0 +1,60 @@
+//! Time as teller stamps it on snapshots, undo state and history entries.
+//!
+//! [`SystemClock`] honors [`SOURCE_DATE_EPOCH`], for reproducible output, and
+//! tests use a [`FixedClock`]. Token expiry is always checked against real
+//! time.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Unix time teller stamps with instead of the current time, as for
+/// [reproducible builds](https://reproducible-builds.org/specs/source-date-epoch/)
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+pub trait Clock: Debug + Send + Sync {
+    /// Unix time, in seconds
+    fn now(&self) -> u64;
+}
+
+/// The system's time, or [`SOURCE_DATE_EPOCH`] when set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        std::env::var(SOURCE_DATE_EPOCH)
+            .ok()
+            .and_then(|epoch| epoch.trim().parse().ok())
+            .unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_secs())
+            })
+    }
+}
+
+/// A clock that only moves when told to
+#[derive(Debug, Default)]
+pub struct FixedClock(AtomicU64);
+
+impl FixedClock {
+    #[must_use]
+    pub const fn new(now: u64) -> Self {
+        Self(AtomicU64::new(now))
+    }
+
+    pub fn set(&self, now: u64) {
+        self.0.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.0.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
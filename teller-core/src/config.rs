@@ -1,21 +1,148 @@
 use std::cmp::Ordering;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::{
     collections::{BTreeMap, HashMap},
     path::Path,
 };
 
 use fs_err as fs;
+use indexmap::IndexMap;
 use serde_derive::{Deserialize, Serialize};
 use teller_providers::config::{PathMap, ProviderCfg, KV};
 use teller_providers::providers::ProviderKind;
 use tera::{Context, Tera};
 
+use crate::encrypted;
+use crate::hooks::Hooks;
+use crate::mask::Mask;
+use crate::policy;
+use crate::scan::{Entropy, Rule};
 use crate::Result;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Config {
-    pub providers: BTreeMap<String, ProviderCfg>,
+    /// in the order they are configured, which is the order they are collected
+    /// in, also once serialized again
+    pub providers: IndexMap<String, ProviderCfg>,
+    /// provider kinds and options shared by providers naming them in
+    /// `connection`, see [`resolve_connections`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub connections: BTreeMap<String, Connection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redact: Option<RedactConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan: Option<ScanConfig>,
+    /// how values are masked for display, see [`crate::mask`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mask: Option<Mask>,
+    /// skip maps that fail to load with a warning, instead of failing
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignore_errors: bool,
+    /// commands and webhooks to run around operations
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hooks: Hooks,
+    /// disable core dumps and lock memory before loading providers, see
+    /// [`crate::harden`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub harden: bool,
+    /// environment of commands started by `teller run`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run: Option<RunConfig>,
+    /// named commands, run with `teller run <name>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub commands: BTreeMap<String, CommandCfg>,
+    /// what teller keeps about changes it makes, besides the journal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistoryConfig>,
+    /// rules denying operations, see [`crate::policy`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policy: Vec<policy::Rule>,
+    /// key-values declared here instead of in a provider, collected after the
+    /// maps
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, String>,
+    /// keys rendered from collected keys with Tera, e.g. `DATABASE_URL:
+    /// "postgres://{{ DB_USER }}@{{ DB_HOST }}/app"`, see
+    /// [`crate::template::render_derived`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub derived: BTreeMap<String, String>,
+}
+
+/// How to reach a provider, declared once for several provider entries
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Connection {
+    pub kind: ProviderKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<serde_json::Value>,
+}
+
+/// Detection that does not depend on fetched values
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ScanConfig {
+    /// use the built-in rules (AWS keys, GitHub tokens, private keys, ...)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub builtin_rules: bool,
+    /// custom regex rules
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+    /// detect high entropy strings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entropy: Option<Entropy>,
+}
+
+/// Controls over which fetched values get redacted
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedactConfig {
+    /// values shorter than this are never redacted (default: 2)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    /// keys whose values are never redacted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skip_keys: Vec<String>,
+    /// values that are never redacted, e.g. `DEBUG` or a port number
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_values: Vec<String>,
+}
+
+/// Which variables of teller's own environment commands get, on top of
+/// collected key-values, and which key-values are SSH keys
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunConfig {
+    /// variables kept when the environment is reset, exact names or globs such
+    /// as `CARGO_*`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keep_env: Vec<String>,
+    /// variables never passed on, exact names or globs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub drop_env: Vec<String>,
+    /// keys of SSH private keys, loaded into an ephemeral agent for commands
+    /// instead of set as variables
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ssh_keys: Vec<String>,
+    /// write SSH keys to temporary identity files instead of an agent
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ssh_identity_files: bool,
+}
+
+/// What teller keeps about changes, see [`crate::history`]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryConfig {
+    /// keep the state of maps before each put, delete or copy, encrypted, so
+    /// `teller undo` can put it back
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub undo: bool,
+}
+
+/// A command preset, bound to the maps it needs
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandCfg {
+    /// the command line, run with the shell
+    pub cmd: String,
+    /// maps the command gets key-values from, by id or as `provider/id`. All
+    /// maps when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maps: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -38,6 +165,82 @@ fn apply_eqeq(config: &mut Config) {
     });
 }
 
+/// Give providers naming a `connection` its kind and options, resolving YAML
+/// merge keys (`<<: *anchor`) first. Options a provider sets itself win over
+/// the connection's.
+///
+/// # Errors
+///
+/// This function will return an error if a connection is not declared, or
+/// merge keys are not valid
+pub(crate) fn resolve_connections(value: &mut serde_yaml::Value) -> Result<()> {
+    value.apply_merge()?;
+    let connections = value
+        .get("connections")
+        .and_then(serde_yaml::Value::as_mapping)
+        .cloned()
+        .unwrap_or_default();
+    let Some(providers) = value
+        .get_mut("providers")
+        .and_then(serde_yaml::Value::as_mapping_mut)
+    else {
+        return Ok(());
+    };
+    for (name, provider) in providers.iter_mut() {
+        let Some(provider) = provider.as_mapping_mut() else {
+            continue;
+        };
+        let Some(connection) = provider.remove("connection") else {
+            continue;
+        };
+        let found = connection
+            .as_str()
+            .and_then(|connection| connections.get(connection))
+            .and_then(serde_yaml::Value::as_mapping)
+            .ok_or_else(|| {
+                crate::Error::Message(format!(
+                    "provider {}: cannot find connection {}",
+                    name.as_str().unwrap_or_default(),
+                    connection.as_str().unwrap_or_default()
+                ))
+            })?;
+        if let Some(kind) = found.get("kind") {
+            provider
+                .entry("kind".into())
+                .or_insert_with(|| kind.clone());
+        }
+        if let Some(options) = found.get("options") {
+            match provider.get_mut("options") {
+                Some(serde_yaml::Value::Mapping(own)) => {
+                    if let Some(shared) = options.as_mapping() {
+                        for (key, value) in shared {
+                            own.entry(key.clone()).or_insert_with(|| value.clone());
+                        }
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    provider.insert("options".into(), options.clone());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Give maps without a `prefix` their provider's
+pub(crate) fn apply_prefix(config: &mut Config) {
+    for provider in config.providers.values_mut() {
+        if let Some(prefix) = &provider.prefix {
+            for pm in &mut provider.maps {
+                if pm.prefix.is_none() {
+                    pm.prefix = Some(prefix.clone());
+                }
+            }
+        }
+    }
+}
+
 impl Config {
     /// Config from text
     ///
@@ -46,9 +249,17 @@ impl Config {
     /// This function will return an error if serialization fails
     pub fn with_vars(text: &str, vars: &HashMap<String, String>) -> Result<Self> {
         let rendered_text = Tera::one_off(text, &Context::from_serialize(vars)?, false)?;
-        let mut config: Self = serde_yaml::from_str(&rendered_text)?;
+        let mut value: serde_yaml::Value = serde_yaml::from_str(&rendered_text)?;
+        if encrypted::has_encrypted(&rendered_text) && encrypted::contains_tagged(&value) {
+            encrypted::decrypt_tagged(&mut value, &encrypted::identities()?)?;
+        }
+        resolve_connections(&mut value)?;
+        // back through text, where plain scalars such as `8080` deserialize as
+        // strings wherever strings are expected
+        let mut config: Self = serde_yaml::from_str(&serde_yaml::to_string(&value)?)?;
 
         apply_eqeq(&mut config);
+        apply_prefix(&mut config);
 
         Ok(config)
     }
@@ -91,7 +302,10 @@ impl Config {
             })
             .collect();
 
-        let config = Self { providers: res };
+        let config = Self {
+            providers: res.into_iter().collect(),
+            ..Self::default()
+        };
 
         let a: String = serde_yaml::to_string(&config)?;
         Ok(a)
@@ -104,6 +318,186 @@ pub struct Match {
     pub position: Option<(usize, usize)>,
     pub offset: usize,
     pub query: KV,
+    /// the detection rule that found this match, `None` when a fetched value was found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// the commit that introduced this match, when scanning history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Eq, PartialEq, Ord, PartialOrd)]
+pub struct CommitInfo {
+    pub id: String,
+    /// `name <email>`
+    pub author: String,
+    /// seconds since the epoch
+    pub time: i64,
+}
+
+/// Everything fetched from the configured providers, by provider and map
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Collected {
+    pub providers: Vec<ProviderKVs>,
+    /// keys collected more than once, from maps, `values` or `derived`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<Duplicate>,
+}
+
+/// A key collected more than once. Exports keep its last value, the one
+/// that `won`.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Duplicate {
+    pub key: String,
+    /// where the exported value comes from, as `[provider] map id (path)`
+    pub won: String,
+    /// where the values it replaced come from, in collection order
+    pub shadowed: Vec<String>,
+}
+
+impl Duplicate {
+    /// Keys of `kvs` appearing more than once, in the order they first appear
+    #[must_use]
+    pub fn find(kvs: &[KV]) -> Vec<Self> {
+        let mut sources: Vec<(&str, Vec<String>)> = vec![];
+        for kv in kvs {
+            let source = provenance(kv);
+            match sources.iter_mut().find(|(key, _)| *key == kv.key) {
+                Some((_, found)) => found.push(source),
+                None => sources.push((&kv.key, vec![source])),
+            }
+        }
+        sources
+            .into_iter()
+            .filter(|(_, found)| found.len() > 1)
+            .map(|(key, mut shadowed)| Self {
+                key: key.to_string(),
+                won: shadowed.pop().unwrap_or_default(),
+                shadowed,
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "key {} from {} wins over {}",
+            self.key,
+            self.won,
+            self.shadowed.join(", ")
+        )
+    }
+}
+
+/// Where a key-value was collected from, values without a provider are
+/// declared in the configuration
+fn provenance(kv: &KV) -> String {
+    match (&kv.provider, &kv.path) {
+        (Some(provider), Some(path)) => {
+            format!("[{}] {} ({})", provider.name, path.id, path.path)
+        }
+        (Some(provider), None) => format!("[{}]", provider.name),
+        (None, _) => "the configuration".to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderKVs {
+    pub name: String,
+    pub kind: ProviderKind,
+    pub maps: Vec<MapKVs>,
+}
+
+/// What one map fetched, or why fetching it failed
+#[derive(Debug, Clone, Serialize)]
+pub struct MapKVs {
+    pub id: String,
+    pub path: String,
+    pub kvs: Vec<KV>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// the error itself, e.g. to tell a missing map from rejected credentials
+    #[serde(skip)]
+    pub cause: Option<Arc<teller_providers::Error>>,
+    /// e.g. mapped keys that were not found
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// the map may fail to load without failing a collect
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
+}
+
+impl MapKVs {
+    /// A map that was fetched, warning about anything it was expected to have but
+    /// did not
+    #[must_use]
+    pub fn fetched(pm: &PathMap, kvs: Vec<KV>) -> Self {
+        let mut warnings = pm
+            .keys
+            .keys()
+            .filter(|key| !kvs.iter().any(|kv| &kv.from_key == *key))
+            .map(|key| format!("key {key:?} not found"))
+            .collect::<Vec<_>>();
+        if kvs.is_empty() && warnings.is_empty() {
+            warnings.push("no values".to_string());
+        }
+        Self {
+            id: pm.id.clone(),
+            path: pm.path.clone(),
+            kvs,
+            error: None,
+            cause: None,
+            warnings,
+            optional: pm.optional,
+        }
+    }
+
+    #[must_use]
+    pub fn failed(pm: &PathMap, error: teller_providers::Error) -> Self {
+        Self {
+            id: pm.id.clone(),
+            path: pm.path.clone(),
+            kvs: vec![],
+            error: Some(error.to_string()),
+            cause: Some(Arc::new(error)),
+            warnings: vec![],
+            optional: pm.optional,
+        }
+    }
+}
+
+impl Collected {
+    /// All KVs, in the same order [`crate::teller::Teller::collect`] returns them
+    #[must_use]
+    pub fn kvs(&self) -> Vec<KV> {
+        self.providers
+            .iter()
+            .flat_map(|p| &p.maps)
+            .flat_map(|m| m.kvs.iter().cloned())
+            .collect()
+    }
+
+    /// Maps that failed, as `(provider name, map, error)`
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &MapKVs, &str)> {
+        self.providers.iter().flat_map(|p| {
+            p.maps
+                .iter()
+                .filter_map(|m| Some((p.name.as_str(), m, m.error.as_deref()?)))
+        })
+    }
+
+    /// Warnings of maps that were fetched, as `(provider name, map, warning)`
+    pub fn warnings(&self) -> impl Iterator<Item = (&str, &MapKVs, &str)> {
+        self.providers.iter().flat_map(|p| {
+            p.maps.iter().flat_map(move |m| {
+                m.warnings
+                    .iter()
+                    .map(move |w| (p.name.as_str(), m, w.as_str()))
+            })
+        })
+    }
 }
 
 impl PartialOrd for Match {
@@ -137,6 +531,96 @@ mod tests {
         assert_yaml_snapshot!(config);
     }
 
+    #[test]
+    fn load_redact_config() {
+        let config = Config::from_text(
+            r"
+providers:
+  dot_1:
+    kind: dotenv
+    maps:
+      - id: stg
+        path: one.env
+redact:
+  min_length: 4
+  skip_keys: [PORT]
+  allow_values: [DEBUG]
+",
+        )
+        .unwrap();
+        assert_eq!(
+            config.redact,
+            Some(RedactConfig {
+                min_length: Some(4),
+                skip_keys: vec!["PORT".to_string()],
+                allow_values: vec!["DEBUG".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn load_connections() {
+        let config = Config::from_text(
+            r"
+connections:
+  cluster:
+    kind: inmem
+    options:
+      address: https://vault.internal:8200
+      token: { from_env: VAULT_TOKEN }
+shared: &shared
+  kind: dotenv
+providers:
+  vault_app:
+    connection: cluster
+    maps:
+      - id: app
+        path: secret/data/app
+  vault_ops:
+    connection: cluster
+    options:
+      address: https://vault-ops.internal:8200
+    maps:
+      - id: ops
+        path: secret/data/ops
+  dot:
+    <<: *shared
+    maps:
+      - id: dev
+        path: dev.env
+",
+        )
+        .unwrap();
+        let address = |name: &str| {
+            config.providers[name].options.as_ref().unwrap()["address"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(config.providers["vault_app"].kind, ProviderKind::Inmem);
+        assert_eq!(address("vault_app"), "https://vault.internal:8200");
+        assert_eq!(address("vault_ops"), "https://vault-ops.internal:8200");
+        assert_eq!(
+            config.providers["vault_ops"].options.as_ref().unwrap()["token"]["from_env"],
+            "VAULT_TOKEN"
+        );
+        assert_eq!(config.providers["dot"].kind, ProviderKind::Dotenv);
+
+        let err = Config::from_text(
+            r"
+providers:
+  vault_app:
+    connection: nope
+    maps: []
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "provider vault_app: cannot find connection nope"
+        );
+    }
+
     #[test]
     fn can_render_template_config() {
         let data = RenderTemplate {
@@ -0,0 +1,148 @@
+//! Copying a map to another one, and what it did to the target, see
+//! [`crate::teller::Teller::copy`]
+
+use serde_derive::Serialize;
+use serde_variant::to_variant_name;
+
+/// What happens to keys the target already holds with another value
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// only add keys the target does not hold
+    Merge,
+    /// add missing keys and update existing ones
+    #[default]
+    Overwrite,
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        to_variant_name(self).expect("only enum supported").fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Opts {
+    pub mode: Mode,
+    /// delete target keys absent from the source
+    pub prune: bool,
+    /// report what copying would do, without changing the target
+    pub dry_run: bool,
+}
+
+impl Opts {
+    /// Make the target hold exactly the source's key-values
+    #[must_use]
+    pub const fn replace() -> Self {
+        Self {
+            mode: Mode::Overwrite,
+            prune: true,
+            dry_run: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// Keys of a copy to one target, maps are named `<provider>/<map id>`.
+/// Holds key names only, never values.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyReport {
+    pub from: String,
+    pub to: String,
+    pub mode: Mode,
+    /// nothing was changed, keys are what copying would do
+    pub dry_run: bool,
+    /// keys written to the target, overwritten ones included
+    pub copied: Vec<String>,
+    /// keys the target held with another value
+    pub overwritten: Vec<String>,
+    /// keys not written: held with the same value, or held at all when
+    /// merging
+    pub skipped: Vec<String>,
+    /// target keys absent from the source, deleted
+    pub pruned: Vec<String>,
+}
+
+impl CopyReport {
+    /// One line summary, e.g. `copied 4 key(s) in merge mode from dotenv/dev
+    /// to vault/dev (1 skipped)`
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let verb = if self.dry_run { "would copy" } else { "copied" };
+        let mode = match self.mode {
+            Mode::Merge => " in merge mode",
+            Mode::Overwrite => "",
+        };
+        let details = [
+            (self.overwritten.len(), "overwritten"),
+            (self.skipped.len(), "skipped"),
+            (self.pruned.len(), "pruned"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{count} {what}"))
+        .collect::<Vec<_>>();
+        let details = if details.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", details.join(", "))
+        };
+        format!(
+            "{verb} {} key(s){mode} from {} to {}{details}",
+            self.copied.len(),
+            self.from,
+            self.to
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let report = CopyReport {
+            from: "dotenv/dev".to_string(),
+            to: "vault/dev".to_string(),
+            mode: Mode::Overwrite,
+            copied: vec!["A".to_string(), "B".to_string()],
+            pruned: vec!["Z".to_string()],
+            ..CopyReport::default()
+        };
+        assert_eq!(
+            report.summary(),
+            "copied 2 key(s) from dotenv/dev to vault/dev (1 pruned)"
+        );
+
+        let report = CopyReport {
+            mode: Mode::Merge,
+            dry_run: true,
+            overwritten: vec![],
+            skipped: vec!["C".to_string()],
+            pruned: vec![],
+            ..report
+        };
+        assert_eq!(
+            report.summary(),
+            "would copy 2 key(s) in merge mode from dotenv/dev to vault/dev (1 skipped)"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Provider credentials kept in the system keyring by `teller login`, so
+//! bootstrap credentials stay out of env variables and configuration files.
+//!
+//! Entries are stored under the `teller` service, named after the provider.
+//! They hold provider options, such as a `token`, given to providers
+//! configured without them, until they expire.
+
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use age::secrecy::ExposeSecret;
+use keyring::Entry;
+use serde_derive::{Deserialize, Serialize};
+use teller_providers::config::ProviderCfg;
+
+use crate::{Error, Result};
+
+const SERVICE: &str = "teller";
+
+/// Provider kinds taking a `token` option
+const TOKEN_KINDS: &[&str] = &["hashicorp", "hashicorp_consul"];
+
+/// Provider kinds given stored credentials
+const CREDENTIAL_KINDS: &[&str] = &["hashicorp", "hashicorp_consul", "ssm", "aws_secretsmanager"];
+
+/// Whether a provider authenticates with a `token` option
+#[must_use]
+pub fn takes_token(provider: &ProviderCfg) -> bool {
+    TOKEN_KINDS.contains(&provider.kind.to_string().as_str())
+}
+
+/// Options stored for a provider
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Credential {
+    pub options: serde_json::Map<String, serde_json::Value>,
+    /// Unix time, in seconds, after which the options are not used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl Credential {
+    /// A `token` option that does not expire
+    #[must_use]
+    pub fn token(token: &str) -> Self {
+        let mut options = serde_json::Map::new();
+        options.insert("token".to_string(), token.into());
+        Self {
+            options,
+            expires_at: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_expires_at(mut self, expires_at: u64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= now())
+    }
+}
+
+/// Unix time, in seconds
+#[must_use]
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+fn entry(provider: &str) -> Result<Entry> {
+    Entry::new(SERVICE, provider).map_err(keyring_err)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn keyring_err(err: keyring::Error) -> Error {
+    Error::Message(format!("system keyring: {err}"))
+}
+
+/// Store the credential of a provider, replacing any stored before
+///
+/// # Errors
+///
+/// This function will return an error if the keyring cannot be written
+pub fn store(provider: &str, credential: &Credential) -> Result<()> {
+    entry(provider)?
+        .set_password(&serde_json::to_string(credential)?)
+        .map_err(keyring_err)
+}
+
+/// Remove the credential of a provider, returning whether there was one
+///
+/// # Errors
+///
+/// This function will return an error if the keyring cannot be written
+pub fn remove(provider: &str) -> Result<bool> {
+    match entry(provider)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(err) => Err(keyring_err(err)),
+    }
+}
+
+/// The credential stored for a provider
+///
+/// # Errors
+///
+/// This function will return an error if the keyring cannot be read
+pub fn load(provider: &str) -> Result<Option<Credential>> {
+    match entry(provider)?.get_password() {
+        Ok(stored) => Ok(Some(serde_json::from_str(&stored)?)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(keyring_err(err)),
+    }
+}
+
+/// Service and name of the entry holding the age identity states saved for
+/// `teller undo` are encrypted with
+const UNDO_IDENTITY: (&str, &str) = ("teller-undo", "identity");
+
+/// Env variable with the path of an age identity file used instead of the
+/// keyring's, on machines without one
+pub const UNDO_IDENTITY_ENV: &str = "TELLER_UNDO_IDENTITY";
+
+/// The age identity states saved for `teller undo` are encrypted with,
+/// generated and stored on first use, see [`crate::history::Undo`]
+///
+/// # Errors
+///
+/// This function will return an error if the keyring cannot be read or
+/// written, or the file [`UNDO_IDENTITY_ENV`] points to has no identity
+pub fn undo_identity() -> Result<String> {
+    if let Some(path) = std::env::var_os(UNDO_IDENTITY_ENV) {
+        return fs_err::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+            .map(ToString::to_string)
+            .ok_or_else(|| Error::Message(format!("{UNDO_IDENTITY_ENV}: no age identity found")));
+    }
+    let (service, name) = UNDO_IDENTITY;
+    let entry = Entry::new(service, name).map_err(keyring_err)?;
+    match entry.get_password() {
+        Ok(identity) => Ok(identity),
+        Err(keyring::Error::NoEntry) => {
+            let identity = age::x25519::Identity::generate()
+                .to_string()
+                .expose_secret()
+                .to_string();
+            entry.set_password(&identity).map_err(keyring_err)?;
+            Ok(identity)
+        }
+        Err(err) => Err(keyring_err(err)),
+    }
+}
+
+/// Give providers configured without credentials the ones stored for them,
+/// unless expired. A keyring that cannot be read is skipped, so providers fall
+/// back to their own defaults (such as `VAULT_TOKEN`).
+pub fn apply(providers: &mut BTreeMap<String, ProviderCfg>) {
+    for (name, provider) in providers.iter_mut() {
+        if !CREDENTIAL_KINDS.contains(&provider.kind.to_string().as_str()) {
+            continue;
+        }
+        match load(name) {
+            Ok(Some(credential)) if credential.is_expired() => {
+                tracing::debug!(provider = name, "stored credentials expired");
+            }
+            Ok(Some(credential)) => merge(provider, credential),
+            Ok(None) => {}
+            Err(err) => tracing::debug!(provider = name, error = %err, "cannot read credentials"),
+        }
+    }
+}
+
+/// Add the credential's options, unless the provider sets any of them itself
+fn merge(provider: &mut ProviderCfg, credential: Credential) {
+    let options = provider
+        .options
+        .get_or_insert_with(|| serde_json::json!({}));
+    let Some(options) = options.as_object_mut() else {
+        return;
+    };
+    if credential
+        .options
+        .keys()
+        .any(|key| options.contains_key(key))
+    {
+        return;
+    }
+    options.extend(credential.options);
+}
+
+#[cfg(test)]
+mod tests {
+    use teller_providers::providers::ProviderKind;
+
+    use super::*;
+
+    fn provider(options: Option<serde_json::Value>) -> ProviderCfg {
+        ProviderCfg {
+            kind: ProviderKind::Inmem,
+            options,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_options() {
+        let mut unset = provider(None);
+        merge(&mut unset, Credential::token("t1"));
+        assert_eq!(unset.options, Some(serde_json::json!({"token": "t1"})));
+
+        let mut configured = provider(Some(serde_json::json!({"token": "own"})));
+        merge(&mut configured, Credential::token("t1"));
+        assert_eq!(
+            configured.options,
+            Some(serde_json::json!({"token": "own"}))
+        );
+
+        assert!(Credential::token("t1").with_expires_at(1).is_expired());
+        assert!(!Credential::token("t1")
+            .with_expires_at(now() + 60)
+            .is_expired());
+    }
+}
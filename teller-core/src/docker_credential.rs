@@ -0,0 +1,113 @@
+//! The [docker credential helper](https://github.com/docker/docker-credential-helpers)
+//! protocol, so registry credentials are read from a provider map when docker
+//! needs them.
+//!
+//! The credentials of a registry are the keys `<HOST>_USERNAME`,
+//! `<HOST>_PASSWORD` and `<HOST>_SERVER_URL` of the map, with the registry
+//! host upper snake cased as for [`crate::git_credential`]:
+//! `https://index.docker.io/v1/` is `INDEX_DOCKER_IO_USERNAME`, ...
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::git_credential::host_key;
+use crate::{Error, Result};
+
+/// What docker tells `get` answers with when there are no credentials
+pub const NOT_FOUND: &str = "credentials not found in native keychain";
+
+/// What docker asks of the helper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// give the credentials of a registry, read as its server URL
+    Get,
+    /// keep credentials, read as JSON
+    Store,
+    /// forget the credentials of a registry, read as its server URL
+    Erase,
+    /// give the usernames of all registries, by server URL
+    List,
+}
+
+/// Credentials as docker sends and reads them
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Credentials {
+    #[serde(rename = "ServerURL")]
+    pub server_url: String,
+    #[serde(rename = "Username")]
+    pub username: String,
+    #[serde(rename = "Secret")]
+    pub secret: String,
+}
+
+/// Keys of the credentials of a registry, see [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keys {
+    pub username: String,
+    pub password: String,
+    pub server_url: String,
+}
+
+impl Keys {
+    /// The keys of a registry, by its server URL, with or without scheme and path
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the server URL has no host
+    pub fn for_server(server_url: &str) -> Result<Self> {
+        let server_url = server_url.trim();
+        let host = server_url
+            .split_once("://")
+            .map_or(server_url, |(_, rest)| rest)
+            .split('/')
+            .next()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| {
+                Error::Message(format!(
+                    "docker-credential: no registry host in '{server_url}'"
+                ))
+            })?;
+        let host = host_key(host);
+        Ok(Self {
+            username: format!("{host}_USERNAME"),
+            password: format!("{host}_PASSWORD"),
+            server_url: format!("{host}_SERVER_URL"),
+        })
+    }
+
+    #[must_use]
+    pub fn all(&self) -> Vec<String> {
+        vec![
+            self.username.clone(),
+            self.password.clone(),
+            self.server_url.clone(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_keys() {
+        let hub = Keys::for_server("https://index.docker.io/v1/\n").unwrap();
+        assert_eq!(
+            hub,
+            Keys {
+                username: "INDEX_DOCKER_IO_USERNAME".to_string(),
+                password: "INDEX_DOCKER_IO_PASSWORD".to_string(),
+                server_url: "INDEX_DOCKER_IO_SERVER_URL".to_string(),
+            }
+        );
+        assert_eq!(
+            Keys::for_server("localhost:5000").unwrap().username,
+            "LOCALHOST_5000_USERNAME"
+        );
+        assert!(Keys::for_server("https://").is_err());
+
+        let credentials: Credentials =
+            serde_json::from_str(r#"{"ServerURL":"ghcr.io","Username":"bot","Secret":"t0ken"}"#)
+                .unwrap();
+        assert_eq!(credentials.username, "bot");
+    }
+}
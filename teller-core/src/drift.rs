@@ -0,0 +1,179 @@
+//! Divergence between two maps that should hold the same key-values, such as a
+//! source of truth and a store it gets copied to.
+
+use std::collections::BTreeMap;
+
+use serde_derive::Serialize;
+use serde_variant::to_variant_name;
+use teller_providers::config::KV;
+
+#[cfg(feature = "native")]
+use crate::{Error, Result};
+
+/// How a key of the target map differs from the source map
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// in the source, not in the target
+    Missing,
+    /// in both, with different values
+    Changed,
+    /// in the target, not in the source
+    Extra,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        to_variant_name(self).expect("only enum supported").fmt(f)
+    }
+}
+
+/// A key that differs. Holds key names only, never values.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Drift {
+    pub key: String,
+    pub kind: Kind,
+}
+
+/// Drift of a target map from its source, maps are named `<provider>/<map id>`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub from: String,
+    pub to: String,
+    pub drifts: Vec<Drift>,
+}
+
+impl Report {
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.drifts.is_empty()
+    }
+
+    /// One line summary, e.g. `dot1/dev -> dot2/dev: 1 missing, 1 changed (DB_PASS, DB_USER)`
+    #[must_use]
+    pub fn summary(&self) -> String {
+        if self.drifts.is_empty() {
+            return format!("{} -> {}: in sync", self.from, self.to);
+        }
+        let mut counts = BTreeMap::<Kind, usize>::new();
+        for drift in &self.drifts {
+            *counts.entry(drift.kind).or_default() += 1;
+        }
+        format!(
+            "{} -> {}: {} ({})",
+            self.from,
+            self.to,
+            counts
+                .iter()
+                .map(|(kind, count)| format!("{count} {kind}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.drifts
+                .iter()
+                .map(|drift| drift.key.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+/// Compare the key-values of a target map to its source, by key
+#[must_use]
+pub fn compare(source: &[KV], target: &[KV]) -> Vec<Drift> {
+    let source = source
+        .iter()
+        .map(|kv| (kv.key.as_str(), kv.value.expose()))
+        .collect::<BTreeMap<_, _>>();
+    let target = target
+        .iter()
+        .map(|kv| (kv.key.as_str(), kv.value.expose()))
+        .collect::<BTreeMap<_, _>>();
+    let mut drifts = source
+        .iter()
+        .filter_map(|(key, value)| match target.get(key) {
+            None => Some((*key, Kind::Missing)),
+            Some(other) if other != value => Some((*key, Kind::Changed)),
+            Some(_) => None,
+        })
+        .chain(
+            target
+                .keys()
+                .filter(|key| !source.contains_key(*key))
+                .map(|key| (*key, Kind::Extra)),
+        )
+        .map(|(key, kind)| Drift {
+            key: key.to_string(),
+            kind,
+        })
+        .collect::<Vec<_>>();
+    drifts.sort();
+    drifts
+}
+
+/// Post reports to a webhook, as a Slack compatible `{"text": ...}` payload
+///
+/// # Errors
+///
+/// This function will return an error if the webhook cannot be called or does
+/// not respond with a success status
+#[cfg(feature = "native")]
+pub fn notify(url: &str, reports: &[Report], synced: bool) -> Result<()> {
+    let mut text = String::from("teller: secrets drifted");
+    for report in reports {
+        text.push_str("\n• ");
+        text.push_str(&report.summary());
+    }
+    if synced {
+        text.push_str("\nsynced targets from their sources");
+    }
+    ureq::post(url)
+        .send_json(serde_json::json!({ "text": text }))
+        .map_err(|err| Error::Message(format!("webhook {url}: {err}")))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_maps() {
+        let source = [
+            KV::from_kv("DB_USER", "admin"),
+            KV::from_kv("DB_PASS", "s3cret"),
+            KV::from_kv("API_KEY", "k1"),
+        ];
+        let target = [
+            KV::from_kv("DB_USER", "admin"),
+            KV::from_kv("DB_PASS", "old"),
+            KV::from_kv("DEBUG", "1"),
+        ];
+        let report = Report {
+            from: "dot1/dev".to_string(),
+            to: "dot2/dev".to_string(),
+            drifts: compare(&source, &target),
+        };
+        assert_eq!(
+            report.drifts,
+            vec![
+                Drift {
+                    key: "API_KEY".to_string(),
+                    kind: Kind::Missing
+                },
+                Drift {
+                    key: "DB_PASS".to_string(),
+                    kind: Kind::Changed
+                },
+                Drift {
+                    key: "DEBUG".to_string(),
+                    kind: Kind::Extra
+                },
+            ]
+        );
+        assert_eq!(
+            report.summary(),
+            "dot1/dev -> dot2/dev: 1 missing, 1 changed, 1 extra (API_KEY, DB_PASS, DEBUG)"
+        );
+        assert!(compare(&source, &source).is_empty());
+    }
+}
@@ -0,0 +1,183 @@
+//! Values of `teller.yml` encrypted with [age](https://age-encryption.org),
+//! so configurations embedding tokens can be committed:
+//!
+//! ```yaml
+//! providers:
+//!   vault:
+//!     kind: hashicorp
+//!     options:
+//!       token: !encrypted YWdlLWVuY3J5cHRpb24ub3JnL3Yx...
+//! ```
+//!
+//! Values are decrypted when the configuration is loaded, with the age
+//! identity file [`IDENTITY_ENV`] points to.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_yaml::value::Tag;
+use serde_yaml::Value;
+use zeroize::Zeroizing;
+
+use crate::{Error, Result};
+
+/// YAML tag of encrypted values
+pub const TAG: &str = "encrypted";
+
+/// Env variable with the path of the age identity file encrypted values are
+/// decrypted with
+pub const IDENTITY_ENV: &str = "TELLER_IDENTITY";
+
+/// Encrypt a value to age recipients (`age1...`), as the base64 text of a
+/// `!encrypted` value
+///
+/// # Errors
+///
+/// This function will return an error if a recipient is not valid
+pub fn encrypt(plain: &str, recipients: &[String]) -> Result<String> {
+    let recipients = recipients
+        .iter()
+        .map(|recipient| {
+            age::x25519::Recipient::from_str(recipient)
+                .map_err(|err| Error::Message(format!("invalid recipient '{recipient}': {err}")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if recipients.is_empty() {
+        return Err(Error::Message("no recipients given".to_string()));
+    }
+    let encryptor =
+        age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+            .map_err(age_error)?;
+    let mut out = vec![];
+    let mut writer = encryptor.wrap_output(&mut out)?;
+    writer.write_all(plain.as_bytes())?;
+    writer.finish()?;
+    Ok(STANDARD.encode(out))
+}
+
+/// Decrypt the base64 text of a `!encrypted` value
+///
+/// # Errors
+///
+/// This function will return an error if the value cannot be decrypted with
+/// any of the identities
+pub fn decrypt(encrypted: &str, identities: &[Box<dyn age::Identity>]) -> Result<String> {
+    let data = STANDARD
+        .decode(encrypted.trim())
+        .map_err(|err| Error::Message(format!("encrypted value is not base64: {err}")))?;
+    let decryptor = age::Decryptor::new(&data[..]).map_err(age_error)?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(AsRef::as_ref))
+        .map_err(age_error)?;
+    let mut plain = Zeroizing::new(String::new());
+    reader.read_to_string(&mut plain)?;
+    Ok(plain.to_string())
+}
+
+/// The identities of the file [`IDENTITY_ENV`] points to
+///
+/// # Errors
+///
+/// This function will return an error if the variable is not set or the
+/// file is not a valid identity file
+pub fn identities() -> Result<Vec<Box<dyn age::Identity>>> {
+    let path = std::env::var_os(IDENTITY_ENV)
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            Error::Message(format!(
+                "configuration has encrypted values, set {IDENTITY_ENV} to an age identity file"
+            ))
+        })?;
+    identity_file(&path)
+}
+
+/// The identities of an age identity file, as written by `age-keygen`
+///
+/// # Errors
+///
+/// This function will return an error if the file is not a valid identity file
+pub fn identity_file(path: &Path) -> Result<Vec<Box<dyn age::Identity>>> {
+    age::IdentityFile::from_file(path.to_string_lossy().to_string())?
+        .into_identities()
+        .map_err(age_error)
+}
+
+/// Whether a document has `!encrypted` values, without parsing it
+#[must_use]
+pub fn has_encrypted(text: &str) -> bool {
+    text.contains(&format!("!{TAG}"))
+}
+
+/// Whether a YAML value has `!encrypted` values
+#[must_use]
+pub fn contains_tagged(value: &Value) -> bool {
+    match value {
+        Value::Tagged(tagged) => tagged.tag == Tag::new(TAG) || contains_tagged(&tagged.value),
+        Value::Sequence(seq) => seq.iter().any(contains_tagged),
+        Value::Mapping(map) => map.values().any(contains_tagged),
+        _ => false,
+    }
+}
+
+/// Replace `!encrypted` values by their decrypted text
+///
+/// # Errors
+///
+/// This function will return an error if a value cannot be decrypted
+pub fn decrypt_tagged(value: &mut Value, identities: &[Box<dyn age::Identity>]) -> Result<()> {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == Tag::new(TAG) => {
+            let Value::String(encrypted) = &tagged.value else {
+                return Err(Error::Message(format!("!{TAG} values must be strings")));
+            };
+            *value = Value::String(decrypt(encrypted, identities)?);
+        }
+        Value::Tagged(tagged) => decrypt_tagged(&mut tagged.value, identities)?,
+        Value::Sequence(seq) => {
+            for item in seq {
+                decrypt_tagged(item, identities)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                decrypt_tagged(item, identities)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn age_error(err: impl std::fmt::Display) -> Error {
+    Error::Message(format!("encrypted value: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrypt_config_values() {
+        let identity = age::x25519::Identity::generate();
+        let encrypted = encrypt("t0ken", &[identity.to_public().to_string()]).unwrap();
+        assert!(!String::from_utf8_lossy(&STANDARD.decode(&encrypted).unwrap()).contains("t0ken"));
+
+        let text = format!("options:\n  address: http://vault\n  token: !encrypted {encrypted}\n");
+        assert!(has_encrypted(&text));
+        let mut value: Value = serde_yaml::from_str(&text).unwrap();
+        assert!(contains_tagged(&value));
+        let identities: Vec<Box<dyn age::Identity>> = vec![Box::new(identity)];
+        decrypt_tagged(&mut value, &identities).unwrap();
+        assert_eq!(
+            value,
+            serde_yaml::from_str::<Value>("options:\n  address: http://vault\n  token: t0ken\n")
+                .unwrap()
+        );
+        assert!(!contains_tagged(&value));
+
+        let other: Vec<Box<dyn age::Identity>> = vec![Box::new(age::x25519::Identity::generate())];
+        assert!(decrypt(&encrypted, &other).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! A `.env.example` listing the keys teller maps, with placeholder values and
+//! comments telling where each key comes from, so onboarding docs follow
+//! `.teller.yml`:
+//!
+//! ```text
+//! # vault (hashicorp), map prod: secret/data/prod
+//! DB_PASS=
+//! # from stripe-key
+//! STRIPE_KEY=
+//! ```
+//!
+//! Keys are those listed in each map's `keys`. Maps mapping all of their keys
+//! have them fetched from their provider, names only, unless fetching is off.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+use teller_providers::config::{PathMap, KV};
+use teller_providers::providers::ProviderKind;
+
+/// Header of generated files
+const HEADER: &str = "# Generated by `teller generate example-env`, do not edit.\n# Values live \
+                      in the providers of .teller.yml, get them with `teller run` or `teller \
+                      env`.\n";
+
+#[derive(Debug, Clone, Default)]
+pub struct Opts {
+    /// Value written for every key, empty by default
+    pub placeholder: String,
+    /// Fetch the keys of maps that map all of their keys from their provider
+    pub fetch: bool,
+}
+
+/// The keys of a map, as they would be exported
+#[derive(Debug, Clone)]
+pub struct MapKeys<'a> {
+    pub provider: &'a str,
+    pub kind: ProviderKind,
+    pub pm: &'a PathMap,
+    /// none when the map maps all of its keys and they were not fetched
+    pub keys: Option<Vec<KV>>,
+}
+
+/// Render maps as a `.env.example`. A key mapped by more than one map is only
+/// listed for the first.
+#[must_use]
+pub fn render(maps: &[MapKeys<'_>], opts: &Opts) -> String {
+    let mut out = HEADER.to_string();
+    let mut seen = BTreeSet::new();
+    for map in maps {
+        let _ = write!(
+            out,
+            "\n# {} ({}), map {}: {}\n",
+            map.provider, map.kind, map.pm.id, map.pm.path
+        );
+        let Some(keys) = &map.keys else {
+            out.push_str("# all keys of the map, not fetched\n");
+            continue;
+        };
+        let prefix = map.pm.prefix.as_deref().unwrap_or_default();
+        for kv in keys {
+            if !seen.insert(kv.key.clone()) {
+                let _ = writeln!(out, "# {} is listed above", kv.key);
+                continue;
+            }
+            if kv.key != format!("{prefix}{}", kv.from_key) {
+                let _ = writeln!(out, "# from {}", kv.from_key);
+            }
+            let _ = writeln!(out, "{}={}", kv.key, opts.placeholder);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use teller_providers::config::ProviderInfo;
+
+    use super::*;
+
+    #[test]
+    fn example_env() {
+        let mut prod = PathMap::from_path("secret/data/prod");
+        prod.id = "prod".to_string();
+        prod.prefix = Some("APP_".to_string());
+        let mut dev = PathMap::from_path("dev.env");
+        dev.id = "dev".to_string();
+        let info = ProviderInfo {
+            kind: ProviderKind::Inmem,
+            name: "mem".to_string(),
+        };
+        let maps = vec![
+            MapKeys {
+                provider: "mem",
+                kind: ProviderKind::Inmem,
+                pm: &prod,
+                keys: Some(vec![
+                    KV::from_value("", "stripe-key", "STRIPE_KEY", &prod, info.clone()),
+                    KV::from_value("", "DB_PASS", "DB_PASS", &prod, info),
+                ]),
+            },
+            MapKeys {
+                provider: "local",
+                kind: ProviderKind::Inmem,
+                pm: &dev,
+                keys: None,
+            },
+        ];
+        assert_eq!(
+            render(
+                &maps,
+                &Opts {
+                    placeholder: "changeme".to_string(),
+                    fetch: false,
+                }
+            ),
+            format!(
+                "{HEADER}
+# mem (inmem), map prod: secret/data/prod
+# from stripe-key
+APP_STRIPE_KEY=changeme
+APP_DB_PASS=changeme
+
+# local (inmem), map dev: dev.env
+# all keys of the map, not fetched
+"
+            )
+        );
+    }
+}
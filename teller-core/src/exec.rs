@@ -1,16 +1,45 @@
-use std::{collections::HashMap, path::Path, process::Output};
+use std::{borrow::Cow, collections::HashMap, path::Path, process::Output};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use teller_providers::config::KV;
 
 // use crate::{Error, Result};
 // use teller_providers::errors::{Error, Result};
-use crate::{Error, Result};
+use crate::{export, Error, Result};
+#[allow(clippy::struct_excessive_bools)]
 pub struct Opts<'a> {
     pub pwd: &'a Path,
     pub capture: bool,
     pub sh: bool,
     pub reset_env: bool,
+    /// write the key-values to the command's stdin in this format
+    pub stdin: Option<export::Format>,
+    /// do not set the key-values as environment variables
+    pub no_env: bool,
+    /// variables kept on top of [`ENV_OK`] when resetting the environment,
+    /// names or globs
+    pub keep_env: &'a [String],
+    /// variables never passed on from teller's environment, names or globs
+    pub drop_env: &'a [String],
+    /// static variables, e.g. from env files, set on top of teller's
+    /// environment and below the key-values
+    pub extra_env: &'a [(String, String)],
+    /// keys of SSH private keys, given to the command through an ephemeral
+    /// agent instead of as variables. Only with [`crate::teller::Teller`].
+    pub ssh_keys: &'a [String],
+    /// write SSH keys to temporary identity files instead of an agent
+    pub ssh_identity_files: bool,
+}
+
+lazy_static! {
+    static ref PLACEHOLDER: Regex =
+        Regex::new(r"\{\{\s*key:([^}\s]+)\s*\}\}").expect("valid regex");
 }
 
-const ENV_OK: &[&str] = &[
+/// Variables kept when resetting the environment
+pub const ENV_OK: &[&str] = &[
     "USER",
     "HOME",
     "PATH",
@@ -25,17 +54,36 @@ const ENV_OK: &[&str] = &[
     "LOGNAME",
 ];
 
+/// Variables Windows programs need to run at all, also kept when resetting the
+/// environment there
+#[cfg(windows)]
+const ENV_OK_WINDOWS: &[&str] = &[
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "TEMP",
+    "TMP",
+    "USERNAME",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "PROGRAMDATA",
+    "PROGRAMFILES",
+];
+
 /// Run a command
 ///
 /// # Errors
 ///
 /// This function will return an error if running command fails
 pub fn cmd(cmdstr: &str, env_kvs: &[(String, String)], opts: &Opts<'_>) -> Result<Output> {
-    let words = if opts.sh {
-        shell_command_argv(cmdstr.into())
-    } else {
-        shell_words::split(cmdstr)?.iter().map(Into::into).collect()
-    };
+    if opts.sh {
+        let env_map = env_map(env_kvs, opts)?;
+        return spawn(&shell_expression(cmdstr), &env_map, env_kvs, opts);
+    }
+    let words = shell_words::split(cmdstr)?;
     cmd_slice(
         words
             .iter()
@@ -47,38 +95,250 @@ pub fn cmd(cmdstr: &str, env_kvs: &[(String, String)], opts: &Opts<'_>) -> Resul
     )
 }
 
-fn cmd_slice(words: &[&str], env_kvs: &[(String, String)], opts: &Opts<'_>) -> Result<Output> {
-    // env handling
-    let mut env_map: HashMap<_, _> = if opts.reset_env {
-        std::env::vars()
-            .filter(|(k, _)| ENV_OK.contains(&k.as_str()))
-            .collect()
-    } else {
-        std::env::vars().collect()
-    };
-
-    for (k, v) in env_kvs {
-        env_map.insert(k.clone(), v.clone());
-    }
+/// Run a command given as separate arguments, without parsing or a shell. On
+/// Windows the program is looked up with `PATHEXT`, so `npm` finds `npm.cmd`.
+///
+/// # Errors
+///
+/// This function will return an error if running command fails
+pub fn cmd_slice(words: &[&str], env_kvs: &[(String, String)], opts: &Opts<'_>) -> Result<Output> {
+    let env_map = env_map(env_kvs, opts)?;
 
     // no shell
     let (first, rest) = words
         .split_first()
         .ok_or_else(|| Error::Message("command has not enough arguments".to_string()))?;
 
-    let mut expr = duct::cmd(Path::new(first), rest)
-        .dir(opts.pwd)
-        .full_env(&env_map);
+    // a bare name is looked up in PATH, only actual paths are taken as paths
+    let expr = if first.contains(['/', std::path::MAIN_SEPARATOR]) {
+        duct::cmd(Path::new(first), rest)
+    } else {
+        duct::cmd(program(first, &env_map), rest)
+    };
+    spawn(&expr, &env_map, env_kvs, opts)
+}
+
+fn env_map(env_kvs: &[(String, String)], opts: &Opts<'_>) -> Result<HashMap<String, String>> {
+    // env handling
+    let keep = globs(opts.keep_env)?;
+    let drop = globs(opts.drop_env)?;
+    let mut env_map: HashMap<_, _> = std::env::vars()
+        .filter(|(k, _)| !drop.is_match(k) && (!opts.reset_env || is_env_ok(k) || keep.is_match(k)))
+        .collect();
+    env_map.extend(opts.extra_env.iter().cloned());
+
+    if !opts.no_env {
+        for (k, v) in env_kvs {
+            env_map.insert(k.clone(), v.clone());
+        }
+    }
+    Ok(env_map)
+}
+
+fn spawn(
+    expr: &duct::Expression,
+    env_map: &HashMap<String, String>,
+    env_kvs: &[(String, String)],
+    opts: &Opts<'_>,
+) -> Result<Output> {
+    let mut expr = expr.dir(opts.pwd).full_env(env_map);
 
     if opts.capture {
         expr = expr.stdout_capture();
     }
+    if let Some(format) = &opts.stdin {
+        let kvs = env_kvs
+            .iter()
+            .map(|(k, v)| KV::from_kv(k, v))
+            .collect::<Vec<_>>();
+        expr = expr.stdin_bytes(format.export(&kvs)?);
+    }
+
+    // failing commands are told apart from commands failing to start
+    let expr = expr.unchecked();
+    #[cfg(windows)]
+    {
+        let handle = expr.start().map_err(Error::Exec)?;
+        // keep the job open until the command is done, closing it (or teller
+        // being killed) kills whatever the command left running. The command
+        // is already running, so failing here only loses that.
+        let _job = windows::Job::for_pids(&handle.pids())
+            .inspect_err(|err| tracing::warn!(error = %err, "cannot create job object"))
+            .ok();
+        return checked(handle.into_output()?);
+    }
+    #[cfg(not(windows))]
+    {
+        checked(expr.run().map_err(Error::Exec)?)
+    }
+}
+
+fn checked(output: Output) -> Result<Output> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(Error::Child(output.status))
+    }
+}
 
-    Ok(expr.run()?)
+#[cfg(not(windows))]
+fn is_env_ok(name: &str) -> bool {
+    ENV_OK.contains(&name)
+}
+
+#[cfg(windows)]
+fn is_env_ok(name: &str) -> bool {
+    ENV_OK
+        .iter()
+        .chain(ENV_OK_WINDOWS)
+        .any(|ok| ok.eq_ignore_ascii_case(name))
+}
+
+#[cfg(not(windows))]
+fn program(name: &str, _env_map: &HashMap<String, String>) -> String {
+    name.to_string()
+}
+
+/// Find a program in `PATH` with each extension of `PATHEXT`, as `cmd.exe`
+/// does. Windows itself only tries `.exe`.
+#[cfg(windows)]
+fn program(name: &str, env_map: &HashMap<String, String>) -> String {
+    let var = |key: &str| {
+        env_map
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    };
+    if Path::new(name).extension().is_some() {
+        return name.to_string();
+    }
+    let exts = var("PATHEXT").unwrap_or(".COM;.EXE;.BAT;.CMD");
+    std::env::split_paths(var("PATH").unwrap_or_default())
+        .flat_map(|dir| {
+            exts.split(';')
+                .filter(|ext| !ext.is_empty())
+                .map(move |ext| dir.join(format!("{name}{ext}")))
+        })
+        .find(|candidate| candidate.is_file())
+        .map_or_else(|| name.to_string(), |found| found.display().to_string())
 }
 
 #[cfg(unix)]
-fn shell_command_argv(command: String) -> Vec<String> {
+pub(crate) fn shell_expression(command: &str) -> duct::Expression {
+    let argv = shell_command_argv(command.to_string());
+    duct::cmd(&argv[0], &argv[1..])
+}
+
+/// `cmd.exe /S /C "<command>"`, passed as is: `/S` strips exactly the outer
+/// quotes, instead of the MSVCRT escaping arguments normally get which
+/// `cmd.exe` does not understand
+#[cfg(windows)]
+pub(crate) fn shell_expression(command: &str) -> duct::Expression {
+    use std::os::windows::process::CommandExt;
+
+    let comspec = shell_command_argv(String::new()).swap_remove(0);
+    let command = format!("\"{command}\"");
+    duct::cmd(comspec, ["/S", "/C"]).before_spawn(move |cmd| {
+        cmd.raw_arg(&command);
+        Ok(())
+    })
+}
+
+/// Join arguments into a command line for the platform's shell: POSIX quoting
+/// on Unix, MSVCRT quoting on Windows
+#[must_use]
+pub fn join_args(args: &[String]) -> String {
+    if cfg!(windows) {
+        args.iter()
+            .map(|arg| msvcrt_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        shell_words::join(args)
+    }
+}
+
+/// Quote an argument so that programs using the MSVCRT rules (most Windows
+/// programs) read it back unchanged: backslashes are only special before a
+/// double quote
+#[must_use]
+pub fn msvcrt_quote(arg: &str) -> Cow<'_, str> {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '\x0b', '"']) {
+        return Cow::Borrowed(arg);
+    }
+    let mut out = String::with_capacity(arg.len() + 2);
+    out.push('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                out.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                out.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                out.extend(std::iter::repeat_n('\\', backslashes));
+                out.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    out.extend(std::iter::repeat_n('\\', backslashes * 2));
+    out.push('"');
+    Cow::Owned(out)
+}
+
+fn globs(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        // variable names are case insensitive on Windows
+        let glob = GlobBuilder::new(pattern)
+            .case_insensitive(cfg!(windows))
+            .build()
+            .map_err(|err| {
+                Error::Message(format!("invalid environment pattern '{pattern}': {err}"))
+            })?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|err| Error::Message(format!("invalid environment patterns: {err}")))
+}
+
+/// Replace `{{key:NAME}}` placeholders in command arguments with values
+///
+/// # Errors
+///
+/// This function will return an error if a placeholder names a missing key
+pub fn substitute_args(args: &[&str], env_kvs: &[(String, String)]) -> Result<Vec<String>> {
+    args.iter()
+        .map(|arg| {
+            let mut missing = None;
+            let replaced = PLACEHOLDER.replace_all(arg, |caps: &Captures<'_>| {
+                let key = &caps[1];
+                env_kvs.iter().find(|(k, _)| k == key).map_or_else(
+                    || {
+                        missing.get_or_insert_with(|| key.to_string());
+                        String::new()
+                    },
+                    |(_, v)| v.clone(),
+                )
+            });
+            missing.map_or_else(
+                || Ok(replaced.to_string()),
+                |key| {
+                    Err(Error::Message(format!(
+                        "no key '{key}' for placeholder in command"
+                    )))
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+pub(crate) fn shell_command_argv(command: String) -> Vec<String> {
     use std::env;
 
     let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into());
@@ -86,13 +346,92 @@ fn shell_command_argv(command: String) -> Vec<String> {
 }
 
 #[cfg(windows)]
-fn shell_command_argv(command: String) -> Vec<String> {
+pub(crate) fn shell_command_argv(command: String) -> Vec<String> {
     let comspec = std::env::var_os("COMSPEC")
         .and_then(|s| s.into_string().ok())
         .unwrap_or_else(|| "cmd.exe".into());
     vec![comspec, "/C".into(), command]
 }
 
+/// Job objects, to terminate a command's whole process tree with it
+#[cfg(windows)]
+mod windows {
+    use std::{io, mem, ptr};
+
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        System::{
+            JobObjects::{
+                AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+                SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+                JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            },
+            Threading::{OpenProcess, PROCESS_SET_QUOTA, PROCESS_TERMINATE},
+        },
+    };
+
+    /// A job that kills its processes, and the processes they start, when
+    /// dropped
+    pub struct Job(HANDLE);
+
+    impl Job {
+        /// Create a job and assign the processes to it
+        ///
+        /// # Errors
+        ///
+        /// This function will return an error if the job cannot be created or
+        /// a process cannot be assigned to it
+        pub fn for_pids(pids: &[u32]) -> io::Result<Self> {
+            // SAFETY: no security attributes and no name are valid arguments
+            let job = Self(unsafe { CreateJobObjectW(ptr::null(), ptr::null()) });
+            if job.0 == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: the structure is plain data, all zeros is a valid value
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            // SAFETY: `info` is the structure for this information class, and
+            // outlives the call
+            let ok = unsafe {
+                SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    ptr::addr_of!(info).cast(),
+                    u32::try_from(mem::size_of_val(&info)).unwrap_or(u32::MAX),
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            for pid in pids {
+                // SAFETY: opening a process by id has no preconditions
+                let process =
+                    unsafe { OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0, *pid) };
+                if process == 0 {
+                    // already exited
+                    continue;
+                }
+                // SAFETY: both handles are open
+                let ok = unsafe { AssignProcessToJobObject(job.0, process) };
+                let err = io::Error::last_os_error();
+                // SAFETY: the process handle is open and not used after this
+                unsafe { CloseHandle(process) };
+                if ok == 0 {
+                    return Err(err);
+                }
+            }
+            Ok(job)
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: the job handle is open and owned by this value
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -102,8 +441,8 @@ mod tests {
     use teller_providers::config::KV;
     use teller_providers::providers::ProviderKind;
 
-    use super::cmd;
     use super::Opts;
+    use super::{cmd, cmd_slice, msvcrt_quote, substitute_args};
 
     #[test]
     #[cfg(not(windows))]
@@ -119,13 +458,20 @@ mod tests {
                     name: "test".to_string(),
                 },
             ))
-            .map(|kv| (kv.key.clone(), kv.value.clone()))
+            .map(|kv| (kv.key.clone(), kv.value.expose().to_string()))
             .collect::<Vec<_>>(),
             &Opts {
                 pwd: Path::new("."),
                 capture: true,
                 reset_env: true,
                 sh: true,
+                stdin: None,
+                no_env: false,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
             },
         )
         .unwrap();
@@ -133,6 +479,168 @@ mod tests {
         assert_debug_snapshot!(s);
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn run_multiline_env() {
+        let cert = "-----BEGIN CERTIFICATE-----\nMII=\n-----END CERTIFICATE-----\n";
+        let out = cmd(
+            "printf %s \"$CERT\"",
+            &[("CERT".to_string(), cert.to_string())],
+            &Opts {
+                pwd: Path::new("."),
+                capture: true,
+                reset_env: true,
+                sh: true,
+                stdin: None,
+                no_env: false,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout[..]), cert);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn run_with_stdin() {
+        let out = cmd(
+            "/bin/sh -c 'cat; echo; echo no_env$MY_VAR'",
+            &[("MY_VAR".to_string(), "shazam".to_string())],
+            &Opts {
+                pwd: Path::new("."),
+                capture: true,
+                reset_env: true,
+                sh: false,
+                stdin: Some(crate::export::Format::JSON),
+                no_env: true,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&out.stdout[..]),
+            "{\"MY_VAR\":\"shazam\"}\nno_env\n"
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn extra_env_below_kvs() {
+        let out = cmd(
+            "/bin/sh -c 'echo $STATIC $SECRET'",
+            &[("SECRET".to_string(), "from-provider".to_string())],
+            &Opts {
+                pwd: Path::new("."),
+                capture: true,
+                reset_env: true,
+                sh: false,
+                stdin: None,
+                no_env: false,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[
+                    ("STATIC".to_string(), "from-file".to_string()),
+                    ("SECRET".to_string(), "from-file".to_string()),
+                ],
+                ssh_keys: &[],
+                ssh_identity_files: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&out.stdout[..]),
+            "from-file from-provider\n"
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn keep_and_drop_env() {
+        // cargo sets CARGO_PKG_* and CARGO_MANIFEST_DIR when running tests
+        let out = cmd(
+            "/usr/bin/env",
+            &[],
+            &Opts {
+                pwd: Path::new("."),
+                capture: true,
+                reset_env: true,
+                sh: false,
+                stdin: None,
+                no_env: false,
+                keep_env: &["CARGO_PKG_*".to_string()],
+                drop_env: &["CARGO_PKG_VERSION*".to_string(), "PATH".to_string()],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
+            },
+        )
+        .unwrap();
+        let stdout = String::from_utf8_lossy(&out.stdout[..]).to_string();
+        assert!(stdout.contains("CARGO_PKG_NAME=teller-core"));
+        assert!(!stdout.contains("CARGO_PKG_VERSION"));
+        assert!(!stdout.contains("CARGO_MANIFEST_DIR="));
+        assert!(!stdout.contains("PATH="));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn run_program_from_path() {
+        let out = cmd_slice(
+            &["echo", "hello world"],
+            &[],
+            &Opts {
+                pwd: Path::new("."),
+                capture: true,
+                reset_env: true,
+                sh: false,
+                stdin: None,
+                no_env: false,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout[..]), "hello world\n");
+    }
+
+    #[test]
+    fn quote_msvcrt() {
+        assert_eq!(msvcrt_quote("plain"), "plain");
+        assert_eq!(msvcrt_quote(""), "\"\"");
+        assert_eq!(
+            msvcrt_quote(r"C:\Program Files\x"),
+            r#""C:\Program Files\x""#
+        );
+        assert_eq!(msvcrt_quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(msvcrt_quote(r"trailing \"), r#""trailing \\""#);
+        assert_eq!(msvcrt_quote(r#"a\"b"#), r#""a\\\"b""#);
+    }
+
+    #[test]
+    fn substitute_placeholders() {
+        let kvs = [("TOKEN".to_string(), "s3cret".to_string())];
+        assert_eq!(
+            substitute_args(
+                &["curl", "-H", "auth: {{key:TOKEN}}", "{{ key:TOKEN }}"],
+                &kvs
+            )
+            .unwrap(),
+            vec!["curl", "-H", "auth: s3cret", "s3cret"]
+        );
+        assert!(substitute_args(&["{{key:MISSING}}"], &kvs).is_err());
+    }
+
     #[ignore]
     #[test]
     fn env_reset() {
@@ -147,13 +655,20 @@ mod tests {
                     name: "test".to_string(),
                 },
             ))
-            .map(|kv| (kv.key.clone(), kv.value.clone()))
+            .map(|kv| (kv.key.clone(), kv.value.expose().to_string()))
             .collect::<Vec<_>>(),
             &Opts {
                 pwd: Path::new("."),
                 capture: true,
                 reset_env: false, // <-- notice this!
                 sh: false,
+                stdin: None,
+                no_env: false,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
             },
         )
         .unwrap();
@@ -173,13 +688,20 @@ mod tests {
                     name: "test".to_string(),
                 },
             ))
-            .map(|kv| (kv.key.clone(), kv.value.clone()))
+            .map(|kv| (kv.key.clone(), kv.value.expose().to_string()))
             .collect::<Vec<_>>(),
             &Opts {
                 pwd: Path::new("."),
                 capture: true,
                 reset_env: true, // <-- reset env
                 sh: false,
+                stdin: None,
+                no_env: false,
+                keep_env: &[],
+                drop_env: &[],
+                extra_env: &[],
+                ssh_keys: &[],
+                ssh_identity_files: false,
             },
         )
         .unwrap();
@@ -7,7 +7,7 @@ use serde_derive::{Deserialize, Serialize};
 use serde_variant::to_variant_name;
 use strum::EnumIter;
 use strum::IntoEnumIterator;
-use teller_providers::config::KV;
+use teller_providers::{config::KV, envfile};
 
 use crate::{Error, Result};
 
@@ -32,6 +32,8 @@ pub enum Format {
     ENV,
     #[serde(rename = "shell")]
     Shell,
+    #[serde(rename = "fish")]
+    Fish,
 }
 
 impl std::fmt::Display for Format {
@@ -67,6 +69,7 @@ impl Format {
             Self::CSV => Self::export_csv(kvs),
             Self::ENV => Ok(Self::export_env(kvs)),
             Self::Shell => Ok(Self::export_shell(kvs)),
+            Self::Fish => Ok(Self::export_fish(kvs)),
         }
     }
 
@@ -75,7 +78,26 @@ impl Format {
         out.push_str("#!/bin/sh\n");
 
         for kv in kvs {
-            out.push_str(&format!("export {}='{}'\n", kv.key, kv.value));
+            // single quotes keep everything literal, including newlines, except
+            // single quotes themselves which are closed, escaped and reopened
+            out.push_str(&format!(
+                "export {}='{}'\n",
+                kv.key,
+                kv.value.expose().replace('\'', r"'\''")
+            ));
+        }
+        out
+    }
+
+    fn export_fish(kvs: &[KV]) -> String {
+        let mut out = String::new();
+        for kv in kvs {
+            // in fish single quotes, only backslashes and single quotes are escaped
+            out.push_str(&format!(
+                "set -gx {} '{}'\n",
+                kv.key,
+                kv.value.expose().replace('\\', r"\\").replace('\'', r"\'")
+            ));
         }
         out
     }
@@ -83,7 +105,11 @@ impl Format {
     fn export_env(kvs: &[KV]) -> String {
         let mut out = String::new();
         for kv in kvs {
-            out.push_str(&format!("{}={}\n", kv.key, kv.value));
+            out.push_str(&format!(
+                "{}={}\n",
+                kv.key,
+                envfile::quote(kv.value.expose())
+            ));
         }
         out
     }
@@ -91,7 +117,7 @@ impl Format {
     fn export_csv(kvs: &[KV]) -> Result<String> {
         let mut wtr = WriterBuilder::new().from_writer(vec![]);
         for kv in kvs {
-            wtr.write_record(&[kv.key.clone(), kv.value.clone()])?;
+            wtr.write_record(&[kv.key.clone(), kv.value.expose().to_string()])?;
         }
         Ok(String::from_utf8(
             wtr.into_inner()
@@ -100,3 +126,31 @@ impl Format {
         )?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_multiline() {
+        let kvs = [
+            KV::from_kv("CERT", "-----BEGIN-----\nMII=\n-----END-----"),
+            KV::from_kv("QUOTE", "it's"),
+        ];
+        assert_eq!(
+            Format::ENV.export(&kvs).unwrap(),
+            "CERT=\"-----BEGIN-----\\nMII=\\n-----END-----\"\nQUOTE=\"it's\"\n"
+        );
+        assert_eq!(
+            Format::Shell.export(&kvs).unwrap(),
+            "#!/bin/sh\nexport CERT='-----BEGIN-----\nMII=\n-----END-----'\nexport \
+             QUOTE='it'\\''s'\n"
+        );
+        assert_eq!(
+            Format::Fish
+                .export(&[KV::from_kv("PATHS", r"C:\dir"), kvs[1].clone()])
+                .unwrap(),
+            "set -gx PATHS 'C:\\\\dir'\nset -gx QUOTE 'it\\'s'\n"
+        );
+    }
+}
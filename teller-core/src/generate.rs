@@ -0,0 +1,106 @@
+//! Random values generated inside teller, so new secrets go straight to
+//! providers without being displayed, see
+//! [`crate::teller::Teller::generate`].
+
+use rand::{rngs::OsRng, Rng};
+use teller_providers::config::{Charset, Generation};
+
+use crate::{Error, Result};
+
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const PUNCTUATION: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// The characters of a charset
+#[must_use]
+pub fn chars(charset: Charset) -> Vec<u8> {
+    let parts: &[&str] = match charset {
+        Charset::Alnum => &[UPPER, LOWER, DIGITS],
+        Charset::Alpha => &[UPPER, LOWER],
+        Charset::Digits => &[DIGITS],
+        Charset::Hex => &[DIGITS, "abcdef"],
+        Charset::UrlSafe => &[UPPER, LOWER, DIGITS, "_-"],
+        Charset::Ascii => &[UPPER, LOWER, DIGITS, PUNCTUATION],
+    };
+    parts.concat().into_bytes()
+}
+
+/// Parse a policy such as `len=32,charset=alnum`, parts left out are the
+/// defaults of [`Generation`]
+///
+/// # Errors
+///
+/// This function will return an error if a part is unknown or its value is
+/// not valid
+pub fn parse(policy: &str) -> Result<Generation> {
+    let mut generation = Generation::default();
+    for part in policy
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let invalid = || Error::Message(format!("invalid generation policy part '{part}'"));
+        let (name, value) = part.split_once('=').ok_or_else(invalid)?;
+        match name.trim() {
+            "len" | "length" => {
+                generation.length = value.trim().parse().map_err(|_| invalid())?;
+            }
+            "charset" => {
+                generation.charset =
+                    serde_json::from_value(serde_json::Value::String(value.trim().to_string()))
+                        .map_err(|_| {
+                            Error::Message(format!(
+                                "unknown charset '{value}', use one of alnum, alpha, digits, hex, \
+                                 urlsafe or ascii"
+                            ))
+                        })?;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(generation)
+}
+
+/// A random value, from the operating system's secure random source
+///
+/// # Errors
+///
+/// This function will return an error if the length is 0
+pub fn value(generation: &Generation) -> Result<String> {
+    if generation.length == 0 {
+        return Err(Error::Message(
+            "generated values need a length of at least 1".to_string(),
+        ));
+    }
+    let chars = chars(generation.charset);
+    Ok((0..generation.length)
+        .map(|_| char::from(chars[OsRng.gen_range(0..chars.len())]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_and_values() {
+        assert_eq!(parse("").unwrap(), Generation::default());
+        assert_eq!(
+            parse("len=12, charset=hex").unwrap(),
+            Generation {
+                length: 12,
+                charset: Charset::Hex
+            }
+        );
+        assert!(parse("len=many").is_err());
+        assert!(parse("charset=emoji").is_err());
+        assert!(parse("entropy=high").is_err());
+
+        let hex = value(&parse("len=64,charset=hex").unwrap()).unwrap();
+        assert_eq!(hex.len(), 64);
+        assert!(hex.bytes().all(|c| chars(Charset::Hex).contains(&c)));
+        assert_ne!(hex, value(&parse("len=64,charset=hex").unwrap()).unwrap());
+        assert!(value(&parse("len=0").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! The [git credential helper](https://git-scm.com/docs/gitcredentials)
+//! protocol, so HTTPS credentials live in a provider map instead of
+//! `~/.git-credentials`:
+//!
+//! ```text
+//! $ git config --global credential.helper '!teller git-credential --map vault/git'
+//! ```
+//!
+//! The credentials of a host are the keys `<HOST>_USERNAME` and
+//! `<HOST>_PASSWORD` of the map, with the host upper snake cased:
+//! `github.com` is `GITHUB_COM_USERNAME` and `GITHUB_COM_PASSWORD`.
+
+use std::fmt::Write as _;
+
+use crate::{Error, Result};
+
+/// What git asks of the helper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// give the credentials of a host, if any
+    Get,
+    /// keep credentials git used successfully
+    Store,
+    /// forget credentials the host rejected
+    Erase,
+}
+
+/// The attributes git sends and reads, one `key=value` per line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Credential {
+    pub protocol: Option<String>,
+    pub host: Option<String>,
+    pub path: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Credential {
+    /// Parse what git writes to the helper's stdin, up to an empty line.
+    /// Attributes teller has no use for are skipped.
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        let mut credential = Self::default();
+        for line in text.lines().take_while(|line| !line.is_empty()) {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = Some(value.to_string());
+            match key {
+                "protocol" => credential.protocol = value,
+                "host" => credential.host = value,
+                "path" => credential.path = value,
+                "username" => credential.username = value,
+                "password" => credential.password = value,
+                _ => {}
+            }
+        }
+        credential
+    }
+
+    /// The credentials as git reads them from `get`
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (key, value) in [("username", &self.username), ("password", &self.password)] {
+            if let Some(value) = value {
+                let _ = writeln!(text, "{key}={value}");
+            }
+        }
+        text
+    }
+
+    /// Keys of the username and password of the host, see [module docs](self)
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if git did not send a host
+    pub fn keys(&self) -> Result<(String, String)> {
+        let host = self
+            .host
+            .as_deref()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| Error::Message("git-credential: git sent no host".to_string()))?;
+        let host = host_key(host);
+        Ok((format!("{host}_USERNAME"), format!("{host}_PASSWORD")))
+    }
+}
+
+/// A host as a key, upper snake cased: `git.example.com:8443` is
+/// `GIT_EXAMPLE_COM_8443`
+#[must_use]
+pub fn host_key(host: &str) -> String {
+    host.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credential_protocol() {
+        let credential = Credential::parse(concat!(
+            "protocol=https\nhost=git.example.com:8443\n",
+            "wwwauth[]=Basic\nusername=bot\n",
+            "\nhost=ignored\n"
+        ));
+        assert_eq!(
+            credential,
+            Credential {
+                protocol: Some("https".to_string()),
+                host: Some("git.example.com:8443".to_string()),
+                username: Some("bot".to_string()),
+                ..Credential::default()
+            }
+        );
+        assert_eq!(
+            credential.keys().unwrap(),
+            (
+                "GIT_EXAMPLE_COM_8443_USERNAME".to_string(),
+                "GIT_EXAMPLE_COM_8443_PASSWORD".to_string()
+            )
+        );
+        assert!(Credential::default().keys().is_err());
+        assert_eq!(
+            Credential {
+                password: Some("t0ken".to_string()),
+                ..credential
+            }
+            .to_text(),
+            "username=bot\npassword=t0ken\n"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Process hardening for environments where secrets must not reach disk: no core
+//! dumps (which would hold every collected value) and no swapping.
+
+use crate::{Error, Result};
+
+/// Disable core dumps and lock all memory of the process, current and future,
+/// so secrets are never swapped out. Call before collecting secrets.
+///
+/// # Errors
+///
+/// This function will return an error if the platform is not supported, or the
+/// process is not allowed to lock its memory (see `ulimit -l`)
+pub fn harden() -> Result<()> {
+    disable_core_dumps()?;
+    lock_memory()
+}
+
+/// Disable core dumps, and on Linux, mark the process as not dumpable so it
+/// cannot be attached to or dumped by other unprivileged processes
+///
+/// # Errors
+///
+/// This function will return an error if the platform is not supported or the
+/// limits cannot be set
+#[cfg(unix)]
+pub fn disable_core_dumps() -> Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid rlimit that outlives the call
+    if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &raw const limit) } != 0 {
+        return Err(os_error("cannot disable core dumps"));
+    }
+    #[cfg(target_os = "linux")]
+    // SAFETY: PR_SET_DUMPABLE takes a single integer argument
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } != 0 {
+        return Err(os_error("cannot mark process as not dumpable"));
+    }
+    Ok(())
+}
+
+/// Lock all current and future memory of the process into RAM
+///
+/// # Errors
+///
+/// This function will return an error if the platform is not supported or the
+/// process is not allowed to lock its memory
+#[cfg(unix)]
+pub fn lock_memory() -> Result<()> {
+    // SAFETY: mlockall only takes flags
+    if unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) } != 0 {
+        return Err(os_error(
+            "cannot lock memory (raise the limit with `ulimit -l`)",
+        ));
+    }
+    Ok(())
+}
+
+/// Disable core dumps
+///
+/// # Errors
+///
+/// Not supported on this platform
+#[cfg(not(unix))]
+pub fn disable_core_dumps() -> Result<()> {
+    Err(Error::Message(
+        "disabling core dumps is not supported on this platform".to_string(),
+    ))
+}
+
+/// Lock all current and future memory of the process into RAM
+///
+/// # Errors
+///
+/// Not supported on this platform
+#[cfg(not(unix))]
+pub fn lock_memory() -> Result<()> {
+    Err(Error::Message(
+        "locking memory is not supported on this platform".to_string(),
+    ))
+}
+
+#[cfg(unix)]
+fn os_error(message: &str) -> Error {
+    Error::Message(format!("{message}: {}", std::io::Error::last_os_error()))
+}
+
+#[cfg(test)]
+mod tests {
+    /// Set in the copy of the test binary that hardens itself
+    #[cfg(unix)]
+    const CHILD: &str = "TELLER_HARDEN_TEST_CHILD";
+
+    /// Hardening cannot be undone, so it runs in a copy of the test binary
+    /// running only this test, instead of the process running all of them
+    #[test]
+    #[cfg(unix)]
+    fn no_core_dumps() {
+        if std::env::var_os(CHILD).is_none() {
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "harden::tests::no_core_dumps",
+                    "--test-threads=1",
+                ])
+                .env(CHILD, "1")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+
+        super::disable_core_dumps().unwrap();
+
+        let mut limit = libc::rlimit {
+            rlim_cur: 1,
+            rlim_max: 1,
+        };
+        // SAFETY: `limit` is a valid rlimit that outlives the call
+        assert_eq!(
+            unsafe { libc::getrlimit(libc::RLIMIT_CORE, &raw mut limit) },
+            0
+        );
+        assert_eq!((limit.rlim_cur, limit.rlim_max), (0, 0));
+    }
+}
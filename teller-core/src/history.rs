@@ -0,0 +1,368 @@
+//! A local journal of the changes teller makes to providers, one per
+//! configuration, for providers without versioning of their own (dotenv,
+//! Consul, etcd).
+//!
+//! Entries are appended as JSON lines and chained: each holds the hash of the
+//! one before, so [`Journal::verify`] finds edited or removed entries. Entries
+//! hold key names, never values.
+//!
+//! Journals are kept in `$TELLER_HISTORY_DIR`, or `$XDG_STATE_HOME/teller/history`
+//! (`~/.local/state/teller/history`), named after their configuration's path.
+//!
+//! Next to a journal, [`Undo`] keeps the state of the maps the last put, delete
+//! or copy changed, as it was before, encrypted with an age identity.
+
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use fs_err as fs;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::clock::{Clock, SystemClock};
+use crate::encrypted;
+use crate::policy::Operation;
+use crate::snapshot::Snapshot;
+use crate::{Error, Result};
+
+/// Env variable overriding where journals are kept
+pub const HISTORY_DIR_ENV: &str = "TELLER_HISTORY_DIR";
+
+/// `prev` of the first entry
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// A change to record
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub operation: Operation,
+    /// changed maps, as `<provider>/<map id>`
+    pub maps: Vec<String>,
+    /// changed keys, all keys of the maps when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+    /// where values came from, e.g. the source map of a copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Record {
+    #[must_use]
+    pub const fn new(operation: Operation, maps: Vec<String>) -> Self {
+        Self {
+            operation,
+            maps,
+            keys: vec![],
+            source: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_keys(mut self, keys: Vec<String>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    #[must_use]
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub seq: u64,
+    /// in seconds since the Unix epoch
+    pub time: u64,
+    #[serde(flatten)]
+    pub record: Record,
+    /// hash of the previous entry
+    pub prev: String,
+    /// hash of this entry, over all of its other fields
+    pub hash: String,
+}
+
+impl Entry {
+    fn digest(&self) -> Result<String> {
+        let unhashed = Self {
+            hash: String::new(),
+            ..self.clone()
+        };
+        Ok(hex(&Sha256::digest(serde_json::to_vec(&unhashed)?)))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
+pub struct Journal {
+    path: PathBuf,
+    clock: Arc<dyn Clock>,
+}
+
+impl Journal {
+    #[must_use]
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Stamp entries with this clock's time
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The journal of a configuration file, see [module docs](self)
+    #[must_use]
+    pub fn for_config(config: &Path) -> Self {
+        let config = fs::canonicalize(config).unwrap_or_else(|_| config.to_path_buf());
+        let dir = std::env::var_os(HISTORY_DIR_ENV).map_or_else(
+            || {
+                std::env::var_os("XDG_STATE_HOME")
+                    .map(PathBuf::from)
+                    .or_else(|| home::home_dir().map(|home| home.join(".local").join("state")))
+                    .unwrap_or_default()
+                    .join("teller")
+                    .join("history")
+            },
+            PathBuf::from,
+        );
+        let name = hex(&Sha256::digest(config.to_string_lossy().as_bytes())[..8]);
+        Self::new(dir.join(format!("{name}.jsonl")))
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries, oldest first, none if nothing was recorded yet
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the journal cannot be read or
+    /// parsed
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        fs::read_to_string(&self.path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// Append a change, chained to the last entry
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the journal cannot be read or
+    /// written
+    pub fn record(&self, record: Record) -> Result<Entry> {
+        let last = self.entries()?.pop();
+        let mut entry = Entry {
+            seq: last.as_ref().map_or(1, |last| last.seq + 1),
+            time: self.clock.now(),
+            record,
+            prev: last.map_or_else(|| GENESIS.to_string(), |last| last.hash),
+            hash: String::new(),
+        };
+        entry.hash = entry.digest()?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(entry)
+    }
+
+    /// Check that no entry was edited or removed
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error naming the first entry breaking the
+    /// chain, or if the journal cannot be read
+    pub fn verify(&self) -> Result<usize> {
+        let entries = self.entries()?;
+        let mut prev = GENESIS.to_string();
+        for entry in &entries {
+            if entry.prev != prev || entry.digest()? != entry.hash {
+                return Err(Error::Message(format!(
+                    "history: entry {} of {} was changed, or entries before it were removed",
+                    entry.seq,
+                    self.path.display()
+                )));
+            }
+            prev.clone_from(&entry.hash);
+        }
+        Ok(entries.len())
+    }
+}
+
+/// Gives the age identity (`AGE-SECRET-KEY-1...`) undo states are encrypted
+/// with, called only when one is saved or loaded
+pub type IdentitySource = fn() -> Result<String>;
+
+/// The state of maps before the last change, for `teller undo`
+pub struct Undo {
+    path: PathBuf,
+    identity: IdentitySource,
+}
+
+impl Undo {
+    #[must_use]
+    pub const fn new(path: PathBuf, identity: IdentitySource) -> Self {
+        Self { path, identity }
+    }
+
+    /// The undo state kept next to a journal
+    #[must_use]
+    pub fn for_journal(journal: &Journal, identity: IdentitySource) -> Self {
+        Self::new(journal.path.with_extension("undo"), identity)
+    }
+
+    fn identity(&self) -> Result<age::x25519::Identity> {
+        (self.identity)()?
+            .parse()
+            .map_err(|err| Error::Message(format!("undo: invalid identity: {err}")))
+    }
+
+    /// Keep the state of maps about to change, replacing the one kept before
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the identity cannot be had, or the
+    /// state cannot be written
+    pub fn save(&self, snapshot: &Snapshot) -> Result<()> {
+        let recipient = self.identity()?.to_public().to_string();
+        let encrypted = encrypted::encrypt(&serde_json::to_string(snapshot)?, &[recipient])?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use fs::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&self.path)?.write_all(encrypted.as_bytes())?;
+        Ok(())
+    }
+
+    /// The state kept, none if there is nothing to undo
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the state cannot be read or
+    /// decrypted
+    pub fn load(&self) -> Result<Option<Snapshot>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let identities: Vec<Box<dyn age::Identity>> = vec![Box::new(self.identity()?)];
+        let plain = encrypted::decrypt(&fs::read_to_string(&self.path)?, &identities)?;
+        Ok(Some(serde_json::from_str(&plain)?))
+    }
+
+    /// Forget the state kept, once undone or when it is no longer the state
+    /// before the last change
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the state cannot be removed
+    pub fn clear(&self) -> Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_chain() {
+        let path =
+            std::env::temp_dir().join(format!("teller-history-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let journal = Journal::new(path.clone());
+        assert_eq!(journal.verify().unwrap(), 0);
+
+        let first = journal
+            .record(
+                Record::new(Operation::Put, vec!["vault/app".to_string()])
+                    .with_keys(vec!["API_KEY".to_string()]),
+            )
+            .unwrap();
+        let second = journal
+            .record(Record::new(
+                Operation::Delete,
+                vec!["vault/app".to_string()],
+            ))
+            .unwrap();
+        assert_eq!(first.prev, GENESIS);
+        assert_eq!(second.prev, first.hash);
+        assert_eq!(second.seq, 2);
+        assert_eq!(journal.verify().unwrap(), 2);
+
+        let text = fs::read_to_string(&path).unwrap();
+        fs::write(&path, text.replace("API_KEY", "OTHER_KEY")).unwrap();
+        assert!(journal.verify().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn undo_state() {
+        #[allow(clippy::unnecessary_wraps)]
+        fn identity() -> Result<String> {
+            static IDENTITY: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+            Ok(IDENTITY
+                .get_or_init(|| {
+                    use age::secrecy::ExposeSecret;
+                    age::x25519::Identity::generate()
+                        .to_string()
+                        .expose_secret()
+                        .to_string()
+                })
+                .clone())
+        }
+        let journal = Journal::new(
+            std::env::temp_dir().join(format!("teller-undo-{}.jsonl", std::process::id())),
+        );
+        let undo = Undo::for_journal(&journal, identity);
+        assert_eq!(undo.load().unwrap(), None);
+
+        let snapshot = Snapshot {
+            created_at: 1_700_000_000,
+            maps: vec![crate::snapshot::MapSnapshot {
+                provider: "mem".to_string(),
+                map_id: "dev".to_string(),
+                path: "app/dev".to_string(),
+                kvs: [("DB_PASS".to_string(), "s3cret".into())].into(),
+            }],
+        };
+        undo.save(&snapshot).unwrap();
+        assert!(!fs::read_to_string(&undo.path).unwrap().contains("s3cret"));
+        assert_eq!(undo.load().unwrap(), Some(snapshot));
+        undo.clear().unwrap();
+        assert_eq!(undo.load().unwrap(), None);
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::time::Duration;
+
+use serde_derive::{Deserialize, Serialize};
+use serde_variant::to_variant_name;
+
+use crate::{Error, Result};
+
+/// When a hook runs
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    BeforeCollect,
+    AfterCollect,
+    BeforePut,
+    AfterPut,
+    BeforeDelete,
+    AfterDelete,
+    AfterCopy,
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        to_variant_name(self).expect("only enum supported").fmt(f)
+    }
+}
+
+impl Event {
+    /// A failing hook of a `before_*` event stops the operation, other hooks only
+    /// warn
+    #[must_use]
+    pub const fn is_before(self) -> bool {
+        matches!(
+            self,
+            Self::BeforeCollect | Self::BeforePut | Self::BeforeDelete
+        )
+    }
+}
+
+/// A command to run, or a URL to `POST` the [`Context`] to, as JSON
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hook {
+    /// run with the shell, gets the context as `TELLER_EVENT`, `TELLER_PROVIDERS`,
+    /// `TELLER_MAP_ID` and `TELLER_KEYS` (comma separated) environment variables
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+}
+
+pub type Hooks = BTreeMap<Event, Vec<Hook>>;
+
+/// How long a webhook gets to connect and respond
+#[cfg(feature = "native")]
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a hook is told about an operation. Holds key names only, never values.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+    pub event: Event,
+    pub providers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map_id: Option<String>,
+    pub keys: Vec<String>,
+}
+
+impl Context {
+    #[must_use]
+    pub const fn new(event: Event) -> Self {
+        Self {
+            event,
+            providers: vec![],
+            map_id: None,
+            keys: vec![],
+        }
+    }
+
+    #[must_use]
+    pub fn with_providers(mut self, providers: &[String]) -> Self {
+        self.providers = providers.to_vec();
+        self
+    }
+
+    #[must_use]
+    pub fn with_map_id(mut self, map_id: &str) -> Self {
+        self.map_id = Some(map_id.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn with_keys(mut self, keys: Vec<String>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    #[cfg(feature = "native")]
+    fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("TELLER_EVENT", self.event.to_string()),
+            ("TELLER_PROVIDERS", self.providers.join(",")),
+            ("TELLER_MAP_ID", self.map_id.clone().unwrap_or_default()),
+            ("TELLER_KEYS", self.keys.join(",")),
+        ]
+    }
+}
+
+impl Hook {
+    /// Run the command, then call the webhook. Command output goes to stderr so
+    /// it never mixes with teller's own output.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the command fails or exits with a
+    /// non-zero code, or the webhook cannot be called or does not respond with a
+    /// success status
+    #[cfg(feature = "native")]
+    pub fn run(&self, context: &Context) -> Result<()> {
+        if let Some(command) = &self.command {
+            let mut expr = crate::exec::shell_expression(command).stdout_to_stderr();
+            for (k, v) in context.env() {
+                expr = expr.env(k, v);
+            }
+            expr.run()?;
+        }
+        if let Some(url) = &self.webhook {
+            ureq::AgentBuilder::new()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .post(url)
+                .send_json(context)
+                .map_err(|err| Error::Message(format!("webhook {url}: {err}")))?;
+        }
+        Ok(())
+    }
+
+    /// Hooks cannot run without the `native` feature
+    ///
+    /// # Errors
+    ///
+    /// Always
+    #[cfg(not(feature = "native"))]
+    pub fn run(&self, _context: &Context) -> Result<()> {
+        Err(Error::Message(
+            "hooks are not supported in this build".to_string(),
+        ))
+    }
+}
+
+/// Run all hooks of an event, in order. Returns the errors of the hooks that
+/// failed, so callers decide whether a failure stops the operation.
+#[must_use]
+pub fn run(hooks: &Hooks, context: &Context) -> Vec<Error> {
+    hooks
+        .get(&context.event)
+        .into_iter()
+        .flatten()
+        .filter_map(|hook| hook.run(context).err())
+        .collect()
+}
+
+/// Like [`run`], on the blocking thread pool so commands and webhooks do not
+/// stall the async runtime
+#[cfg(feature = "native")]
+pub async fn spawn(hooks: &Hooks, context: &Context) -> Vec<Error> {
+    let Some(event_hooks) = hooks.get(&context.event) else {
+        return vec![];
+    };
+    let hooks = Hooks::from([(context.event, event_hooks.clone())]);
+    let context = context.clone();
+    tokio::task::spawn_blocking(move || run(&hooks, &context))
+        .await
+        .unwrap_or_else(|err| vec![Error::Message(format!("hook task: {err}"))])
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    fn context() -> Context {
+        Context::new(Event::AfterPut)
+            .with_providers(&["dot1".to_string(), "dot2".to_string()])
+            .with_map_id("dev")
+            .with_keys(vec!["DB_USER".to_string(), "DB_PASS".to_string()])
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn run_command_hooks() {
+        let dir = std::env::temp_dir().join("teller-hooks");
+        fs_err::create_dir_all(&dir).unwrap();
+        let out = dir.join("out.txt");
+        let hooks = Hooks::from([(
+            Event::AfterPut,
+            vec![
+                Hook {
+                    command: Some(format!(
+                        "echo $TELLER_EVENT $TELLER_PROVIDERS $TELLER_MAP_ID $TELLER_KEYS > {}",
+                        out.display()
+                    )),
+                    webhook: None,
+                },
+                Hook {
+                    command: Some("exit 3".to_string()),
+                    webhook: None,
+                },
+            ],
+        )]);
+
+        let errors = run(&hooks, &context());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            fs_err::read_to_string(&out).unwrap(),
+            "after_put dot1,dot2 dev DB_USER,DB_PASS\n"
+        );
+        assert!(run(&hooks, &Context::new(Event::AfterDelete)).is_empty());
+    }
+
+    #[test]
+    fn run_webhooks() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        let hook = Hook {
+            command: None,
+            webhook: Some(url),
+        };
+        hook.run(&context()).unwrap();
+        assert_eq!(
+            server.join().unwrap(),
+            r#"{"event":"after_put","providers":["dot1","dot2"],"map_id":"dev","keys":["DB_USER","DB_PASS"]}"#
+        );
+    }
+}
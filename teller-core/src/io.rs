@@ -1,6 +1,6 @@
 use std::{
-    io::{self, Read},
-    path::Path,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
 };
 
 use fs_err::File;
@@ -18,3 +18,134 @@ pub fn is_binary_file(path: &Path) -> io::Result<bool> {
 
     Ok(false) // No null byte found, likely a text file.
 }
+
+/// Follows a file the way `tail -F` does: keeps reading what gets appended to it,
+/// and starts over when the file is rotated (replaced or truncated).
+pub struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    id: Option<FileId>,
+    pos: u64,
+}
+
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = ();
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn file_id(meta: &std::fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+const fn file_id(_meta: &std::fs::Metadata) -> Option<FileId> {
+    // no cheap stable identity, rotation is only detected by truncation
+    None
+}
+
+/// Outcome of a single [`Follower::read_appended`] call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Appended {
+    /// Bytes appended to the file being followed
+    Data(usize),
+    /// The file was rotated, reading restarts from the top of the new file
+    Rotated,
+}
+
+impl Follower {
+    /// Follow a file. When `from_start` is false, only data appended from now on is read.
+    /// A missing file is fine, it will be picked up once it's created.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file exists but cannot be opened
+    pub fn new(path: &Path, from_start: bool) -> io::Result<Self> {
+        let mut follower = Self {
+            path: path.to_path_buf(),
+            file: None,
+            id: None,
+            pos: 0,
+        };
+        if follower.open()? && !from_start {
+            follower.pos = follower
+                .file
+                .as_ref()
+                .map_or(Ok(0), |f| f.metadata().map(|m| m.len()))?;
+        }
+        Ok(follower)
+    }
+
+    fn open(&mut self) -> io::Result<bool> {
+        match File::open(&self.path) {
+            Ok(file) => {
+                self.id = file_id(&file.metadata()?);
+                self.file = Some(file);
+                self.pos = 0;
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.file = None;
+                self.id = None;
+                self.pos = 0;
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn rotated(&self) -> io::Result<bool> {
+        let Some(file) = &self.file else {
+            return Ok(false);
+        };
+        let current = match std::fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            // moved away and not yet recreated, keep draining the old one
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let replaced = self.id.is_some() && file_id(&current) != self.id;
+        let truncated = file.metadata()?.len() < self.pos;
+        Ok(replaced || truncated)
+    }
+
+    /// Read whatever was appended since the last call into `buf`.
+    ///
+    /// On rotation nothing is read, and [`Appended::Rotated`] is returned so that callers
+    /// can flush what they hold for the old file first.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if IO fails
+    pub fn read_appended(&mut self, buf: &mut Vec<u8>) -> io::Result<Appended> {
+        // not created yet when we started following, all of it is new
+        if self.file.is_none() && !self.open()? {
+            return Ok(Appended::Data(0));
+        }
+        if self.rotated()? {
+            // drain what's left in the old file before switching over
+            let drained = self.read_to_end(buf)?;
+            if drained > 0 {
+                return Ok(Appended::Data(drained));
+            }
+            self.open()?;
+            return Ok(Appended::Rotated);
+        }
+        Ok(Appended::Data(self.read_to_end(buf)?))
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(0);
+        };
+        if file.metadata()?.len() < self.pos {
+            return Ok(0);
+        }
+        file.seek(SeekFrom::Start(self.pos))?;
+        let read = file.read_to_end(buf)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
@@ -1,11 +1,41 @@
+#[cfg(all(unix, feature = "native"))]
+pub mod agent;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod ci;
+pub mod clock;
 pub mod config;
+pub mod copy;
+#[cfg(feature = "native")]
+pub mod credentials;
+pub mod docker_credential;
+pub mod drift;
+pub mod encrypted;
+pub mod example;
+#[cfg(feature = "native")]
 pub mod exec;
 pub mod export;
+pub mod generate;
+pub mod git_credential;
+pub mod harden;
+pub mod history;
+pub mod hooks;
 mod io;
+pub mod mask;
+pub mod migrate;
+pub mod policy;
+pub mod progress;
+pub mod provision;
 pub mod redact;
 pub mod scan;
+pub mod search;
+pub mod snapshot;
+#[cfg(feature = "native")]
+pub mod ssh;
+pub mod stats;
 pub mod teller;
 pub mod template;
+pub mod validate;
 
 use std::string::FromUtf8Error;
 
@@ -14,6 +44,21 @@ pub enum Error {
     #[error("{0}")]
     Message(String),
 
+    #[error("denied by policy: {0}")]
+    Denied(String),
+
+    /// A provider or map is not in the configuration
+    #[error("{0}")]
+    NotFound(String),
+
+    /// A command could not be started
+    #[error("cannot run command: {0}")]
+    Exec(std::io::Error),
+
+    /// A command ran and failed
+    #[error("command failed with {0}")]
+    Child(std::process::ExitStatus),
+
     #[error(transparent)]
     Shellwords(#[from] shell_words::ParseError),
 
@@ -43,5 +88,9 @@ pub enum Error {
 
     #[error(transparent)]
     Utf(#[from] FromUtf8Error),
+
+    #[cfg(feature = "native")]
+    #[error(transparent)]
+    Git(#[from] git2::Error),
 }
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,130 @@
+//! Masking values for display, e.g. by `teller show`, the terminal UI and scan
+//! findings. Set with `mask` in `.teller.yml`:
+//!
+//! ```yaml
+//! mask:
+//!   visible: 0
+//!   fill: "•"
+//! ```
+//!
+//! Values are cut by characters, never inside a multi-byte one.
+
+use std::fmt::Write as _;
+
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// How much of a value a mask shows
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Style {
+    /// the first `visible` characters, e.g. `s3***`
+    #[default]
+    Prefix,
+    /// none, a short hash instead, e.g. `sha256:4f9c0b1e`, so equal values can
+    /// be told apart from different ones. Values that can be guessed can be
+    /// confirmed from it.
+    Hash,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Mask {
+    pub style: Style,
+    /// characters shown at the start of values
+    pub visible: usize,
+    /// what stands for the hidden characters
+    pub fill: char,
+    /// how many times `fill` is repeated, whatever the length of the value
+    pub width: usize,
+}
+
+impl Default for Mask {
+    fn default() -> Self {
+        Self {
+            style: Style::Prefix,
+            visible: 2,
+            fill: '*',
+            width: 3,
+        }
+    }
+}
+
+impl Mask {
+    #[must_use]
+    pub const fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_visible(mut self, visible: usize) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_fill(mut self, fill: char) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Mask a value
+    #[must_use]
+    pub fn apply(&self, value: &str) -> String {
+        match self.style {
+            Style::Prefix => {
+                let mut masked = value.chars().take(self.visible).collect::<String>();
+                masked.extend(std::iter::repeat_n(self.fill, self.width));
+                masked
+            }
+            Style::Hash => {
+                let digest = Sha256::digest(value.as_bytes());
+                digest
+                    .iter()
+                    .take(4)
+                    .fold("sha256:".to_string(), |mut hex, b| {
+                        let _ = write!(hex, "{b:02x}");
+                        hex
+                    })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks() {
+        assert_eq!(Mask::default().apply("s3cret"), "s3***");
+        assert_eq!(Mask::default().apply(""), "***");
+        // multi-byte characters are kept whole
+        assert_eq!(Mask::default().apply("мир-пароль"), "ми***");
+        assert_eq!(Mask::default().apply("🔑🔒x"), "🔑🔒***");
+        assert_eq!(
+            Mask::default()
+                .with_visible(0)
+                .with_fill('•')
+                .with_width(5)
+                .apply("s3cret"),
+            "•••••"
+        );
+
+        let hashed = Mask::default().with_style(Style::Hash);
+        assert_eq!(hashed.apply("s3cret"), hashed.apply("s3cret"));
+        assert_ne!(hashed.apply("s3cret"), hashed.apply("s3cres"));
+        assert!(hashed.apply("s3cret").starts_with("sha256:"));
+        assert_eq!(hashed.apply("s3cret").len(), "sha256:".len() + 8);
+
+        let mask: Mask = serde_yaml::from_str("style: hash").unwrap();
+        assert_eq!(mask, Mask::default().with_style(Style::Hash));
+    }
+}
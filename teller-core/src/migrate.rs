@@ -0,0 +1,40 @@
+//! Moving every map of a provider to another provider, see
+//! [`crate::teller::Teller::migrate`]
+
+use std::collections::BTreeMap;
+
+use serde_derive::Serialize;
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Default)]
+pub struct Opts {
+    /// path of each map in the target provider, by map id. Maps not listed
+    /// keep their source path.
+    pub paths: BTreeMap<String, String>,
+}
+
+impl Opts {
+    #[must_use]
+    pub fn with_path(mut self, map_id: &str, path: &str) -> Self {
+        self.paths.insert(map_id.to_string(), path.to_string());
+        self
+    }
+}
+
+/// A map copied to the target provider
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct MigratedMap {
+    pub map_id: String,
+    pub from_path: String,
+    pub to_path: String,
+    /// number of keys copied
+    pub keys: usize,
+}
+
+pub struct Migration {
+    pub maps: Vec<MigratedMap>,
+    /// the configuration with the source provider's maps on the target
+    /// provider, and the source provider removed
+    pub config: Config,
+}
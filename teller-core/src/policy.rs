@@ -0,0 +1,218 @@
+//! Rules denying operations, checked by [`crate::teller::Teller`] before
+//! calling providers:
+//!
+//! ```yaml
+//! policy:
+//!   - deny: [delete]
+//!     path: prod/*
+//!     reason: production secrets are only deleted by the platform team
+//!   - deny: [export]
+//!     sensitivity: Critical
+//!     formats: [json]
+//! ```
+//!
+//! A rule applies when every condition it sets matches: provider name, map id
+//! and path are globs, `sensitivity` matches keys at or above it and `formats`
+//! lists export formats.
+
+use globset::GlobBuilder;
+use serde_derive::{Deserialize, Serialize};
+use teller_providers::config::{Sensitivity, KV};
+
+use crate::{Error, Result};
+
+/// Operations rules can deny
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Put,
+    Delete,
+    /// writing a map from another map, with `teller copy`
+    Copy,
+    /// writing maps from a snapshot
+    Restore,
+    /// writing maps to the provider they are migrated to
+    Migrate,
+    Export,
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        serde_variant::to_variant_name(self)
+            .expect("only enum supported")
+            .fmt(f)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rule {
+    pub deny: Vec<Operation>,
+    /// provider names, a glob
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// map ids, a glob
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<String>,
+    /// map paths, a glob
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// only maps or keys at least this sensitive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<Sensitivity>,
+    /// only exports to these formats
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub formats: Vec<String>,
+    /// told when the rule denies an operation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// What an operation touches
+#[derive(Debug, Clone)]
+pub struct Target<'a> {
+    pub operation: Operation,
+    pub provider: &'a str,
+    pub map_id: &'a str,
+    pub path: &'a str,
+    pub sensitivity: Sensitivity,
+    /// export format
+    pub format: Option<&'a str>,
+}
+
+impl<'a> Target<'a> {
+    /// An exported key-value, from the map it was fetched from
+    #[must_use]
+    pub fn exported(kv: &'a KV, format: &'a str) -> Self {
+        Self {
+            operation: Operation::Export,
+            provider: kv.provider.as_ref().map_or("", |p| p.name.as_str()),
+            map_id: kv.path.as_ref().map_or("", |p| p.id.as_str()),
+            path: kv.path.as_ref().map_or("", |p| p.path.as_str()),
+            sensitivity: kv
+                .meta
+                .as_ref()
+                .map(|meta| meta.sensitivity.clone())
+                .unwrap_or_default(),
+            format: Some(format),
+        }
+    }
+}
+
+impl std::fmt::Display for Target<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {}/{} ({})",
+            self.operation, self.provider, self.map_id, self.path
+        )?;
+        if let Some(format) = self.format {
+            write!(f, " as {format}")?;
+        }
+        Ok(())
+    }
+}
+
+fn glob_matches(pattern: Option<&String>, value: &str) -> Result<bool> {
+    let Some(pattern) = pattern else {
+        return Ok(true);
+    };
+    let glob = GlobBuilder::new(pattern)
+        .literal_separator(false)
+        .build()
+        .map_err(|err| Error::Message(format!("policy: invalid glob '{pattern}': {err}")))?;
+    Ok(glob.compile_matcher().is_match(value))
+}
+
+impl Rule {
+    /// Whether the rule denies the operation
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a glob of the rule is not valid
+    pub fn denies(&self, target: &Target<'_>) -> Result<bool> {
+        Ok(self.deny.contains(&target.operation)
+            && glob_matches(self.provider.as_ref(), target.provider)?
+            && glob_matches(self.map.as_ref(), target.map_id)?
+            && glob_matches(self.path.as_ref(), target.path)?
+            && self
+                .sensitivity
+                .as_ref()
+                .is_none_or(|sensitivity| target.sensitivity >= *sensitivity)
+            && (self.formats.is_empty()
+                || target
+                    .format
+                    .is_some_and(|format| self.formats.iter().any(|f| f == format))))
+    }
+}
+
+/// Fail when a rule denies the operation
+///
+/// # Errors
+///
+/// This function will return [`Error::Denied`] when a rule denies the
+/// operation, or an error if a glob of a rule is not valid
+pub fn check(rules: &[Rule], target: &Target<'_>) -> Result<()> {
+    for rule in rules {
+        if rule.denies(target)? {
+            return Err(Error::Denied(rule.reason.as_ref().map_or_else(
+                || target.to_string(),
+                |reason| format!("{target}: {reason}"),
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(operation: Operation, path: &str, sensitivity: Sensitivity) -> Target<'_> {
+        Target {
+            operation,
+            provider: "vault",
+            map_id: "app",
+            path,
+            sensitivity,
+            format: None,
+        }
+    }
+
+    #[test]
+    fn deny_rules() {
+        let rules: Vec<Rule> = serde_yaml::from_str(
+            r"
+- deny: [delete]
+  path: prod/*
+  reason: ask the platform team
+- deny: [export]
+  sensitivity: High
+  formats: [json]
+",
+        )
+        .unwrap();
+
+        let delete_prod = target(Operation::Delete, "prod/app", Sensitivity::None);
+        assert_eq!(
+            check(&rules, &delete_prod).unwrap_err().to_string(),
+            "denied by policy: delete of vault/app (prod/app): ask the platform team"
+        );
+        assert!(check(
+            &rules,
+            &target(Operation::Delete, "dev/app", Sensitivity::None)
+        )
+        .is_ok());
+        assert!(check(
+            &rules,
+            &target(Operation::Put, "prod/app", Sensitivity::None)
+        )
+        .is_ok());
+
+        let mut export = target(Operation::Export, "dev/app", Sensitivity::Critical);
+        assert!(check(&rules, &export).is_ok());
+        export.format = Some("json");
+        assert!(check(&rules, &export).is_err());
+        export.sensitivity = Sensitivity::Medium;
+        assert!(check(&rules, &export).is_ok());
+    }
+}
@@ -0,0 +1,56 @@
+//! Progress of the calls [`crate::teller::Teller`] makes to providers, so
+//! long operations (collecting many maps, copying hundreds of keys) can show
+//! where they are at.
+
+use teller_providers::config::PathMap;
+
+/// What a provider is asked to do with a map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Get,
+    Put,
+    Delete,
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Get => "getting",
+            Self::Put => "putting",
+            Self::Delete => "deleting",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// with the number of keys written, when known
+    Started {
+        keys: Option<usize>,
+    },
+    /// with the number of keys read or written
+    Done {
+        keys: usize,
+    },
+    Failed(String),
+}
+
+/// A provider call starting or ending
+#[derive(Debug, Clone)]
+pub struct Progress<'a> {
+    pub step: Step,
+    pub provider: &'a str,
+    pub pm: &'a PathMap,
+    pub status: Status,
+}
+
+impl Progress<'_> {
+    /// Identifies the call across its events
+    #[must_use]
+    pub fn id(&self) -> String {
+        format!("{} {}/{}", self.step, self.provider, self.pm.id)
+    }
+}
+
+/// Receives progress, it is dropped when no handler is set
+pub type ProgressHandler = Box<dyn Fn(&Progress<'_>) + Send + Sync>;
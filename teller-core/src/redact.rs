@@ -1,53 +1,314 @@
 use std::{
     borrow::Cow,
-    io::{BufRead, Write},
+    fmt::Write as _,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    thread,
+    time::Duration,
 };
 
+use aho_corasick::{AhoCorasick, MatchKind};
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD},
+    Engine,
+};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 // use crate::{Result, KV};
 use teller_providers::config::KV;
 
-pub struct Redactor {}
+use crate::{
+    config::RedactConfig,
+    io::{Appended, Follower},
+};
+
+const DEFAULT_REDACT_WITH: &str = "[REDACTED]";
+/// values shorter than this are left alone unless configured otherwise
+pub const DEFAULT_MIN_LENGTH: usize = 2;
+/// JSON field names that are redacted regardless of their value
+pub const DEFAULT_FIELDS: &[&str] = &[
+    "password",
+    "*_password",
+    "passwd",
+    "secret",
+    "*_secret",
+    "token",
+    "*_token",
+    "api_key",
+    "apikey",
+    "authorization",
+    "cookie",
+];
+/// bytes read from the input at a time when redacting a stream
+const CHUNK_SIZE: usize = 64 * 1024;
+/// number of hex chars taken from the keyed hash when building a token
+const TOKEN_LEN: usize = 6;
+
+#[derive(Debug, Clone, Default)]
+pub enum Mode {
+    /// Replace secrets with the map's `redact_with`, or `[REDACTED]`
+    #[default]
+    Fixed,
+    /// Replace secrets with a stable token derived from a keyed hash of the value,
+    /// e.g. `[REDACTED:a1b2c3]`, so the same secret always maps to the same token
+    Token { key: Vec<u8> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Opts {
+    pub mode: Mode,
+    /// Also redact common encodings of each secret (base64, URL-encoded, JSON-escaped)
+    pub encoded: bool,
+    /// Treat input as JSON lines: redact inside JSON values and re-serialize each line
+    pub json_lines: bool,
+    /// Globs of JSON field names whose values are always redacted in JSON lines mode,
+    /// matched case-insensitively. `None` uses [`DEFAULT_FIELDS`].
+    pub fields: Option<Vec<String>>,
+    /// Values shorter than this are never redacted
+    pub min_length: usize,
+    /// Keys whose values are never redacted
+    pub skip_keys: Vec<String>,
+    /// Values that are never redacted, whatever key holds them
+    pub allow_values: Vec<String>,
+}
+
+impl Opts {
+    /// Apply the `redact` section of a teller configuration over these options
+    #[must_use]
+    pub fn with_config(mut self, config: &RedactConfig) -> Self {
+        if let Some(min_length) = config.min_length {
+            self.min_length = min_length;
+        }
+        self.skip_keys.extend(config.skip_keys.iter().cloned());
+        self.allow_values
+            .extend(config.allow_values.iter().cloned());
+        self
+    }
+}
+
+impl Default for Opts {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Fixed,
+            encoded: true,
+            json_lines: false,
+            fields: None,
+            min_length: DEFAULT_MIN_LENGTH,
+            skip_keys: vec![],
+            allow_values: vec![],
+        }
+    }
+}
+
+pub struct Redactor {
+    opts: Opts,
+}
 
 impl Redactor {
     #[must_use]
     pub const fn new() -> Self {
-        Self {}
+        Self {
+            opts: Opts {
+                mode: Mode::Fixed,
+                encoded: true,
+                json_lines: false,
+                fields: None,
+                min_length: DEFAULT_MIN_LENGTH,
+                skip_keys: vec![],
+                allow_values: vec![],
+            },
+        }
+    }
+
+    #[must_use]
+    pub const fn with_opts(opts: Opts) -> Self {
+        Self { opts }
     }
 
-    /// Redact a reader into writer
+    /// Redact a reader into writer.
+    ///
+    /// Input is streamed in chunks through a single Aho-Corasick automaton built from
+    /// all secrets, so line endings (including CRLF) are kept as-is, and a secret that
+    /// is split across two reads is still caught.
     ///
     /// # Errors
     ///
     /// This function will return an error if IO fails
     pub fn redact<R: BufRead, W: Write>(
         &self,
-        reader: R,
+        mut reader: R,
         mut writer: W,
         kvs: &[KV],
     ) -> std::io::Result<()> {
-        for line in reader.lines().map_while(Result::ok) {
-            let redacted = self.redact_string(line.as_str(), kvs);
-            writer.write_all(redacted.as_bytes())?;
-            writer.write_all(&[b'\n'])?; // TODO: support crlf for windows
+        let automaton = self.automaton(kvs)?;
+        if self.opts.json_lines {
+            return self.redact_json_lines(&automaton, reader, writer);
+        }
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let mut pending = Vec::with_capacity(CHUNK_SIZE);
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            pending.extend_from_slice(&chunk[..read]);
+            let eof = read == 0;
+            let consumed = automaton.write_redacted(&pending, eof, &mut writer)?;
+            pending.drain(..consumed);
+            writer.flush()?;
+            if eof {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Redact line by line, where each line that parses as JSON has its string values
+    /// redacted and its sensitive fields blanked, and is written back as compact JSON.
+    /// Lines that are not JSON are redacted as text.
+    fn redact_json_lines<R: BufRead, W: Write>(
+        &self,
+        automaton: &Automaton<'_>,
+        mut reader: R,
+        mut writer: W,
+    ) -> io::Result<()> {
+        let fields = self.field_globs()?;
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            let content = line.trim_end_matches(['\r', '\n']);
+            let ending = &line[content.len()..];
+            match serde_json::from_str::<serde_json::Value>(content) {
+                Ok(mut value) => {
+                    self.redact_json_value(&mut value, &fields, automaton);
+                    serde_json::to_writer(&mut writer, &value)?;
+                }
+                Err(_) => writer.write_all(automaton.redact_str(content).as_bytes())?,
+            }
+            writer.write_all(ending.as_bytes())?;
             writer.flush()?;
+            line.clear();
         }
         Ok(())
     }
 
+    fn redact_json_value(
+        &self,
+        value: &mut serde_json::Value,
+        fields: &GlobSet,
+        automaton: &Automaton<'_>,
+    ) {
+        match value {
+            serde_json::Value::String(s) => {
+                if let Cow::Owned(redacted) = automaton.redact_str(s) {
+                    *s = redacted;
+                }
+            }
+            serde_json::Value::Number(n) => {
+                if let Cow::Owned(redacted) = automaton.redact_str(&n.to_string()) {
+                    *value = serde_json::Value::String(redacted);
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.redact_json_value(item, fields, automaton);
+                }
+            }
+            serde_json::Value::Object(map) => {
+                for (field, item) in map.iter_mut() {
+                    let scalar = matches!(
+                        item,
+                        serde_json::Value::String(_)
+                            | serde_json::Value::Number(_)
+                            | serde_json::Value::Bool(_)
+                    );
+                    if scalar && fields.is_match(field) {
+                        let raw = item
+                            .as_str()
+                            .map_or_else(|| item.to_string(), ToString::to_string);
+                        *item = serde_json::Value::String(self.replacement_for_value(&raw).into());
+                    } else {
+                        self.redact_json_value(item, fields, automaton);
+                    }
+                }
+            }
+            serde_json::Value::Null | serde_json::Value::Bool(_) => {}
+        }
+    }
+
+    fn field_globs(&self) -> io::Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+        let fields = self.opts.fields.as_ref().map_or_else(
+            || DEFAULT_FIELDS.iter().map(ToString::to_string).collect(),
+            Clone::clone,
+        );
+        for field in fields {
+            builder.add(
+                GlobBuilder::new(&field)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+            );
+        }
+        builder
+            .build()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Start following log files for redaction; drive it with [`Watch::poll`] or
+    /// [`Watch::run`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if IO fails
+    pub fn watch(&self, paths: &[PathBuf], kvs: &[KV], from_start: bool) -> io::Result<Watch<'_>> {
+        Ok(Watch {
+            redactor: self,
+            automaton: self.automaton(kvs)?,
+            files: paths
+                .iter()
+                .map(|path| Ok((Follower::new(path, from_start)?, vec![])))
+                .collect::<io::Result<Vec<_>>>()?,
+        })
+    }
+
+    fn automaton(&self, kvs: &[KV]) -> io::Result<Automaton<'_>> {
+        let mut patterns = vec![];
+        let mut replacements = vec![];
+        for kv in kvs.iter().filter(|kv| self.is_redactable(kv)) {
+            let replacement = self.replacement_for(kv);
+            for needle in self.needles(kv) {
+                patterns.push(needle);
+                replacements.push(replacement.clone());
+            }
+        }
+        let finder = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(&patterns)
+            .map_err(io::Error::other)?;
+        let max_len = patterns.iter().map(String::len).max().unwrap_or_default();
+        let mut sorted = patterns
+            .into_iter()
+            .map(String::into_bytes)
+            .collect::<Vec<_>>();
+        sorted.sort();
+
+        Ok(Automaton {
+            finder,
+            replacements,
+            sorted,
+            max_len,
+        })
+    }
+
     #[must_use]
     pub fn redact_string<'a>(&'a self, message: &'a str, kvs: &[KV]) -> Cow<'_, str> {
         if self.has_match(message, kvs) {
             let mut redacted = message.to_string();
-            for kv in kvs {
-                // only replace values with at least 2 chars
-                if kv.value.len() >= 2 {
-                    redacted = redacted.replace(
-                        &kv.value,
-                        kv.meta
-                            .as_ref()
-                            .and_then(|m| m.redact_with.as_ref())
-                            .map_or("[REDACTED]", |s| s.as_str()),
-                    );
+            for kv in kvs.iter().filter(|kv| self.is_redactable(kv)) {
+                let replacement = self.replacement_for(kv);
+                for needle in self.needles(kv) {
+                    redacted = redacted.replace(&needle, &replacement);
                 }
             }
             Cow::Owned(redacted)
@@ -56,10 +317,253 @@ impl Redactor {
         }
     }
 
+    /// The text a secret is replaced with. A map's `redact_with` always wins,
+    /// otherwise it is decided by the redaction mode.
+    #[must_use]
+    pub fn replacement_for(&self, kv: &KV) -> Cow<'_, str> {
+        if let Some(redact_with) = kv.meta.as_ref().and_then(|m| m.redact_with.as_ref()) {
+            return Cow::Owned(redact_with.clone());
+        }
+        self.replacement_for_value(kv.value.expose())
+    }
+
+    fn replacement_for_value(&self, value: &str) -> Cow<'_, str> {
+        match &self.opts.mode {
+            Mode::Fixed => Cow::Borrowed(DEFAULT_REDACT_WITH),
+            Mode::Token { key } => Cow::Owned(format!("[REDACTED:{}]", token(key, value))),
+        }
+    }
+
+    /// All the forms a secret is looked up by: the raw value, its other line
+    /// endings (a multi-line value printed on Windows has CRLF endings),
+    /// followed by its encoded variants when enabled.
+    #[must_use]
+    pub fn needles(&self, kv: &KV) -> Vec<String> {
+        let mut needles = vec![kv.value.expose().to_string()];
+        for variant in line_ending_variants(kv.value.expose()) {
+            if !needles.contains(&variant) {
+                needles.push(variant);
+            }
+        }
+        if self.opts.encoded {
+            for variant in encoded_variants(kv.value.expose()) {
+                if !needles.contains(&variant) {
+                    needles.push(variant);
+                }
+            }
+        }
+        needles
+    }
+
+    /// Whether a KV's value should be redacted at all, given the length threshold,
+    /// skipped keys and allowed values
+    #[must_use]
+    pub fn is_redactable(&self, kv: &KV) -> bool {
+        // an empty pattern matches everywhere, never go below 1
+        kv.value.expose().len() >= self.opts.min_length.max(1)
+            && !self.opts.skip_keys.contains(&kv.key)
+            && !self
+                .opts
+                .allow_values
+                .iter()
+                .any(|v| v == kv.value.expose())
+    }
+
     #[must_use]
     pub fn has_match<'a>(&'a self, message: &'a str, kvs: &[KV]) -> bool {
-        kvs.iter().any(|kv| message.contains(&kv.value))
+        kvs.iter().filter(|kv| self.is_redactable(kv)).any(|kv| {
+            message.contains(kv.value.expose())
+                || line_ending_variants(kv.value.expose())
+                    .iter()
+                    .any(|variant| message.contains(variant))
+                || (self.opts.encoded
+                    && encoded_variants(kv.value.expose())
+                        .iter()
+                        .any(|variant| message.contains(variant)))
+        })
+    }
+}
+
+/// Follows a set of log files (across rotations) and writes what gets appended to
+/// them, redacted. Only complete lines are written, so that lines coming from
+/// different files do not interleave.
+pub struct Watch<'a> {
+    redactor: &'a Redactor,
+    automaton: Automaton<'a>,
+    /// each followed file, with the bytes read from it but not yet written
+    files: Vec<(Follower, Vec<u8>)>,
+}
+
+impl Watch<'_> {
+    /// Read what was appended to the files since the last poll and write it redacted.
+    /// Returns the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if IO fails
+    pub fn poll<W: Write>(&mut self, writer: &mut W) -> io::Result<usize> {
+        let mut total = 0;
+        for (follower, pending) in &mut self.files {
+            match follower.read_appended(pending)? {
+                Appended::Data(read) => {
+                    total += read;
+                    // write up to and including the last complete line
+                    if let Some(nl) = pending.iter().rposition(|b| *b == b'\n') {
+                        let consumed = write_chunk(
+                            self.redactor,
+                            &self.automaton,
+                            &pending[..=nl],
+                            false,
+                            writer,
+                        )?;
+                        pending.drain(..consumed);
+                    }
+                }
+                Appended::Rotated => {
+                    // the old file is gone, whatever is left of it is final
+                    write_chunk(self.redactor, &self.automaton, pending, true, writer)?;
+                    pending.clear();
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(total)
+    }
+
+    /// Poll forever, sleeping `interval` whenever there's nothing new. Blocks the
+    /// thread, see [`crate::teller::Teller::redact_watch`] for async code.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if IO fails
+    pub fn run<W: Write>(&mut self, mut writer: W, interval: Duration) -> io::Result<()> {
+        loop {
+            if self.poll(&mut writer)? == 0 {
+                thread::sleep(interval);
+            }
+        }
+    }
+}
+
+fn write_chunk<W: Write>(
+    redactor: &Redactor,
+    automaton: &Automaton<'_>,
+    chunk: &[u8],
+    eof: bool,
+    writer: &mut W,
+) -> io::Result<usize> {
+    if redactor.opts.json_lines {
+        redactor.redact_json_lines(automaton, chunk, writer)?;
+        Ok(chunk.len())
+    } else {
+        automaton.write_redacted(chunk, eof, writer)
+    }
+}
+
+struct Automaton<'a> {
+    finder: AhoCorasick,
+    /// replacement text, indexed by pattern id
+    replacements: Vec<Cow<'a, str>>,
+    /// all patterns, sorted, to tell if a buffer ends in the middle of one
+    sorted: Vec<Vec<u8>>,
+    max_len: usize,
+}
+
+impl Automaton<'_> {
+    /// Write `buf` redacted, holding back a tail that may be the start of a secret
+    /// continuing in the next read. Returns how many bytes of `buf` were consumed.
+    fn write_redacted<W: Write>(&self, buf: &[u8], eof: bool, writer: &mut W) -> io::Result<usize> {
+        let safe_end = if eof { buf.len() } else { self.safe_end(buf) };
+        let mut pos = 0;
+        for m in self.finder.find_iter(buf) {
+            // from here on a match may still grow with more input
+            if m.start() >= safe_end {
+                break;
+            }
+            writer.write_all(&buf[pos..m.start()])?;
+            writer.write_all(self.replacements[m.pattern().as_usize()].as_bytes())?;
+            pos = m.end();
+        }
+        let consumed = pos.max(safe_end);
+        writer.write_all(&buf[pos..consumed])?;
+        Ok(consumed)
+    }
+
+    fn redact_str<'s>(&self, s: &'s str) -> Cow<'s, str> {
+        if !self.finder.is_match(s) {
+            return Cow::Borrowed(s);
+        }
+        let mut out = Vec::with_capacity(s.len());
+        // writing into a vec cannot fail, and replacing whole utf-8 patterns keeps it utf-8
+        let _ = self.write_redacted(s.as_bytes(), true, &mut out);
+        Cow::Owned(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    /// First offset in the buffer's tail from which the rest of the buffer is a prefix of
+    /// some pattern (or the buffer's length if there's none)
+    fn safe_end(&self, buf: &[u8]) -> usize {
+        let tail_start = buf.len().saturating_sub(self.max_len.saturating_sub(1));
+        (tail_start..buf.len())
+            .find(|&i| {
+                let rest = &buf[i..];
+                let idx = self.sorted.partition_point(|p| p.as_slice() < rest);
+                self.sorted.get(idx).is_some_and(|p| p.starts_with(rest))
+            })
+            .unwrap_or(buf.len())
+    }
+}
+
+/// Encodings a secret commonly leaks in: base64 (standard and URL-safe, padded or not),
+/// URL (percent) encoding and JSON string escaping.
+/// A multi-line value with LF and with CRLF line endings
+fn line_ending_variants(value: &str) -> Vec<String> {
+    if !value.contains('\n') {
+        return vec![];
     }
+    let lf = value.replace("\r\n", "\n");
+    let crlf = lf.replace('\n', "\r\n");
+    vec![lf, crlf]
+}
+
+fn encoded_variants(value: &str) -> Vec<String> {
+    let bytes = value.as_bytes();
+    let json = serde_json::to_string(value).unwrap_or_default();
+    vec![
+        STANDARD.encode(bytes),
+        STANDARD_NO_PAD.encode(bytes),
+        URL_SAFE.encode(bytes),
+        URL_SAFE_NO_PAD.encode(bytes),
+        url_encode(value),
+        json.get(1..json.len().saturating_sub(1))
+            .unwrap_or_default()
+            .to_string(),
+    ]
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters
+fn url_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            out.push(char::from(b));
+        } else {
+            let _ = write!(out, "%{b:02X}");
+        }
+    }
+    out
+}
+
+/// First `TOKEN_LEN` hex chars of HMAC-SHA256(key, value)
+fn token(key: &[u8], value: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(value.as_bytes());
+    let digest = mac.finalize().into_bytes();
+    let mut out = String::with_capacity(TOKEN_LEN);
+    for b in digest.iter().take(TOKEN_LEN.div_ceil(2)) {
+        let _ = write!(out, "{b:02x}");
+    }
+    out.truncate(TOKEN_LEN);
+    out
 }
 
 impl Default for Redactor {
@@ -70,7 +574,7 @@ impl Default for Redactor {
 
 #[cfg(test)]
 mod tests {
-    use std::io::{BufReader, BufWriter};
+    use std::io::{BufReader, BufWriter, Read};
 
     use stringreader::StringReader;
     use teller_providers::{config::ProviderInfo, providers::ProviderKind};
@@ -82,7 +586,7 @@ mod tests {
         let data = "foobar\nfoobaz\n";
         let mut reader = BufReader::new(StringReader::new(data));
         let mut writer = BufWriter::new(Vec::new());
-        let redactor = Redactor {};
+        let redactor = Redactor::new();
 
         redactor.redact(&mut reader, &mut writer, &[]).unwrap();
         let s = String::from_utf8(writer.into_inner().unwrap()).unwrap();
@@ -94,7 +598,7 @@ mod tests {
         let data = "foobar\nfoobaz\n";
         let mut reader = BufReader::new(StringReader::new(data));
         let mut writer = BufWriter::new(Vec::new());
-        let redactor = Redactor {};
+        let redactor = Redactor::new();
 
         redactor
             .redact(
@@ -114,4 +618,312 @@ mod tests {
         let s = String::from_utf8(writer.into_inner().unwrap()).unwrap();
         assert_eq!(s, "foobar\n[REDACTED]\n");
     }
+
+    #[test]
+    fn redact_with_token() {
+        let provider = ProviderInfo {
+            kind: ProviderKind::Inmem,
+            name: "test".to_string(),
+        };
+        let kvs = [
+            KV::from_literal("some/path", "k1", "foobaz", provider.clone()),
+            KV::from_literal("some/path", "k2", "hunter2", provider),
+        ];
+        let redactor = Redactor::with_opts(Opts {
+            mode: Mode::Token {
+                key: b"s3cr3t".to_vec(),
+            },
+            ..Opts::default()
+        });
+
+        let first = redactor.redact_string("foobaz and hunter2, foobaz", &kvs);
+        assert!(!first.contains("foobaz"));
+        assert!(!first.contains("hunter2"));
+
+        let tokens = first
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|w| w.starts_with("[REDACTED:"))
+            .collect::<Vec<_>>();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0], tokens[2]);
+        assert_ne!(tokens[0], tokens[1]);
+        assert_eq!(tokens[0].len(), "[REDACTED:]".len() + TOKEN_LEN);
+
+        // stable for the same key, different for another key
+        assert_eq!(
+            first,
+            redactor.redact_string("foobaz and hunter2, foobaz", &kvs)
+        );
+        let other = Redactor::with_opts(Opts {
+            mode: Mode::Token {
+                key: b"other".to_vec(),
+            },
+            ..Opts::default()
+        });
+        assert_ne!(
+            first,
+            other.redact_string("foobaz and hunter2, foobaz", &kvs)
+        );
+    }
+
+    #[test]
+    fn redact_encoded() {
+        let kvs = [KV::from_literal(
+            "some/path",
+            "k",
+            "p@ss \"word\"",
+            ProviderInfo {
+                kind: ProviderKind::Inmem,
+                name: "test".to_string(),
+            },
+        )];
+        let redactor = Redactor::new();
+
+        // base64, base64 url-safe (no pad), percent encoded, json escaped
+        for leaked in [
+            "cEBzcyAid29yZCI=",
+            "cEBzcyAid29yZCI",
+            "p%40ss%20%22word%22",
+            r#"{"password":"p@ss \"word\""}"#,
+        ] {
+            let line = format!("got {leaked} here");
+            assert!(redactor.has_match(&line, &kvs), "{leaked}");
+            assert!(
+                !redactor.redact_string(&line, &kvs).contains(leaked),
+                "{leaked}"
+            );
+        }
+
+        let plain = Redactor::with_opts(Opts {
+            encoded: false,
+            ..Opts::default()
+        });
+        assert_eq!(
+            plain.redact_string("got cEBzcyAid29yZCI= here", &kvs),
+            "got cEBzcyAid29yZCI= here"
+        );
+    }
+
+    /// yields one byte per read, so every secret is split across reads
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn redact_crlf_multiline() {
+        let kvs = [KV::from_kv("CERT", "-----BEGIN-----\nMII=\n-----END-----")];
+        let mut writer = Vec::new();
+        Redactor::new()
+            .redact(
+                BufReader::new(StringReader::new(
+                    "cert: -----BEGIN-----\r\nMII=\r\n-----END-----\r\n",
+                )),
+                &mut writer,
+                &kvs,
+            )
+            .unwrap();
+        assert_eq!(String::from_utf8(writer).unwrap(), "cert: [REDACTED]\r\n");
+    }
+
+    #[test]
+    fn redact_stream() {
+        let provider = ProviderInfo {
+            kind: ProviderKind::Inmem,
+            name: "test".to_string(),
+        };
+        let kvs = [
+            KV::from_literal("some/path", "short", "foo", provider.clone()),
+            KV::from_literal("some/path", "long", "foobar", provider),
+        ];
+        let data = "foobar\r\nfoo fo\r\nxfoo";
+        let expected = "[REDACTED]\r\n[REDACTED] fo\r\nx[REDACTED]";
+
+        let mut writer = Vec::new();
+        Redactor::new()
+            .redact(
+                BufReader::with_capacity(1, Trickle(data.as_bytes())),
+                &mut writer,
+                &kvs,
+            )
+            .unwrap();
+        assert_eq!(String::from_utf8(writer).unwrap(), expected);
+
+        let mut writer = Vec::new();
+        Redactor::new()
+            .redact(BufReader::new(StringReader::new(data)), &mut writer, &kvs)
+            .unwrap();
+        assert_eq!(String::from_utf8(writer).unwrap(), expected);
+    }
+
+    #[test]
+    fn redact_json_lines() {
+        let kvs = [KV::from_literal(
+            "some/path",
+            "k",
+            "foobaz",
+            ProviderInfo {
+                kind: ProviderKind::Inmem,
+                name: "test".to_string(),
+            },
+        )];
+        let data = concat!(
+            r#"{"msg":"login with foobaz","user":{"name":"joe","db_password":"hunter2"}}"#,
+            "\r\n",
+            r#"{"items":["a","foobaz"],"token":42,"count":1}"#,
+            "\n",
+            "plain foobaz line\n",
+        );
+        let mut writer = Vec::new();
+        Redactor::with_opts(Opts {
+            json_lines: true,
+            ..Opts::default()
+        })
+        .redact(BufReader::new(StringReader::new(data)), &mut writer, &kvs)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(writer).unwrap(),
+            concat!(
+                r#"{"msg":"login with [REDACTED]","user":{"name":"joe","db_password":"[REDACTED]"}}"#,
+                "\r\n",
+                r#"{"items":["a","[REDACTED]"],"token":"[REDACTED]","count":1}"#,
+                "\n",
+                "plain [REDACTED] line\n",
+            )
+        );
+
+        let mut writer = Vec::new();
+        Redactor::with_opts(Opts {
+            json_lines: true,
+            fields: Some(vec!["NAME".to_string()]),
+            ..Opts::default()
+        })
+        .redact(
+            BufReader::new(StringReader::new(r#"{"name":"joe","password":"hunter2"}"#)),
+            &mut writer,
+            &kvs,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(writer).unwrap(),
+            r#"{"name":"[REDACTED]","password":"hunter2"}"#
+        );
+    }
+
+    #[test]
+    fn redact_with_controls() {
+        let provider = ProviderInfo {
+            kind: ProviderKind::Inmem,
+            name: "test".to_string(),
+        };
+        let kvs = [
+            KV::from_literal("some/path", "PORT", "8080", provider.clone()),
+            KV::from_literal("some/path", "LOG_LEVEL", "DEBUG", provider.clone()),
+            KV::from_literal("some/path", "PIN", "123", provider.clone()),
+            KV::from_literal("some/path", "DB_PASS", "hunter22", provider),
+        ];
+        let message = "DEBUG: pin 123, pass hunter22 on port 8080";
+        assert_eq!(
+            Redactor::new().redact_string(message, &kvs),
+            "[REDACTED]: pin [REDACTED], pass [REDACTED] on port [REDACTED]"
+        );
+
+        let opts = Opts::default().with_config(&RedactConfig {
+            min_length: Some(4),
+            skip_keys: vec!["PORT".to_string()],
+            allow_values: vec!["DEBUG".to_string()],
+        });
+        let redactor = Redactor::with_opts(opts);
+        assert_eq!(
+            redactor.redact_string(message, &kvs),
+            "DEBUG: pin 123, pass [REDACTED] on port 8080"
+        );
+
+        let mut writer = Vec::new();
+        redactor
+            .redact(
+                BufReader::new(StringReader::new(message)),
+                &mut writer,
+                &kvs,
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer).unwrap(),
+            "DEBUG: pin 123, pass [REDACTED] on port 8080"
+        );
+    }
+
+    #[test]
+    fn watch_files() {
+        let dir = std::env::temp_dir().join(format!("teller-watch-{}", std::process::id()));
+        fs_err::create_dir_all(&dir).unwrap();
+        let app = dir.join("app.log");
+        let other = dir.join("other.log");
+        fs_err::write(&app, "old foobaz line\n").unwrap();
+
+        let kvs = [KV::from_literal(
+            "some/path",
+            "k",
+            "foobaz",
+            ProviderInfo {
+                kind: ProviderKind::Inmem,
+                name: "test".to_string(),
+            },
+        )];
+        let redactor = Redactor::new();
+        let mut watch = redactor
+            .watch(&[app.clone(), other.clone()], &kvs, false)
+            .unwrap();
+        let mut out = Vec::new();
+
+        // existing content is skipped, a missing file is fine
+        assert_eq!(watch.poll(&mut out).unwrap(), 0);
+
+        let append = |path: &std::path::Path, text: &str| {
+            let mut f = fs_err::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .unwrap();
+            f.write_all(text.as_bytes()).unwrap();
+        };
+        append(&app, "new foobaz line\npartial foo");
+        append(&other, "other foobaz\n");
+        watch.poll(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "new [REDACTED] line\nother [REDACTED]\n"
+        );
+
+        // rotate: the tail of the old file is flushed, then the new file is read from its start
+        out.clear();
+        append(&app, "baz\n");
+        fs_err::rename(&app, dir.join("app.log.1")).unwrap();
+        fs_err::write(&app, "rotated foobaz\n").unwrap();
+        for _ in 0..3 {
+            watch.poll(&mut out).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "partial [REDACTED]\nrotated [REDACTED]\n"
+        );
+
+        // truncation also counts as rotation
+        out.clear();
+        fs_err::write(&app, "x\n").unwrap();
+        for _ in 0..2 {
+            watch.poll(&mut out).unwrap();
+        }
+        assert_eq!(String::from_utf8_lossy(&out), "x\n");
+
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
 }
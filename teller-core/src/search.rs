@@ -0,0 +1,199 @@
+//! Finding where keys are defined, by a fuzzy or regex search over key names
+//! and, optionally, values.
+
+use regex::Regex;
+use serde_derive::Serialize;
+use teller_providers::config::KV;
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Default)]
+pub struct Opts {
+    /// the pattern is a regex, instead of fuzzy matched
+    pub regex: bool,
+    /// also match values, found values are never part of results
+    pub values: bool,
+}
+
+/// A key that matched, and where it comes from
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub key: String,
+    /// the key in the provider, before key mapping
+    pub from_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// matched by value rather than key name
+    pub by_value: bool,
+    /// higher is a closer match
+    pub score: i64,
+}
+
+impl std::fmt::Display for Hit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {} ({}): {}",
+            self.provider.as_deref().unwrap_or("n/a"),
+            self.map_id.as_deref().unwrap_or_default(),
+            self.path.as_deref().unwrap_or_default(),
+            self.key
+        )?;
+        if self.from_key != self.key {
+            write!(f, " (from {})", self.from_key)?;
+        }
+        if self.by_value {
+            write!(f, " [value matches]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Score a fuzzy match, case insensitive: exact matches first, then substrings,
+/// then the pattern's characters in order with the fewest gaps
+#[must_use]
+#[allow(clippy::cast_possible_wrap)]
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    if text == pattern {
+        return Some(3000);
+    }
+    if text.contains(&pattern) {
+        return Some(2000 - (text.len() - pattern.len()) as i64);
+    }
+    let mut gaps = 0;
+    let mut chars = text.chars();
+    for p in pattern.chars() {
+        loop {
+            let c = chars.next()?;
+            if c == p {
+                break;
+            }
+            gaps += 1;
+        }
+    }
+    Some(1000 - gaps)
+}
+
+/// Search collected key-values, returning hits closest match first
+///
+/// # Errors
+///
+/// This function will return an error if the pattern is not a valid regex
+pub fn search(pattern: &str, kvs: &[KV], opts: &Opts) -> Result<Vec<Hit>> {
+    let regex = if opts.regex {
+        Some(
+            Regex::new(pattern)
+                .map_err(|err| Error::Message(format!("invalid pattern '{pattern}': {err}")))?,
+        )
+    } else {
+        None
+    };
+    let score = |text: &str| {
+        regex.as_ref().map_or_else(
+            || fuzzy_score(pattern, text),
+            |regex| regex.is_match(text).then_some(0),
+        )
+    };
+    let value_matches = |value: &str| {
+        regex.as_ref().map_or_else(
+            || value.to_lowercase().contains(&pattern.to_lowercase()),
+            |regex| regex.is_match(value),
+        )
+    };
+
+    let mut hits = kvs
+        .iter()
+        .filter_map(|kv| {
+            let (score, by_value) = match score(&kv.key).or_else(|| score(&kv.from_key)) {
+                Some(score) => (score, false),
+                None if opts.values && value_matches(kv.value.expose()) => (0, true),
+                None => return None,
+            };
+            Some(Hit {
+                key: kv.key.clone(),
+                from_key: kv.from_key.clone(),
+                provider: kv.provider.as_ref().map(|p| p.name.clone()),
+                map_id: kv.path.as_ref().map(|p| p.id.clone()),
+                path: kv.path.as_ref().map(|p| p.path.clone()),
+                by_value,
+                score,
+            })
+        })
+        .collect::<Vec<_>>();
+    hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.key.cmp(&b.key)));
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("stripe", "STRIPE"), Some(3000));
+        assert_eq!(fuzzy_score("stripe", "STRIPE_KEY"), Some(1996));
+        assert_eq!(fuzzy_score("strkey", "STRIPE_KEY"), Some(996));
+        assert_eq!(fuzzy_score("keystripe", "STRIPE_KEY"), None);
+    }
+
+    #[test]
+    fn search_keys_and_values() {
+        let kvs = [
+            KV::from_kv("STRIPE_KEY", "sk_live_1"),
+            KV::from_kv("STRIPE_WEBHOOK_SECRET", "whsec_1"),
+            KV::from_kv("DB_PASS", "stripe-db"),
+        ];
+        let keys = |hits: Vec<Hit>| hits.into_iter().map(|h| h.key).collect::<Vec<_>>();
+
+        assert_eq!(
+            keys(search("stripekey", &kvs, &Opts::default()).unwrap()),
+            vec!["STRIPE_KEY"]
+        );
+        assert_eq!(
+            keys(search("stripe", &kvs, &Opts::default()).unwrap()),
+            vec!["STRIPE_KEY", "STRIPE_WEBHOOK_SECRET"]
+        );
+        let hits = search(
+            "stripe",
+            &kvs,
+            &Opts {
+                values: true,
+                ..Opts::default()
+            },
+        )
+        .unwrap();
+        assert!(hits[2].by_value);
+        assert_eq!(
+            keys(
+                search(
+                    "^STRIPE_(KEY|TOKEN)$",
+                    &kvs,
+                    &Opts {
+                        regex: true,
+                        ..Opts::default()
+                    }
+                )
+                .unwrap()
+            ),
+            vec!["STRIPE_KEY"]
+        );
+        assert!(search(
+            "(",
+            &kvs,
+            &Opts {
+                regex: true,
+                ..Opts::default()
+            }
+        )
+        .is_err());
+    }
+}
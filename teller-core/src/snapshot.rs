@@ -0,0 +1,183 @@
+//! Point-in-time copies of every map's key-values, encrypted with
+//! [age](https://age-encryption.org), to back up secret stores and restore them.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use age::secrecy::SecretString;
+use serde_derive::{Deserialize, Serialize};
+use teller_providers::secret::SecretValue;
+use zeroize::Zeroizing;
+
+use crate::{Error, Result};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// when the snapshot was taken, in seconds since the Unix epoch
+    pub created_at: u64,
+    pub maps: Vec<MapSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapSnapshot {
+    pub provider: String,
+    pub map_id: String,
+    pub path: String,
+    /// values by their key in the provider, before any key mapping
+    pub kvs: BTreeMap<String, SecretValue>,
+}
+
+/// How to encrypt a snapshot
+pub enum Encryption {
+    Passphrase(String),
+    /// age public keys (`age1...`)
+    Recipients(Vec<String>),
+}
+
+/// How to decrypt a snapshot
+pub enum Decryption {
+    Passphrase(String),
+    /// an age identity file, as written by `age-keygen`
+    IdentityFile(PathBuf),
+}
+
+impl Snapshot {
+    /// Number of keys in all maps
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.maps.iter().map(|map| map.kvs.len()).sum()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Serialize and encrypt
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a recipient is not valid or
+    /// encryption fails
+    pub fn encrypt(&self, encryption: &Encryption) -> Result<Vec<u8>> {
+        let encryptor = match encryption {
+            Encryption::Passphrase(passphrase) => {
+                age::Encryptor::with_user_passphrase(SecretString::from(passphrase.clone()))
+            }
+            Encryption::Recipients(recipients) => {
+                let recipients = recipients
+                    .iter()
+                    .map(|recipient| {
+                        age::x25519::Recipient::from_str(recipient).map_err(|err| {
+                            Error::Message(format!("invalid recipient '{recipient}': {err}"))
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if recipients.is_empty() {
+                    return Err(Error::Message("no recipients given".to_string()));
+                }
+                age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+                    .map_err(age_error)?
+            }
+        };
+        let plain = Zeroizing::new(serde_json::to_vec(self)?);
+        let mut out = vec![];
+        let mut writer = encryptor.wrap_output(&mut out)?;
+        writer.write_all(&plain)?;
+        writer.finish()?;
+        Ok(out)
+    }
+
+    /// Decrypt and deserialize
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the snapshot cannot be decrypted
+    /// with the given key, or is not valid
+    pub fn decrypt(data: &[u8], decryption: &Decryption) -> Result<Self> {
+        let decryptor = age::Decryptor::new(data).map_err(age_error)?;
+        let identities: Vec<Box<dyn age::Identity>> = match decryption {
+            Decryption::Passphrase(_) if !decryptor.is_scrypt() => {
+                return Err(Error::Message(
+                    "snapshot is encrypted to recipients, decrypt it with an identity file"
+                        .to_string(),
+                ))
+            }
+            Decryption::IdentityFile(_) if decryptor.is_scrypt() => {
+                return Err(Error::Message(
+                    "snapshot is encrypted with a passphrase, not to an identity".to_string(),
+                ))
+            }
+            Decryption::Passphrase(passphrase) => vec![Box::new(age::scrypt::Identity::new(
+                SecretString::from(passphrase.clone()),
+            ))],
+            Decryption::IdentityFile(path) => {
+                age::IdentityFile::from_file(path.to_string_lossy().to_string())?
+                    .into_identities()
+                    .map_err(age_error)?
+            }
+        };
+        let mut reader = decryptor
+            .decrypt(identities.iter().map(AsRef::as_ref))
+            .map_err(age_error)?;
+        let mut plain = Zeroizing::new(vec![]);
+        reader.read_to_end(&mut plain)?;
+        Ok(serde_json::from_slice(&plain)?)
+    }
+}
+
+fn age_error(err: impl std::fmt::Display) -> Error {
+    Error::Message(format!("snapshot: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use age::secrecy::ExposeSecret;
+
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            created_at: 1_700_000_000,
+            maps: vec![MapSnapshot {
+                provider: "mem".to_string(),
+                map_id: "dev".to_string(),
+                path: "app/dev".to_string(),
+                kvs: BTreeMap::from([("DB_PASS".to_string(), "s3cret".into())]),
+            }],
+        }
+    }
+
+    #[test]
+    fn passphrase_roundtrip() {
+        let data = snapshot()
+            .encrypt(&Encryption::Passphrase("correct horse".to_string()))
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&data).contains("s3cret"));
+        assert_eq!(
+            Snapshot::decrypt(&data, &Decryption::Passphrase("correct horse".to_string())).unwrap(),
+            snapshot()
+        );
+        assert!(Snapshot::decrypt(&data, &Decryption::Passphrase("wrong".to_string())).is_err());
+    }
+
+    #[test]
+    fn recipients_roundtrip() {
+        let identity = age::x25519::Identity::generate();
+        let path = std::env::temp_dir().join(format!("teller-identity-{}", std::process::id()));
+        fs_err::write(&path, identity.to_string().expose_secret()).unwrap();
+
+        let data = snapshot()
+            .encrypt(&Encryption::Recipients(vec![identity
+                .to_public()
+                .to_string()]))
+            .unwrap();
+        assert_eq!(
+            Snapshot::decrypt(&data, &Decryption::IdentityFile(path.clone())).unwrap(),
+            snapshot()
+        );
+        fs_err::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//! SSH keys from collected key-values, for the duration of a command.
+//!
+//! Keys are loaded into an ephemeral `ssh-agent` the command gets as
+//! `SSH_AUTH_SOCK`, or written to temporary identity files the command gets in
+//! `GIT_SSH_COMMAND`, without touching `~/.ssh`.
+//!
+//! A certificate for a key `DEPLOY_KEY` is taken from `DEPLOY_KEY_CERT`, if
+//! collected.
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use fs_err as fs;
+
+use crate::{provision, Error, Result};
+
+/// how long to wait for the agent's socket
+const AGENT_TIMEOUT: Duration = Duration::from_secs(5);
+const CERT_SUFFIX: &str = "_CERT";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// load keys into an ephemeral agent
+    #[default]
+    Agent,
+    /// write keys to temporary identity files
+    IdentityFiles,
+}
+
+/// A private key, with its certificate if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    pub name: String,
+    pub private: String,
+    pub certificate: Option<String>,
+}
+
+/// Take the keys named in `names`, and their certificates, out of `env_kvs`
+///
+/// # Errors
+///
+/// This function will return an error if a named key was not collected
+pub fn take_keys(env_kvs: &mut Vec<(String, String)>, names: &[String]) -> Result<Vec<Key>> {
+    let mut take = |name: &str| {
+        env_kvs
+            .iter()
+            .position(|(k, _)| k == name)
+            .map(|idx| env_kvs.remove(idx).1)
+    };
+    names
+        .iter()
+        .map(|name| {
+            let private = take(name)
+                .ok_or_else(|| Error::NotFound(format!("ssh key '{name}' was not collected")))?;
+            Ok(Key {
+                name: name.clone(),
+                private,
+                certificate: take(&format!("{name}{CERT_SUFFIX}")),
+            })
+        })
+        .collect()
+}
+
+/// Keys made available to commands, until dropped: the agent is stopped and
+/// identity files are removed
+#[derive(Debug)]
+pub struct Session {
+    // removed with everything in it when dropped
+    dir: tempfile::TempDir,
+    agent: Option<Child>,
+    env: Vec<(String, String)>,
+}
+
+impl Session {
+    /// Load keys into a new agent, or write them to identity files
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the keys cannot be written, the
+    /// agent does not start or does not take a key
+    pub fn start(keys: &[Key], mode: Mode) -> Result<Self> {
+        let dir = tempfile::Builder::new().prefix("teller-ssh-").tempdir()?;
+        let files = keys
+            .iter()
+            .map(|key| write_key(dir.path(), key))
+            .collect::<Result<Vec<_>>>()?;
+        let mut session = Self {
+            dir,
+            agent: None,
+            env: vec![],
+        };
+        match mode {
+            Mode::Agent => {
+                let socket = session.dir.path().join("agent.sock");
+                session.agent = Some(start_agent(&socket)?);
+                let socket = socket.to_string_lossy().to_string();
+                for (key, file) in keys.iter().zip(&files) {
+                    let status = Command::new("ssh-add")
+                        .arg("-q")
+                        .arg(file)
+                        .env("SSH_AUTH_SOCK", &socket)
+                        .stdin(Stdio::null())
+                        .status()
+                        .map_err(Error::Exec)?;
+                    if !status.success() {
+                        return Err(Error::Message(format!(
+                            "ssh-add cannot load ssh key '{}', is it a private key without \
+                             passphrase?",
+                            key.name
+                        )));
+                    }
+                    // the agent has it, it is not needed on disk
+                    fs::remove_file(file)?;
+                    let _ = fs::remove_file(cert_path(file));
+                }
+                session.env.push(("SSH_AUTH_SOCK".to_string(), socket));
+            }
+            Mode::IdentityFiles => {
+                let mut ssh = vec![
+                    "ssh".to_string(),
+                    "-o".to_string(),
+                    "IdentitiesOnly=yes".to_string(),
+                ];
+                for file in &files {
+                    ssh.push("-i".to_string());
+                    ssh.push(file.to_string_lossy().to_string());
+                }
+                session
+                    .env
+                    .push(("GIT_SSH_COMMAND".to_string(), shell_words::join(ssh)));
+            }
+        }
+        Ok(session)
+    }
+
+    /// Variables pointing commands at the keys
+    #[must_use]
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(agent) = &mut self.agent {
+            let _ = agent.kill();
+            let _ = agent.wait();
+        }
+    }
+}
+
+fn write_key(dir: &Path, key: &Key) -> Result<PathBuf> {
+    let path = dir.join(format!("id_{}", key.name.to_lowercase()));
+    provision::write(&path, &with_newline(&key.private))?;
+    if let Some(certificate) = &key.certificate {
+        // ssh and ssh-add pick up the certificate next to the key
+        provision::write(&cert_path(&path), &with_newline(certificate))?;
+    }
+    Ok(path)
+}
+
+fn cert_path(key: &Path) -> PathBuf {
+    let mut name = key.as_os_str().to_owned();
+    name.push("-cert.pub");
+    PathBuf::from(name)
+}
+
+/// Keys kept in single-line values lose their trailing newline, which ssh
+/// needs to read them
+fn with_newline(value: &str) -> String {
+    if value.ends_with('\n') {
+        value.to_string()
+    } else {
+        format!("{value}\n")
+    }
+}
+
+fn start_agent(socket: &Path) -> Result<Child> {
+    let mut agent = Command::new("ssh-agent")
+        .arg("-D")
+        .arg("-a")
+        .arg(socket)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(Error::Exec)?;
+    let started = Instant::now();
+    while !socket.exists() {
+        if agent.try_wait()?.is_some() || started.elapsed() > AGENT_TIMEOUT {
+            let _ = agent.kill();
+            let _ = agent.wait();
+            return Err(Error::Message("ssh-agent did not start".to_string()));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Ok(agent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taking_keys() {
+        let mut env_kvs = vec![
+            ("DEPLOY_KEY".to_string(), "private".to_string()),
+            ("DEPLOY_KEY_CERT".to_string(), "cert".to_string()),
+            ("OTHER".to_string(), "value".to_string()),
+        ];
+        assert_eq!(
+            take_keys(&mut env_kvs, &["DEPLOY_KEY".to_string()]).unwrap(),
+            vec![Key {
+                name: "DEPLOY_KEY".to_string(),
+                private: "private".to_string(),
+                certificate: Some("cert".to_string()),
+            }]
+        );
+        assert_eq!(env_kvs, vec![("OTHER".to_string(), "value".to_string())]);
+        assert!(take_keys(&mut env_kvs, &["NOPE".to_string()]).is_err());
+    }
+
+    #[test]
+    fn identity_files() {
+        let key = Key {
+            name: "DEPLOY_KEY".to_string(),
+            private: "private".to_string(),
+            certificate: Some("cert".to_string()),
+        };
+        let session = Session::start(&[key], Mode::IdentityFiles).unwrap();
+        let (name, command) = &session.env()[0];
+        assert_eq!(name, "GIT_SSH_COMMAND");
+        let file = session.dir.path().join("id_deploy_key");
+        assert_eq!(
+            shell_words::split(command).unwrap(),
+            vec![
+                "ssh",
+                "-o",
+                "IdentitiesOnly=yes",
+                "-i",
+                file.to_str().unwrap()
+            ]
+        );
+        assert_eq!(fs::read_to_string(&file).unwrap(), "private\n");
+        assert_eq!(fs::read_to_string(cert_path(&file)).unwrap(), "cert\n");
+
+        let dir = session.dir.path().to_path_buf();
+        drop(session);
+        assert!(!dir.exists());
+    }
+}
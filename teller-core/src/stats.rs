@@ -0,0 +1,160 @@
+//! Numbers about each configured provider, for auditing large configurations
+//! and finding slow backends, see [`crate::teller::Teller::stats`]
+
+use serde_derive::Serialize;
+use teller_providers::providers::ProviderKind;
+
+/// One fetch of a map
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapStats {
+    pub id: String,
+    pub path: String,
+    /// keys fetched
+    pub keys: usize,
+    /// how long fetching took, in milliseconds
+    pub latency_ms: u64,
+    /// seconds since teller last changed the map, according to its history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_secs_ago: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl MapStats {
+    /// One line summary, e.g. `dev (secret/data/dev): 8 key(s) in 120ms,
+    /// changed 3h ago`
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let what = format!("{} ({})", self.id, self.path);
+        if let Some(error) = &self.error {
+            return format!("{what}: failed after {}ms: {error}", self.latency_ms);
+        }
+        let changed = self
+            .changed_secs_ago
+            .map(|secs| format!(", changed {} ago", age(secs)))
+            .unwrap_or_default();
+        format!(
+            "{what}: {} key(s) in {}ms{changed}",
+            self.keys, self.latency_ms
+        )
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProviderStats {
+    pub name: String,
+    pub kind: ProviderKind,
+    pub maps: Vec<MapStats>,
+}
+
+impl ProviderStats {
+    #[must_use]
+    pub fn keys(&self) -> usize {
+        self.maps.iter().map(|map| map.keys).sum()
+    }
+
+    /// Time spent fetching all maps, in milliseconds
+    #[must_use]
+    pub fn latency_ms(&self) -> u64 {
+        self.maps.iter().map(|map| map.latency_ms).sum()
+    }
+
+    #[must_use]
+    pub fn slowest(&self) -> Option<&MapStats> {
+        self.maps.iter().max_by_key(|map| map.latency_ms)
+    }
+
+    #[must_use]
+    pub fn failed(&self) -> usize {
+        self.maps.iter().filter(|map| map.error.is_some()).count()
+    }
+
+    /// Seconds since teller last changed any of the maps
+    #[must_use]
+    pub fn changed_secs_ago(&self) -> Option<u64> {
+        self.maps
+            .iter()
+            .filter_map(|map| map.changed_secs_ago)
+            .min()
+    }
+
+    /// One line summary, e.g. `vault (hashicorp): 2 map(s), 12 key(s) in
+    /// 310ms (slowest: prod 250ms), changed 3h ago`
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let slowest = self
+            .slowest()
+            .filter(|_| self.maps.len() > 1)
+            .map(|map| format!(" (slowest: {} {}ms)", map.id, map.latency_ms))
+            .unwrap_or_default();
+        let failed = match self.failed() {
+            0 => String::new(),
+            failed => format!(", {failed} failed"),
+        };
+        let changed = self
+            .changed_secs_ago()
+            .map(|secs| format!(", changed {} ago", age(secs)))
+            .unwrap_or_default();
+        format!(
+            "{} ({}): {} map(s), {} key(s) in {}ms{slowest}{failed}{changed}",
+            self.name,
+            self.kind,
+            self.maps.len(),
+            self.keys(),
+            self.latency_ms()
+        )
+    }
+}
+
+/// A rough age, e.g. `45s`, `12m`, `3h` or `5d`
+fn age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summaries() {
+        let stats = ProviderStats {
+            name: "vault".to_string(),
+            kind: ProviderKind::Inmem,
+            maps: vec![
+                MapStats {
+                    id: "dev".to_string(),
+                    path: "secret/data/dev".to_string(),
+                    keys: 8,
+                    latency_ms: 60,
+                    changed_secs_ago: Some(3 * 3600 + 20),
+                    error: None,
+                },
+                MapStats {
+                    id: "prod".to_string(),
+                    path: "secret/data/prod".to_string(),
+                    latency_ms: 250,
+                    error: Some("permission denied".to_string()),
+                    ..MapStats::default()
+                },
+            ],
+        };
+        assert_eq!(
+            stats.summary(),
+            "vault (inmem): 2 map(s), 8 key(s) in 310ms (slowest: prod 250ms), 1 failed, changed \
+             3h ago"
+        );
+        assert_eq!(
+            stats.maps[0].summary(),
+            "dev (secret/data/dev): 8 key(s) in 60ms, changed 3h ago"
+        );
+        assert_eq!(
+            stats.maps[1].summary(),
+            "prod (secret/data/prod): failed after 250ms: permission denied"
+        );
+    }
+}
@@ -1,44 +1,543 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use fs_err as fs;
+use handlebars::{
+    no_escape, Context as HbsContext, Handlebars, Helper, HelperDef, RenderContext,
+    RenderErrorReason, ScopedJson,
+};
+use ignore::WalkBuilder;
+use lazy_static::lazy_static;
+use regex::Regex;
 use teller_providers::config::KV;
-use tera::{from_value, to_value, Context, Result, Tera};
+use tera::{from_value, to_value, Context, Result, Tera, Value};
+
+/// Files with this extension are rendered with Tera when rendering a directory
+pub const TEMPLATE_EXTENSION: &str = "tera";
+/// Files with these extensions are rendered with Handlebars when rendering a directory
+pub const HANDLEBARS_EXTENSIONS: &[&str] = &["hbs", "handlebars"];
+
+lazy_static! {
+    static ref ENVSUBST_VAR: Regex =
+        Regex::new(r"\$(?:\{([A-Za-z_][A-Za-z0-9_]*)\}|([A-Za-z_][A-Za-z0-9_]*))")
+            .expect("valid regex");
+}
+
+/// Marks the start of a managed section, in any comment style (`# teller:begin`, `// teller:begin`)
+pub const BEGIN_MARKER: &str = "teller:begin";
+/// Marks the end of a managed section
+pub const END_MARKER: &str = "teller:end";
+
+fn string_arg(args: &HashMap<String, Value>, name: &str) -> Result<Option<String>> {
+    args.get(name)
+        .map(|val| {
+            from_value::<String>(val.clone())
+                .map_err(|_| format!("cannot get parameter '{name}'").into())
+        })
+        .transpose()
+}
+
+/// How missing keys are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Fail on the first missing key
+    #[default]
+    Default,
+    /// Render everything, then fail listing all missing keys
+    Strict,
+    /// Render missing keys as empty (or their `default=`), reporting them as missing
+    Lenient,
+}
+
+/// Template engines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    #[default]
+    Tera,
+    /// Handlebars, for templates shared with other tooling
+    Handlebars,
+    /// `${VAR}` and `$VAR` placeholders (as with `envsubst`), for existing configuration
+    /// files. Unknown variables are left as they are, unless in strict or lenient mode.
+    Envsubst,
+}
+
+impl Engine {
+    /// The engine of a template file by its extension: `.tera`, `.hbs` or `.handlebars`
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        if ext == TEMPLATE_EXTENSION {
+            Some(Self::Tera)
+        } else if HANDLEBARS_EXTENSIONS.contains(&ext) {
+            Some(Self::Handlebars)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Opts {
+    pub mode: Mode,
+    pub engine: Engine,
+}
+
+/// A rendered template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendered {
+    pub output: String,
+    /// keys that were not found, in lenient mode
+    pub missing: Vec<String>,
+}
 
-struct KeyFn {
+/// Key lookups shared by both engines
+struct Lookup {
     kvs: Vec<KV>,
+    /// Records missing keys, instead of failing on the first one
+    missing: Option<Mutex<BTreeSet<String>>>,
+}
+
+impl Lookup {
+    fn not_found(&self, name: String, default: Value) -> std::result::Result<Value, String> {
+        let Some(missing) = &self.missing else {
+            return Err(format!("not found: '{name}'"));
+        };
+        missing
+            .lock()
+            .map_err(|_| "poisoned lock".to_string())?
+            .insert(name);
+        Ok(default)
+    }
+
+    fn key(&self, name: String, default: Option<Value>) -> std::result::Result<Value, String> {
+        if let Some(kv) = self.kvs.iter().find(|kv| kv.key == name) {
+            return Ok(Value::String(kv.value.expose().to_string()));
+        }
+        default.map_or_else(|| self.not_found(name, Value::String(String::new())), Ok)
+    }
+
+    fn kv(&self, name: String) -> std::result::Result<Value, String> {
+        if let Some(kv) = self.kvs.iter().find(|kv| kv.key == name) {
+            return to_value(kv).map_err(|e| e.to_string());
+        }
+        self.not_found(name, Value::Null)
+    }
+
+    fn keys(&self, prefix: &str) -> std::result::Result<Value, String> {
+        to_value(
+            self.kvs
+                .iter()
+                .filter(|kv| kv.key.starts_with(prefix))
+                .collect::<Vec<_>>(),
+        )
+        .map_err(|e| e.to_string())
+    }
 }
+
+/// `key(name="DB_PASS", default="")`: the value of a key
+struct KeyFn(Arc<Lookup>);
 impl tera::Function for KeyFn {
-    fn call(
+    fn call(&self, args: &HashMap<String, Value>) -> Result<Value> {
+        let name = string_arg(args, "name")?
+            .ok_or_else(|| tera::Error::from("cannot get parameter 'name'"))?;
+        Ok(self.0.key(name, args.get("default").cloned())?)
+    }
+}
+
+/// `kv(name="DB_PASS")`: a key with its value, provider and path metadata
+struct KvFn(Arc<Lookup>);
+impl tera::Function for KvFn {
+    fn call(&self, args: &HashMap<String, Value>) -> Result<Value> {
+        let name = string_arg(args, "name")?
+            .ok_or_else(|| tera::Error::from("cannot get parameter 'name'"))?;
+        Ok(self.0.kv(name)?)
+    }
+}
+
+/// `keys(prefix="DB_")`: all keys (optionally starting with a prefix) with their values,
+/// provider and path metadata, for iteration
+struct KeysFn(Arc<Lookup>);
+impl tera::Function for KeysFn {
+    fn call(&self, args: &HashMap<String, Value>) -> Result<Value> {
+        let prefix = string_arg(args, "prefix")?.unwrap_or_default();
+        Ok(self.0.keys(&prefix)?)
+    }
+}
+
+/// A lookup as a Handlebars helper, taking the key name (or prefix) as its first param:
+/// `{{key "DB_PASS" default="x"}}`, `{{#with (kv "DB_PASS")}}`, `{{#each (keys "DB_")}}`
+struct LookupHelper {
+    lookup: Arc<Lookup>,
+    call: fn(&Lookup, String, Option<Value>) -> std::result::Result<Value, String>,
+}
+
+impl HelperDef for LookupHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
         &self,
-        args: &std::collections::HashMap<String, tera::Value>,
-    ) -> tera::Result<tera::Value> {
-        args.get("name").map_or_else(
-            || Err("cannot get parameter 'name'".into()),
-            |val| {
-                from_value::<String>(val.clone()).map_or_else(
-                    |_| Err("cannot get parameter 'name'".into()),
-                    |v| {
-                        self.kvs
-                            .iter()
-                            .find(|kv| kv.key == v)
-                            .and_then(|kv| to_value(&kv.value).ok())
-                            .ok_or_else(|| "not found".into())
-                    },
-                )
-            },
-        )
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc HbsContext,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> std::result::Result<ScopedJson<'rc>, handlebars::RenderError> {
+        let name = h
+            .param(0)
+            .and_then(|p| p.value().as_str())
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let default = h.hash_get("default").map(|d| d.value().clone());
+        (self.call)(&self.lookup, name, default)
+            .map(ScopedJson::Derived)
+            .map_err(|e| RenderErrorReason::Other(e).into())
+    }
+}
+
+/// A string filter, for both engines
+type StringFilter = fn(&str) -> std::result::Result<String, String>;
+
+const FILTERS: &[(&str, StringFilter)] = &[
+    ("b64encode", |s| Ok(STANDARD.encode(s))),
+    ("b64decode", |s| {
+        let bytes = STANDARD.decode(s.trim()).map_err(|e| e.to_string())?;
+        String::from_utf8(bytes).map_err(|e| e.to_string())
+    }),
+    ("snake", |s| Ok(snake_case(s))),
+];
+
+/// Tera has its own `upper` and `lower`
+const HANDLEBARS_FILTERS: &[(&str, StringFilter)] = &[
+    ("upper", |s| Ok(s.to_uppercase())),
+    ("lower", |s| Ok(s.to_lowercase())),
+];
+
+struct FilterHelper(&'static str, StringFilter);
+impl HelperDef for FilterHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc HbsContext,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> std::result::Result<ScopedJson<'rc>, handlebars::RenderError> {
+        let s = h
+            .param(0)
+            .and_then(|p| p.value().as_str())
+            .ok_or_else(|| RenderErrorReason::InvalidParamType("string"))?;
+        (self.1)(s)
+            .map(|s| ScopedJson::Derived(Value::String(s)))
+            .map_err(|e| RenderErrorReason::Other(format!("{}: {e}", self.0)).into())
+    }
+}
+
+/// `fooBar-baz QUX` -> `foo_bar_baz_qux`
+fn snake_case(s: &str) -> String {
+    let mut out = String::new();
+    let mut prev_lower = false;
+    for c in s.chars() {
+        if c.is_alphanumeric() {
+            if c.is_uppercase() && prev_lower {
+                out.push('_');
+            }
+            prev_lower = c.is_lowercase() || c.is_numeric();
+            out.extend(c.to_lowercase());
+        } else {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            prev_lower = false;
+        }
     }
+    out.trim_end_matches('_').to_string()
 }
 
 /// Render a template with access to KVs
 ///
+/// Besides Tera's built-ins (such as the `upper` and `lower` filters), templates get:
+///
+/// * `key(name=, default=)`: the value of a key
+/// * `kv(name=)`: a key with its value, provider and path metadata
+/// * `keys(prefix=)`: all keys with their metadata, for iteration
+/// * `b64encode`, `b64decode` and `snake` filters
+///
+/// With Handlebars, these are helpers: `{{key "DB_PASS" default="x"}}`, `{{kv "DB_PASS"}}`,
+/// `{{keys "DB_"}}`, `{{b64encode (key "DB_PASS")}}`, as well as `upper` and `lower`.
+///
 /// # Errors
 ///
 /// This function will return an error if rendering fails
 pub fn render(template: &str, kvs: Vec<KV>) -> Result<String> {
-    let mut tera = Tera::default();
-    tera.register_function("key", KeyFn { kvs });
-    let res = tera.render_str(template, &Context::new())?;
+    let res = Renderer::new(kvs, &Opts::default())
+        .tera
+        .render_str(template, &Context::new())?;
     Ok(res)
 }
 
+/// Render the template of a derived key. Collected keys are variables, as in
+/// `postgres://{{ DB_USER }}@{{ DB_HOST }}/app`, on top of what [`render`]
+/// offers.
+///
+/// # Errors
+///
+/// This function will return an error if rendering fails, e.g. a variable is
+/// not a collected key
+pub fn render_derived(template: &str, kvs: &[KV]) -> crate::Result<String> {
+    let mut context = Context::new();
+    for kv in kvs {
+        context.insert(kv.key.as_str(), kv.value.expose());
+    }
+    Renderer::new(kvs.to_vec(), &Opts::default())
+        .tera
+        .render_str(template, &context)
+        .map_err(|err| {
+            // tera's own message only names the one-off template, the cause
+            // is at the bottom of the chain
+            let mut cause: &dyn std::error::Error = &err;
+            while let Some(source) = cause.source() {
+                cause = source;
+            }
+            crate::Error::Message(cause.to_string())
+        })
+}
+
+/// Render a template, with the engine and handling of missing keys in `opts`
+///
+/// # Errors
+///
+/// This function will return an error if rendering fails, or keys are missing in strict mode
+pub fn render_with(template: &str, kvs: Vec<KV>, opts: &Opts) -> crate::Result<Rendered> {
+    let mut renderer = Renderer::new(kvs, opts);
+    let output = renderer.render(opts.engine, template)?;
+    Ok(Rendered {
+        output,
+        missing: renderer.finish()?,
+    })
+}
+
+struct Renderer {
+    tera: Tera,
+    handlebars: Handlebars<'static>,
+    mode: Mode,
+    lookup: Arc<Lookup>,
+}
+
+impl Renderer {
+    fn new(kvs: Vec<KV>, opts: &Opts) -> Self {
+        let lookup = Arc::new(Lookup {
+            kvs,
+            missing: match opts.mode {
+                Mode::Default => None,
+                Mode::Strict | Mode::Lenient => Some(Mutex::default()),
+            },
+        });
+
+        let mut tera = Tera::default();
+        tera.register_function("kv", KvFn(lookup.clone()));
+        tera.register_function("keys", KeysFn(lookup.clone()));
+        tera.register_function("key", KeyFn(lookup.clone()));
+        for (name, filter) in FILTERS {
+            let (name, filter) = (*name, *filter);
+            tera.register_filter(name, move |value: &Value, _: &HashMap<String, Value>| {
+                let s = from_value::<String>(value.clone())
+                    .map_err(|_| tera::Error::from(format!("{name}: expected a string")))?;
+                Ok(Value::String(
+                    filter(&s).map_err(|e| tera::Error::from(format!("{name}: {e}")))?,
+                ))
+            });
+        }
+
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(no_escape);
+        handlebars.register_helper(
+            "key",
+            Box::new(LookupHelper {
+                lookup: lookup.clone(),
+                call: Lookup::key,
+            }),
+        );
+        handlebars.register_helper(
+            "kv",
+            Box::new(LookupHelper {
+                lookup: lookup.clone(),
+                call: |lookup, name, _| lookup.kv(name),
+            }),
+        );
+        handlebars.register_helper(
+            "keys",
+            Box::new(LookupHelper {
+                lookup: lookup.clone(),
+                call: |lookup, prefix, _| lookup.keys(&prefix),
+            }),
+        );
+        for (name, filter) in FILTERS.iter().chain(HANDLEBARS_FILTERS) {
+            handlebars.register_helper(name, Box::new(FilterHelper(name, *filter)));
+        }
+
+        Self {
+            tera,
+            handlebars,
+            mode: opts.mode,
+            lookup,
+        }
+    }
+
+    fn render(&mut self, engine: Engine, template: &str) -> crate::Result<String> {
+        match engine {
+            Engine::Tera => Ok(self.tera.render_str(template, &Context::new())?),
+            Engine::Handlebars => self
+                .handlebars
+                .render_template(template, &())
+                .map_err(|e| crate::Error::Handlebars(Box::new(e))),
+            Engine::Envsubst => self.envsubst(template),
+        }
+    }
+
+    fn envsubst(&self, template: &str) -> crate::Result<String> {
+        let mut out = String::with_capacity(template.len());
+        let mut last = 0;
+        for caps in ENVSUBST_VAR.captures_iter(template) {
+            let (Some(all), Some(name)) = (caps.get(0), caps.get(1).or_else(|| caps.get(2))) else {
+                continue;
+            };
+            out.push_str(&template[last..all.start()]);
+            last = all.end();
+            match self.lookup.kvs.iter().find(|kv| kv.key == name.as_str()) {
+                Some(kv) => out.push_str(kv.value.expose()),
+                // not ours, e.g. `$host` in an nginx configuration
+                None if self.lookup.missing.is_none() => out.push_str(all.as_str()),
+                None => {
+                    self.lookup
+                        .not_found(name.as_str().to_string(), Value::Null)
+                        .map_err(crate::Error::Message)?;
+                }
+            }
+        }
+        out.push_str(&template[last..]);
+        Ok(out)
+    }
+
+    /// The missing keys, or an error listing them all in strict mode
+    fn finish(self) -> crate::Result<Vec<String>> {
+        let missing: Vec<String> = self
+            .lookup
+            .missing
+            .as_ref()
+            .and_then(|missing| missing.lock().ok().map(|m| m.iter().cloned().collect()))
+            .unwrap_or_default();
+        if self.mode == Mode::Strict && !missing.is_empty() {
+            return Err(crate::Error::Message(format!(
+                "missing keys: {}",
+                missing.join(", ")
+            )));
+        }
+        Ok(missing)
+    }
+}
+
+/// Render a directory tree into `out_dir`.
+///
+/// Files ending with `.tera` (or `.hbs` and `.handlebars` for Handlebars) are rendered and
+/// written without the extension, all other files are copied verbatim. File permissions
+/// are preserved.
+///
+/// Returns the number of rendered templates, and missing keys in lenient mode.
+///
+/// # Errors
+///
+/// This function will return an error if reading, rendering or writing a file fails, or
+/// keys are missing in strict mode
+pub fn render_dir(
+    in_dir: &Path,
+    out_dir: &Path,
+    kvs: Vec<KV>,
+    opts: &Opts,
+) -> crate::Result<(usize, Vec<String>)> {
+    let mut renderer = Renderer::new(kvs, opts);
+    let mut count = 0;
+    for entry in WalkBuilder::new(in_dir).standard_filters(false).build() {
+        let entry = entry.map_err(|e| crate::Error::Message(e.to_string()))?;
+        let path = entry.path();
+        let relative = path.strip_prefix(in_dir).unwrap_or(path);
+        let target = out_dir.join(relative);
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+
+        if let Some(engine) = Engine::from_path(path) {
+            let target = target.with_extension("");
+            let template = fs::read_to_string(path)?;
+            let out = renderer.render(engine, &template).map_err(|e| {
+                crate::Error::Message(format!("cannot render {}: {e:?}", path.display()))
+            })?;
+            let permissions = fs::metadata(path)?.permissions();
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                use fs_err::os::unix::fs::OpenOptionsExt;
+                options.mode(permissions.mode());
+            }
+            let mut file = options.open(&target)?;
+            // an existing file keeps its mode, set it before any value is written
+            file.set_permissions(permissions)?;
+            file.write_all(out.as_bytes())?;
+            count += 1;
+        } else {
+            fs::copy(path, &target)?;
+        }
+    }
+    Ok((count, renderer.finish()?))
+}
+
+/// Replace the managed section of `content`, between the [`BEGIN_MARKER`] and
+/// [`END_MARKER`] lines, with `section`. The marker lines and everything around them
+/// are kept as they are.
+///
+/// # Errors
+///
+/// This function will return an error if there isn't exactly one managed section
+pub fn replace_section(content: &str, section: &str) -> crate::Result<String> {
+    let newline = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let lines = content.split_inclusive('\n').collect::<Vec<_>>();
+    let find = |marker: &str| {
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.contains(marker))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>()
+    };
+    let (begin, end) = match (&find(BEGIN_MARKER)[..], &find(END_MARKER)[..]) {
+        ([begin], [end]) if begin < end => (*begin, *end),
+        _ => {
+            return Err(crate::Error::Message(format!(
+                "expected a single section between '{BEGIN_MARKER}' and '{END_MARKER}' lines"
+            )))
+        }
+    };
+
+    let mut out = lines[..=begin].concat();
+    if !out.ends_with('\n') {
+        out.push_str(newline);
+    }
+    for line in section.lines() {
+        out.push_str(line);
+        out.push_str(newline);
+    }
+    out.push_str(&lines[end..].concat());
+    Ok(out)
+}
+
 #[cfg(test)]
 mod tests {
     use insta::assert_debug_snapshot;
@@ -59,4 +558,271 @@ mod tests {
         )];
         assert_debug_snapshot!(render("hello {{ key(name='k') }}", kvs.to_vec()));
     }
+
+    #[test]
+    fn render_helpers() {
+        let provider = ProviderInfo {
+            kind: ProviderKind::Inmem,
+            name: "test".to_string(),
+        };
+        let kvs = vec![
+            KV::from_literal("some/path", "DB_USER", "admin", provider.clone()),
+            KV::from_literal("some/path", "DB_PASS", "s3cret", provider.clone()),
+            KV::from_literal("other/path", "apiKey", "aGVsbG8=", provider),
+        ];
+        let render = |template: &str| render(template, kvs.clone()).unwrap();
+
+        assert_eq!(render("{{ key(name='NOPE', default='none') }}"), "none");
+        assert!(super::render("{{ key(name='NOPE') }}", kvs.clone()).is_err());
+        assert_eq!(
+            render("{% for kv in keys(prefix='DB_') %}{{ kv.key }}={{ kv.value }};{% endfor %}"),
+            "DB_USER=admin;DB_PASS=s3cret;"
+        );
+        assert_eq!(render("{{ keys() | length }}"), "3");
+        assert_eq!(
+            render("{% set db = kv(name='DB_PASS') %}{{ db.provider.name }}:{{ db.path.path }}"),
+            "test:some/path"
+        );
+        assert_eq!(render("{{ key(name='DB_PASS') | b64encode }}"), "czNjcmV0");
+        assert_eq!(render("{{ key(name='apiKey') | b64decode }}"), "hello");
+        assert_eq!(render("{{ 'apiKey' | snake | upper }}"), "API_KEY");
+    }
+
+    #[test]
+    fn render_directory() {
+        let dir = std::env::temp_dir().join(format!("teller-template-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (in_dir, out_dir) = (dir.join("in"), dir.join("out"));
+        fs::create_dir_all(in_dir.join("nginx")).unwrap();
+        fs::write(in_dir.join("app.env.tera"), "TOKEN={{ key(name='k') }}\n").unwrap();
+        fs::write(
+            in_dir.join("nginx/nginx.conf"),
+            "worker_processes {{ 4 }};\n",
+        )
+        .unwrap();
+        fs::write(in_dir.join(".hidden.tera"), "{{ key(name='k') | upper }}").unwrap();
+        fs::write(in_dir.join("nginx/site.conf.hbs"), "env K={{key \"k\"}};").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                in_dir.join("app.env.tera"),
+                std::fs::Permissions::from_mode(0o600),
+            )
+            .unwrap();
+            // left over from an earlier render, readable by everyone
+            fs::create_dir_all(&out_dir).unwrap();
+            fs::write(out_dir.join("app.env"), "TOKEN=old\n").unwrap();
+            fs::set_permissions(
+                out_dir.join("app.env"),
+                std::fs::Permissions::from_mode(0o644),
+            )
+            .unwrap();
+        }
+
+        let kvs = vec![KV::from_literal(
+            "some/path",
+            "k",
+            "foobaz",
+            ProviderInfo {
+                kind: ProviderKind::Inmem,
+                name: "test".to_string(),
+            },
+        )];
+        assert_eq!(
+            render_dir(&in_dir, &out_dir, kvs, &Opts::default()).unwrap(),
+            (3, vec![])
+        );
+
+        assert_eq!(
+            fs::read_to_string(out_dir.join("app.env")).unwrap(),
+            "TOKEN=foobaz\n"
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join(".hidden")).unwrap(),
+            "FOOBAZ"
+        );
+        // not a template, copied as is
+        assert_eq!(
+            fs::read_to_string(out_dir.join("nginx/nginx.conf")).unwrap(),
+            "worker_processes {{ 4 }};\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(out_dir.join("app.env"))
+                    .unwrap()
+                    .permissions()
+                    .mode()
+                    & 0o777,
+                0o600
+            );
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replace_sections() {
+        let content = "user nginx;\n# teller:begin\nold\n# teller:end\nworker 4;\n";
+        assert_eq!(
+            replace_section(content, "env A=1;\nenv B=2;").unwrap(),
+            "user nginx;\n# teller:begin\nenv A=1;\nenv B=2;\n# teller:end\nworker 4;\n"
+        );
+        assert_eq!(
+            replace_section("// teller:begin\r\n// teller:end", "a\n").unwrap(),
+            "// teller:begin\r\na\r\n// teller:end"
+        );
+
+        assert!(replace_section("no markers", "a").is_err());
+        assert!(replace_section("# teller:end\n# teller:begin\n", "a").is_err());
+        assert!(replace_section(
+            "# teller:begin\n# teller:end\n# teller:begin\n# teller:end\n",
+            "a"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn render_modes() {
+        let kvs = vec![KV::from_literal(
+            "some/path",
+            "k",
+            "foobaz",
+            ProviderInfo {
+                kind: ProviderKind::Inmem,
+                name: "test".to_string(),
+            },
+        )];
+        let template = "{{ key(name='k') }},{{ key(name='A') }},{{ key(name='B', default='b') \
+                        }},{{ kv(name='C') }},{{ key(name='A') }}";
+        let render = |mode| {
+            render_with(
+                template,
+                kvs.clone(),
+                &Opts {
+                    mode,
+                    ..Opts::default()
+                },
+            )
+        };
+
+        let err = render(Mode::Default).unwrap_err().to_string();
+        assert!(err.contains("render"), "{err}");
+        assert_eq!(
+            render(Mode::Strict).unwrap_err().to_string(),
+            "missing keys: A, C"
+        );
+        assert_eq!(
+            render(Mode::Lenient).unwrap(),
+            Rendered {
+                output: "foobaz,,b,,".to_string(),
+                missing: vec!["A".to_string(), "C".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn render_handlebars() {
+        let provider = ProviderInfo {
+            kind: ProviderKind::Inmem,
+            name: "test".to_string(),
+        };
+        let kvs = vec![
+            KV::from_literal("some/path", "DB_USER", "admin", provider.clone()),
+            KV::from_literal("some/path", "DB_PASS", "s3<r>t", provider),
+        ];
+        let opts = Opts {
+            engine: Engine::Handlebars,
+            ..Opts::default()
+        };
+        let render = |template: &str| render_with(template, kvs.clone(), &opts).map(|r| r.output);
+
+        // no HTML escaping
+        assert_eq!(render("pass: {{key \"DB_PASS\"}}").unwrap(), "pass: s3<r>t");
+        assert_eq!(render("{{key \"NOPE\" default=\"none\"}}").unwrap(), "none");
+        assert!(render("{{key \"NOPE\"}}").is_err());
+        assert_eq!(
+            render("{{#each (keys \"DB_\")}}{{this.key}}={{value}};{{/each}}").unwrap(),
+            "DB_USER=admin;DB_PASS=s3<r>t;"
+        );
+        assert_eq!(
+            render("{{#with (kv \"DB_USER\")}}{{provider.name}}:{{path.path}}{{/with}}").unwrap(),
+            "test:some/path"
+        );
+        assert_eq!(
+            render("{{upper (b64encode (key \"DB_USER\"))}} {{snake \"apiKey\"}}").unwrap(),
+            "YWRTAW4= api_key"
+        );
+
+        let lenient = render_with(
+            "[{{key \"NOPE\"}}]",
+            kvs.clone(),
+            &Opts {
+                mode: Mode::Lenient,
+                ..opts
+            },
+        )
+        .unwrap();
+        assert_eq!(lenient.output, "[]");
+        assert_eq!(lenient.missing, vec!["NOPE".to_string()]);
+
+        assert_eq!(
+            Engine::from_path(Path::new("nginx.conf.hbs")),
+            Some(Engine::Handlebars)
+        );
+        assert_eq!(
+            Engine::from_path(Path::new("app.env.tera")),
+            Some(Engine::Tera)
+        );
+        assert_eq!(Engine::from_path(Path::new("app.env")), None);
+    }
+
+    #[test]
+    fn render_envsubst() {
+        let kvs = vec![KV::from_literal(
+            "some/path",
+            "DB_PASS",
+            "s3cret",
+            ProviderInfo {
+                kind: ProviderKind::Inmem,
+                name: "test".to_string(),
+            },
+        )];
+        let template = "password ${DB_PASS};\nurl postgres://u:$DB_PASS@db;\nproxy $host $1 ${};";
+        let render = |mode| {
+            render_with(
+                template,
+                kvs.clone(),
+                &Opts {
+                    mode,
+                    engine: Engine::Envsubst,
+                },
+            )
+        };
+
+        assert_eq!(
+            render(Mode::Default).unwrap().output,
+            "password s3cret;\nurl postgres://u:s3cret@db;\nproxy $host $1 ${};"
+        );
+        let lenient = render(Mode::Lenient).unwrap();
+        assert_eq!(
+            lenient.output,
+            "password s3cret;\nurl postgres://u:s3cret@db;\nproxy  $1 ${};"
+        );
+        assert_eq!(lenient.missing, vec!["host".to_string()]);
+        assert_eq!(
+            render(Mode::Strict).unwrap_err().to_string(),
+            "missing keys: host"
+        );
+    }
+
+    #[test]
+    fn snake_cases() {
+        assert_eq!(snake_case("fooBar-baz QUX"), "foo_bar_baz_qux");
+        assert_eq!(snake_case("DB_PASS"), "db_pass");
+        assert_eq!(snake_case("already_snake"), "already_snake");
+        assert_eq!(snake_case("  key2Value  "), "key2_value");
+    }
 }
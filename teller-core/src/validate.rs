@@ -0,0 +1,224 @@
+use std::collections::BTreeSet;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use regex::Regex;
+use serde_derive::Serialize;
+use teller_providers::config::{PathMap, Validation, ValueFormat, KV};
+
+use crate::{Error, Result};
+
+/// A value that does not follow its map's rules
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Violation {
+    pub provider: String,
+    pub map_id: String,
+    pub key: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {} {}: {}",
+            self.provider, self.map_id, self.key, self.reason
+        )
+    }
+}
+
+/// Check a value against a rule, returning why it fails, for every failing part of
+/// the rule
+///
+/// # Errors
+///
+/// This function will return an error if the rule's `pattern` or `charset` is
+/// not a valid regex
+pub fn check(rule: &Validation, value: &str) -> Result<Vec<String>> {
+    let mut reasons = vec![];
+    if let Some(min_length) = rule.min_length {
+        if value.chars().count() < min_length {
+            reasons.push(format!("shorter than {min_length} characters"));
+        }
+    }
+    if let Some(pattern) = &rule.pattern {
+        if !regex(pattern)?.is_match(value) {
+            reasons.push(format!("does not match pattern {pattern:?}"));
+        }
+    }
+    if let Some(charset) = &rule.charset {
+        if !regex(&format!("^[{charset}]*$"))?.is_match(value) {
+            reasons.push(format!("has characters outside of [{charset}]"));
+        }
+    }
+    if let Some(format) = rule.format {
+        let valid = match format {
+            ValueFormat::Json => serde_json::from_str::<serde_json::Value>(value).is_ok(),
+            ValueFormat::Url => url::Url::parse(value).is_ok(),
+            ValueFormat::Base64 => STANDARD.decode(value).is_ok(),
+        };
+        if !valid {
+            reasons.push(format!("is not valid {}", format_name(format)));
+        }
+    }
+    Ok(reasons)
+}
+
+/// Check KVs against the rules of the map they belong to, `key` picks the
+/// provider side key of a KV that rules are declared for
+///
+/// # Errors
+///
+/// This function will return an error if a rule is not valid
+pub fn check_map(
+    provider: &str,
+    pm: &PathMap,
+    kvs: &[KV],
+    key: fn(&KV) -> &str,
+) -> Result<Vec<Violation>> {
+    let mut violations = vec![];
+    for kv in kvs {
+        if let Some(rule) = pm.validate.get(key(kv)) {
+            violations.extend(check(rule, kv.value.expose())?.into_iter().map(|reason| {
+                Violation {
+                    provider: provider.to_string(),
+                    map_id: pm.id.clone(),
+                    key: key(kv).to_string(),
+                    reason,
+                }
+            }));
+        }
+    }
+    Ok(violations)
+}
+
+/// Check that every key a map declares, in `keys` or `validate`, was fetched with a
+/// non-empty value
+#[must_use]
+pub fn check_required(provider: &str, pm: &PathMap, kvs: &[KV]) -> Vec<Violation> {
+    pm.keys
+        .keys()
+        .chain(pm.validate.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|key| {
+            let reason = match kvs.iter().find(|kv| &kv.from_key == key) {
+                None => "missing",
+                Some(kv) if kv.value.is_empty() => "empty",
+                Some(_) => return None,
+            };
+            Some(Violation {
+                provider: provider.to_string(),
+                map_id: pm.id.clone(),
+                key: key.clone(),
+                reason: reason.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|err| Error::Message(format!("invalid validation rule: {err}")))
+}
+
+const fn format_name(format: ValueFormat) -> &'static str {
+    match format {
+        ValueFormat::Json => "JSON",
+        ValueFormat::Url => "a URL",
+        ValueFormat::Base64 => "base64",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn check_values() {
+        let rule = Validation {
+            pattern: Some("^sk_".to_string()),
+            min_length: Some(8),
+            charset: Some("a-z0-9_".to_string()),
+            format: None,
+        };
+        assert!(check(&rule, "sk_0123456").unwrap().is_empty());
+        assert_eq!(
+            check(&rule, "pk_Ab").unwrap(),
+            vec![
+                "shorter than 8 characters",
+                "does not match pattern \"^sk_\"",
+                "has characters outside of [a-z0-9_]",
+            ]
+        );
+
+        let format = |format| Validation {
+            format: Some(format),
+            ..Validation::default()
+        };
+        assert!(check(&format(ValueFormat::Json), r#"{"a": 1}"#)
+            .unwrap()
+            .is_empty());
+        assert!(!check(&format(ValueFormat::Json), "{a").unwrap().is_empty());
+        assert!(check(&format(ValueFormat::Url), "postgres://db:5432/app")
+            .unwrap()
+            .is_empty());
+        assert!(!check(&format(ValueFormat::Url), "db/app")
+            .unwrap()
+            .is_empty());
+        assert!(check(&format(ValueFormat::Base64), "aGVsbG8=")
+            .unwrap()
+            .is_empty());
+        assert!(!check(&format(ValueFormat::Base64), "hello!")
+            .unwrap()
+            .is_empty());
+
+        let bad = Validation {
+            pattern: Some("(".to_string()),
+            ..Validation::default()
+        };
+        assert!(check(&bad, "x").is_err());
+    }
+
+    #[test]
+    fn check_maps() {
+        let mut pm = PathMap::from_path("app/dev");
+        pm.id = "dev".to_string();
+        pm.validate = BTreeMap::from([(
+            "PORT".to_string(),
+            Validation {
+                pattern: Some("^[0-9]+$".to_string()),
+                ..Validation::default()
+            },
+        )]);
+        let kvs = vec![KV::from_kv("PORT", "80a"), KV::from_kv("HOST", "80a")];
+
+        let violations = check_map("dot1", &pm, &kvs, |kv| &kv.key).unwrap();
+        assert_eq!(
+            violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["[dot1] dev PORT: does not match pattern \"^[0-9]+$\""]
+        );
+    }
+
+    #[test]
+    fn check_required_keys() {
+        let mut pm = PathMap::from_path("app/dev");
+        pm.keys = BTreeMap::from([
+            ("HOST".to_string(), "DB_HOST".to_string()),
+            ("USER".to_string(), "DB_USER".to_string()),
+        ]);
+        pm.validate = BTreeMap::from([("PORT".to_string(), Validation::default())]);
+        let kvs = vec![KV::from_kv("HOST", "db"), KV::from_kv("USER", "")];
+
+        assert_eq!(
+            check_required("dot1", &pm, &kvs)
+                .iter()
+                .map(|v| format!("{} {}", v.key, v.reason))
+                .collect::<Vec<_>>(),
+            vec!["PORT missing", "USER empty"]
+        );
+    }
+}
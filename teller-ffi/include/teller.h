@@ -0,0 +1,30 @@
+/*
+ * C bindings for teller. Requests and responses are JSON documents, see the
+ * teller-ffi crate documentation.
+ *
+ * Link with libteller_ffi, and release every response with
+ * teller_free_string.
+ */
+#ifndef TELLER_H
+#define TELLER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* {"config": "path/to/.teller.yml"} -> {"ok": [kv, ...]} */
+char *teller_collect(const char *request);
+
+/* {"config": "...", "format": "json"} -> {"ok": "<exported text>"} */
+char *teller_export(const char *request);
+
+/* {"config": "...", "text": "..."} -> {"ok": "<redacted text>"} */
+char *teller_redact(const char *request);
+
+void teller_free_string(char *response);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TELLER_H */
@@ -0,0 +1,220 @@
+//! C ABI bindings for teller, so applications in other languages can embed it
+//! instead of running the CLI.
+//!
+//! Every function takes a JSON request as a NUL terminated UTF-8 string and
+//! returns a JSON response, `{"ok": ...}` or `{"error": "..."}`, which must be
+//! released with [`teller_free_string`]. Requests name the configuration with
+//! either `config` (a path to a `teller.yml`) or `config_yaml` (its content).
+//!
+//! See `include/teller.h` for the C declarations.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use teller_core::{config::Config, export, redact, teller::Teller};
+
+#[derive(Deserialize)]
+struct Request {
+    config: Option<String>,
+    config_yaml: Option<String>,
+    /// export format, for `teller_export`
+    format: Option<String>,
+    /// text to redact, for `teller_redact`
+    text: Option<String>,
+}
+
+impl Request {
+    fn config(&self) -> Result<Config, String> {
+        match (&self.config, &self.config_yaml) {
+            (Some(path), None) => Config::from_path(Path::new(path)),
+            (None, Some(yaml)) => Config::from_text(yaml),
+            _ => return Err("expected one of `config` or `config_yaml`".to_string()),
+        }
+        .map_err(|err| err.to_string())
+    }
+}
+
+/// Collect all key-values of the configuration
+///
+/// The response is `{"ok": [KV, ...]}`.
+///
+/// # Safety
+///
+/// `request` must be a valid pointer to a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn teller_collect(request: *const c_char) -> *mut c_char {
+    call(request, |_, teller| async move {
+        let kvs = teller.collect().await.map_err(|err| err.to_string())?;
+        serde_json::to_value(kvs).map_err(|err| err.to_string())
+    })
+}
+
+/// Export all key-values in a `format` (`csv`, `yaml`, `json`, `env` or
+/// `shell`)
+///
+/// The response is `{"ok": "<exported text>"}`.
+///
+/// # Safety
+///
+/// `request` must be a valid pointer to a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn teller_export(request: *const c_char) -> *mut c_char {
+    call(request, |request, teller| async move {
+        let format: export::Format = request
+            .format
+            .as_deref()
+            .ok_or("expected `format`")?
+            .parse()
+            .map_err(|possible| format!("unknown format, use one of: {possible}"))?;
+        let out = teller
+            .export(&format)
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(json!(out))
+    })
+}
+
+/// Redact secrets of the configuration from `text`
+///
+/// The response is `{"ok": "<redacted text>"}`.
+///
+/// # Safety
+///
+/// `request` must be a valid pointer to a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn teller_redact(request: *const c_char) -> *mut c_char {
+    call(request, |request, teller| async move {
+        let text = request.text.as_deref().ok_or("expected `text`")?;
+        let mut out = Vec::new();
+        teller
+            .redact(text.as_bytes(), &mut out, &redact::Opts::default())
+            .await
+            .map_err(|err| err.to_string())?;
+        String::from_utf8(out)
+            .map(Value::String)
+            .map_err(|err| err.to_string())
+    })
+}
+
+/// Release a response
+///
+/// # Safety
+///
+/// `response` must be a pointer returned by a teller function, or null, and
+/// not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn teller_free_string(response: *mut c_char) {
+    if !response.is_null() {
+        drop(CString::from_raw(response));
+    }
+}
+
+/// Parse the request, build teller from its configuration, run `f` and encode
+/// its result as a response. Panics are turned into errors so they never
+/// unwind across the C boundary.
+unsafe fn call<F, Fut>(request: *const c_char, f: F) -> *mut c_char
+where
+    F: FnOnce(Request, Teller) -> Fut,
+    Fut: std::future::Future<Output = Result<Value, String>>,
+{
+    let res = if request.is_null() {
+        Err("request is null".to_string())
+    } else {
+        let request = CStr::from_ptr(request);
+        panic::catch_unwind(AssertUnwindSafe(|| run(request, f)))
+            .unwrap_or_else(|_| Err("teller panicked".to_string()))
+    };
+    let response = match res {
+        Ok(value) => json!({ "ok": value }),
+        Err(err) => json!({ "error": err }),
+    };
+    // a JSON document never holds a NUL byte, they are escaped
+    CString::new(response.to_string())
+        .expect("JSON has no NUL bytes")
+        .into_raw()
+}
+
+fn run<F, Fut>(request: &CStr, f: F) -> Result<Value, String>
+where
+    F: FnOnce(Request, Teller) -> Fut,
+    Fut: std::future::Future<Output = Result<Value, String>>,
+{
+    let request: Request = serde_json::from_str(request.to_str().map_err(|err| err.to_string())?)
+        .map_err(|err| format!("invalid request: {err}"))?;
+    let config = request.config()?;
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| err.to_string())?
+        .block_on(async move {
+            let teller = Teller::from_config(&config)
+                .await
+                .map_err(|err| err.to_string())?;
+            f(request, teller).await
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r"
+providers:
+  mem:
+    kind: inmem
+    options:
+      app/dev:
+        DB_USER: admin
+        DB_PASS: s3cret
+    maps:
+      - id: dev
+        path: app/dev
+";
+
+    fn request(f: unsafe extern "C" fn(*const c_char) -> *mut c_char, request: &Value) -> Value {
+        let request = CString::new(request.to_string()).unwrap();
+        unsafe {
+            let response = f(request.as_ptr());
+            let value = serde_json::from_str(CStr::from_ptr(response).to_str().unwrap()).unwrap();
+            teller_free_string(response);
+            value
+        }
+    }
+
+    #[test]
+    fn call_over_c_abi() {
+        assert_eq!(
+            request(
+                teller_export,
+                &json!({ "config_yaml": CONFIG, "format": "env" })
+            ),
+            json!({ "ok": "DB_PASS=s3cret\nDB_USER=admin\n" })
+        );
+        assert_eq!(
+            request(
+                teller_redact,
+                &json!({ "config_yaml": CONFIG, "text": "login admin:s3cret" })
+            ),
+            json!({ "ok": "login [REDACTED]:[REDACTED]" })
+        );
+        assert_eq!(
+            request(teller_collect, &json!({ "config_yaml": CONFIG }))["ok"][0]["key"],
+            json!("DB_PASS")
+        );
+        assert_eq!(
+            request(teller_collect, &json!({})),
+            json!({ "error": "expected one of `config` or `config_yaml`" })
+        );
+        unsafe {
+            let response = teller_export(std::ptr::null());
+            assert_eq!(
+                CStr::from_ptr(response).to_str().unwrap(),
+                r#"{"error":"request is null"}"#
+            );
+            teller_free_string(response);
+        }
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::BTreeMap;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::providers::ProviderKind;
+use crate::secret::SecretValue;
 
 fn is_default<T: Default + PartialEq>(t: &T) -> bool {
     t == &T::default()
@@ -17,10 +18,17 @@ pub struct ProviderCfg {
     pub options: Option<serde_json::Value>,
     #[serde(rename = "name", skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Factory building this provider, for `custom` providers
+    #[serde(rename = "factory", skip_serializing_if = "Option::is_none")]
+    pub factory: Option<String>,
+    /// prepended to the keys of all maps, e.g. `VAULT_`, so the same key names
+    /// from several providers can coexist
+    #[serde(rename = "prefix", skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
     pub maps: Vec<PathMap>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq, PartialOrd, Ord)]
 pub enum Sensitivity {
     #[default]
     None,
@@ -50,7 +58,7 @@ pub struct MetaInfo {
 }
 #[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
 pub struct KV {
-    pub value: String,
+    pub value: SecretValue,
     pub key: String, // mapped-to key
     pub from_key: String,
     pub path: Option<PathInfo>, // always toplevel
@@ -82,7 +90,7 @@ impl KV {
     pub fn to_data(kvs: &[Self]) -> BTreeMap<String, String> {
         let mut data = BTreeMap::new();
         for kv in kvs {
-            data.insert(kv.key.clone(), kv.value.clone());
+            data.insert(kv.key.clone(), kv.value.expose().to_string());
         }
         data
     }
@@ -117,10 +125,25 @@ impl KV {
         to_key: &str,
         pm: &PathMap,
         provider: ProviderInfo,
+    ) -> Self {
+        Self::from_secret(found_val.into(), from_key, to_key, pm, provider)
+    }
+    /// Like [`KV::from_value`], for values that may be binary
+    #[must_use]
+    pub fn from_secret(
+        value: SecretValue,
+        from_key: &str,
+        to_key: &str,
+        pm: &PathMap,
+        provider: ProviderInfo,
     ) -> Self {
         Self {
-            value: found_val.to_string(),
-            key: to_key.to_string(),
+            value,
+            key: format!(
+                "{}{}",
+                pm.prefix.as_deref().unwrap_or_default(),
+                pm.key_case.apply(to_key)
+            ),
             from_key: from_key.to_string(),
             path: Some(PathInfo {
                 path: pm.path.clone(),
@@ -138,7 +161,7 @@ impl KV {
     #[must_use]
     pub fn from_literal(path: &str, key: &str, value: &str, provider: ProviderInfo) -> Self {
         Self {
-            value: value.to_string(),
+            value: value.into(),
             key: key.to_string(),
             from_key: key.to_string(),
             path: Some(PathInfo {
@@ -155,7 +178,7 @@ impl KV {
     #[must_use]
     pub fn from_kv(key: &str, value: &str) -> Self {
         Self {
-            value: value.to_string(),
+            value: value.into(),
             key: key.to_string(),
             from_key: key.to_string(),
             ..Default::default()
@@ -172,6 +195,12 @@ pub struct PathMap {
     pub path: String,
     #[serde(default, rename = "keys", skip_serializing_if = "is_default")]
     pub keys: BTreeMap<String, String>,
+    // globs of keys to keep once fetched, all of them if empty
+    #[serde(default, rename = "include", skip_serializing_if = "is_default")]
+    pub include: Vec<String>,
+    // globs of keys to leave out once fetched
+    #[serde(default, rename = "exclude", skip_serializing_if = "is_default")]
+    pub exclude: Vec<String>,
     #[serde(default, rename = "decrypt", skip_serializing_if = "is_default")]
     pub decrypt: bool,
     #[serde(default, rename = "sensitivity", skip_serializing_if = "is_default")]
@@ -189,6 +218,150 @@ pub struct PathMap {
     // ignore population if optional + we got error
     #[serde(default, rename = "optional", skip_serializing_if = "is_default")]
     pub optional: bool,
+    // per-key rules values must follow, checked on put and verify
+    #[serde(default, rename = "validate", skip_serializing_if = "is_default")]
+    pub validate: BTreeMap<String, Validation>,
+    // prepended to the keys of this map, the provider's `prefix` if not set
+    #[serde(default, rename = "prefix", skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    // how keys are cased, e.g. to turn kebab-case keys into valid env names
+    #[serde(default, rename = "key_case", skip_serializing_if = "is_default")]
+    pub key_case: KeyCase,
+    // seconds written values live before expiring, for providers with expiry (etcd)
+    #[serde(default, rename = "ttl", skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
+    // for wildcard paths (`secret/apps/*`), prefix keys with the name of the
+    // child path they come from, e.g. `billing_DB_PASS`
+    #[serde(default, rename = "child_prefix", skip_serializing_if = "is_default")]
+    pub child_prefix: bool,
+    // Vault enterprise namespace of this map, instead of the provider's
+    #[serde(default, rename = "namespace", skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    // per-key policies of values generated by `teller put --generate`
+    #[serde(default, rename = "generate", skip_serializing_if = "is_default")]
+    pub generate: BTreeMap<String, Generation>,
+}
+
+/// How the keys of a map are cased
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum KeyCase {
+    /// as found in the provider, or mapped to
+    #[default]
+    #[serde(rename = "preserve")]
+    Preserve,
+    /// `db-pass` and `dbPass` become `DB_PASS`
+    #[serde(rename = "upper_snake")]
+    UpperSnake,
+    /// `db-pass` and `dbPass` become `db_pass`
+    #[serde(rename = "lower")]
+    Lower,
+}
+
+impl KeyCase {
+    /// Apply to a key. Apart from `preserve`, characters that are not valid in
+    /// env variable names become `_`, and words of camelCase keys are split.
+    #[must_use]
+    pub fn apply(self, key: &str) -> String {
+        if self == Self::Preserve {
+            return key.to_string();
+        }
+        let mut snake = String::with_capacity(key.len());
+        let mut prev: Option<char> = None;
+        for c in key.chars() {
+            if c.is_ascii_alphanumeric() {
+                if c.is_ascii_uppercase()
+                    && prev.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit())
+                {
+                    snake.push('_');
+                }
+                snake.push(c);
+            } else {
+                snake.push('_');
+            }
+            prev = Some(c);
+        }
+        if self == Self::UpperSnake {
+            snake.to_ascii_uppercase()
+        } else {
+            snake.to_ascii_lowercase()
+        }
+    }
+}
+
+/// Rules a value must follow, all rules that are set must pass
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct Validation {
+    /// regex the value must match
+    #[serde(default, rename = "pattern", skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(
+        default,
+        rename = "min_length",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub min_length: Option<usize>,
+    /// allowed characters, as the inside of a regex character class, e.g. `A-Za-z0-9_-`
+    #[serde(default, rename = "charset", skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
+    #[serde(default, rename = "format", skip_serializing_if = "Option::is_none")]
+    pub format: Option<ValueFormat>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ValueFormat {
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "url")]
+    Url,
+    #[serde(rename = "base64")]
+    Base64,
+}
+
+/// How a random value is generated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Generation {
+    /// number of characters
+    #[serde(default = "default_generated_length", rename = "length")]
+    pub length: usize,
+    #[serde(default, rename = "charset")]
+    pub charset: Charset,
+}
+
+const fn default_generated_length() -> usize {
+    32
+}
+
+impl Default for Generation {
+    fn default() -> Self {
+        Self {
+            length: default_generated_length(),
+            charset: Charset::default(),
+        }
+    }
+}
+
+/// Characters a generated value is made of
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Charset {
+    /// `A-Za-z0-9`
+    #[default]
+    #[serde(rename = "alnum")]
+    Alnum,
+    /// `A-Za-z`
+    #[serde(rename = "alpha")]
+    Alpha,
+    /// `0-9`
+    #[serde(rename = "digits")]
+    Digits,
+    /// `0-9a-f`
+    #[serde(rename = "hex")]
+    Hex,
+    /// `A-Za-z0-9_-`, safe in URLs and file names
+    #[serde(rename = "urlsafe")]
+    UrlSafe,
+    /// printable ASCII, without space
+    #[serde(rename = "ascii")]
+    Ascii,
 }
 
 impl PathMap {
@@ -200,3 +373,22 @@ impl PathMap {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_case() {
+        for (key, upper, lower) in [
+            ("db-pass", "DB_PASS", "db_pass"),
+            ("dbPass", "DB_PASS", "db_pass"),
+            ("DB_PASS", "DB_PASS", "db_pass"),
+            ("app.v2Token", "APP_V2_TOKEN", "app_v2_token"),
+        ] {
+            assert_eq!(KeyCase::Preserve.apply(key), key);
+            assert_eq!(KeyCase::UpperSnake.apply(key), upper);
+            assert_eq!(KeyCase::Lower.apply(key), lower);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Writing values in `.env` syntax, so they read back unchanged
+//!
+//! Values that need it are double quoted, with quotes, backslashes, `$` and
+//! newlines escaped. Newlines are written as `\n`, keeping multi-line values
+//! such as PEM certificates on a single line.
+
+use std::borrow::Cow;
+
+/// Quote a value for a `.env` file, if it needs quoting
+#[must_use]
+pub fn quote(value: &str) -> Cow<'_, str> {
+    if !value
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | '$' | '#'))
+    {
+        return Cow::Borrowed(value);
+    }
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_values() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("two words"), "\"two words\"");
+        assert_eq!(
+            quote("-----BEGIN CERT-----\nMII=\n-----END CERT-----"),
+            "\"-----BEGIN CERT-----\\nMII=\\n-----END CERT-----\""
+        );
+        assert_eq!(quote(r#"{"a": "$HOME\d"}"#), r#""{\"a\": \"\$HOME\\d\"}""#);
+    }
+}
@@ -1,6 +1,11 @@
 pub mod config;
+pub mod envfile;
 pub mod providers;
 pub mod registry;
+pub mod secret;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+mod traced;
 
 use async_trait::async_trait;
 
@@ -27,6 +32,28 @@ pub trait Provider {
     ///
     /// ...
     async fn del(&self, pm: &PathMap) -> Result<()>;
+    /// Keep the values of a mapping with a `ttl` from expiring. Called
+    /// periodically by long-running modes, a no-op for providers without expiry.
+    ///
+    /// # Errors
+    ///
+    /// ...
+    async fn keep_alive(&self, _pm: &PathMap) -> Result<()> {
+        Ok(())
+    }
+    /// Wait until the values of a mapping change, for pushing changes instead
+    /// of polling. Providers without change notifications return
+    /// [`Error::Unsupported`].
+    ///
+    /// # Errors
+    ///
+    /// ...
+    async fn watch(&self, _pm: &PathMap) -> Result<()> {
+        Err(Error::Unsupported(format!(
+            "{} cannot watch for changes",
+            self.kind().kind
+        )))
+    }
 }
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -54,6 +81,11 @@ pub enum Error {
     #[error("NOT FOUND {path}: {msg}")]
     NotFound { path: String, msg: String },
 
+    /// The provider rejected the credentials, or they lack access to the path.
+    /// Reported by Hashicorp Vault and Consul.
+    #[error("AUTH {path}: {msg}")]
+    Auth { path: String, msg: String },
+
     #[error("GET {path}: {msg}")]
     GetError { path: String, msg: String },
 
@@ -68,6 +100,9 @@ pub enum Error {
 
     #[error("{0}")]
     CreateProviderError(String),
+
+    #[error("{0}")]
+    Unsupported(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
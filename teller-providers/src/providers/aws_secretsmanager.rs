@@ -23,6 +23,7 @@ use aws_config::{self, BehaviorVersion};
 use aws_sdk_secretsmanager as secretsmanager;
 use secretsmanager::config::{Credentials, Region};
 use secretsmanager::operation::get_secret_value::GetSecretValueError;
+use secretsmanager::primitives::Blob;
 use secretsmanager::{error::SdkError, operation::delete_secret::DeleteSecretError};
 use serde_derive::{Deserialize, Serialize};
 
@@ -30,6 +31,7 @@ use super::ProviderKind;
 use crate::config::ProviderInfo;
 use crate::{
     config::{PathMap, KV},
+    secret::SecretValue,
     Error, Provider, Result,
 };
 
@@ -102,6 +104,8 @@ pub struct AWSSecretsManagerOptions {
     pub region: Option<String>,
     pub access_key_id: Option<String>,
     pub secret_access_key: Option<String>,
+    /// Session token of temporary credentials, e.g. from `teller login --sso`
+    pub session_token: Option<String>,
     pub endpoint_url: Option<String>,
 }
 
@@ -127,8 +131,13 @@ impl AWSSecretsManager {
         let client = if let Some(opts) = opts {
             let mut config = aws_config::defaults(BehaviorVersion::v2023_11_09());
             if let (Some(key), Some(secret)) = (opts.access_key_id, opts.secret_access_key) {
-                config = config
-                    .credentials_provider(Credentials::new(key, secret, None, None, "teller"));
+                config = config.credentials_provider(Credentials::new(
+                    key,
+                    secret,
+                    opts.session_token,
+                    None,
+                    "teller",
+                ));
             }
             if let Some(endpoint_url) = opts.endpoint_url {
                 config = config.endpoint_url(endpoint_url);
@@ -150,35 +159,43 @@ impl AWSSecretsManager {
     }
 }
 
+/// A secret's payload: key-values as a JSON string, or a single binary value
+enum Payload {
+    Data(BTreeMap<String, String>),
+    Binary(Vec<u8>),
+}
+
 async fn get_data(
     mode: &Mode,
     client: &secretsmanager::Client,
     pm: &PathMap,
-) -> Result<Option<BTreeMap<String, String>>> {
+) -> Result<Option<Payload>> {
     let resp = client
         .get_secret_value()
         .secret_id(&pm.path)
         .send()
         .await
-        .map_or_else(
-            |e| handle_get_err(mode, e, pm),
-            |res| Ok(res.secret_string().map(std::string::ToString::to_string)),
-        )?;
+        .map_or_else(|e| handle_get_err(mode, e, pm), |res| Ok(Some(res)))?;
 
-    if let Some(raw_string) = resp {
-        Ok(Some(serde_json::from_str::<BTreeMap<String, String>>(
-            &raw_string,
-        )?))
+    let Some(res) = resp else {
+        return Ok(None);
+    };
+    if let Some(blob) = res.secret_binary() {
+        Ok(Some(Payload::Binary(blob.as_ref().to_vec())))
+    } else if let Some(raw_string) = res.secret_string() {
+        Ok(Some(Payload::Data(serde_json::from_str::<
+            BTreeMap<String, String>,
+        >(raw_string)?)))
     } else {
         Ok(None)
     }
 }
 
-async fn put_data(
-    client: &secretsmanager::Client,
-    pm: &PathMap,
-    data: &BTreeMap<String, String>,
-) -> Result<()> {
+async fn put_data(client: &secretsmanager::Client, pm: &PathMap, payload: Payload) -> Result<()> {
+    let (secret_string, secret_binary) = match payload {
+        Payload::Data(data) => (Some(serde_json::to_string(&data)?), None),
+        Payload::Binary(bytes) => (None, Some(Blob::new(bytes))),
+    };
     if client
         .get_secret_value()
         .secret_id(&pm.path)
@@ -189,7 +206,8 @@ async fn put_data(
         client
             .put_secret_value()
             .set_secret_id(Some(pm.path.clone()))
-            .secret_string(serde_json::to_string(&data)?)
+            .set_secret_string(secret_string)
+            .set_secret_binary(secret_binary)
             .send()
             .await
             .map_err(|e| Error::PutError {
@@ -200,7 +218,8 @@ async fn put_data(
         client
             .create_secret()
             .set_name(Some(pm.path.clone()))
-            .secret_string(serde_json::to_string(&data)?)
+            .set_secret_string(secret_string)
+            .set_secret_binary(secret_binary)
             .send()
             .await
             .map_err(|e| Error::PutError {
@@ -222,38 +241,89 @@ impl Provider for AWSSecretsManager {
     }
 
     async fn get(&self, pm: &PathMap) -> Result<Vec<KV>> {
-        get_data(&Mode::Get, &self.client, pm).await?.map_or_else(
-            || Ok(vec![]),
-            |data| Ok(KV::from_data(&data, pm, &self.kind())),
-        )
+        match get_data(&Mode::Get, &self.client, pm).await? {
+            Some(Payload::Data(data)) => Ok(KV::from_data(&data, pm, &self.kind())),
+            // a binary secret is a single value, keyed by the secret's name
+            Some(Payload::Binary(bytes)) => {
+                let name = pm.path.rsplit('/').next().unwrap_or(&pm.path);
+                let to_key = if pm.keys.is_empty() {
+                    Some(name)
+                } else {
+                    pm.keys.get(name).map(String::as_str)
+                };
+                Ok(to_key
+                    .map(|to_key| {
+                        KV::from_secret(
+                            SecretValue::from_bytes(bytes),
+                            name,
+                            to_key,
+                            pm,
+                            self.kind(),
+                        )
+                    })
+                    .into_iter()
+                    .collect())
+            }
+            None => Ok(vec![]),
+        }
     }
 
     async fn put(&self, pm: &PathMap, kvs: &[KV]) -> Result<()> {
-        let mut data = get_data(&Mode::Put, &self.client, pm)
-            .await?
-            .unwrap_or_default();
+        if let [kv] = kvs {
+            if kv.value.is_binary() {
+                return put_data(
+                    &self.client,
+                    pm,
+                    Payload::Binary(kv.value.as_bytes().to_vec()),
+                )
+                .await;
+            }
+        }
+        let mut data = match get_data(&Mode::Put, &self.client, pm).await? {
+            Some(Payload::Data(data)) => data,
+            _ => BTreeMap::new(),
+        };
         for kv in kvs {
-            data.insert(kv.key.clone(), kv.value.clone());
+            if kv.value.is_binary() {
+                return Err(Error::PutError {
+                    msg: format!(
+                        "'{}' is binary, a secret holds a single binary value and it must be put \
+                         alone",
+                        kv.key
+                    ),
+                    path: pm.path.clone(),
+                });
+            }
+            data.insert(kv.key.clone(), kv.value.expose().to_string());
         }
-        put_data(&self.client, pm, &data).await
+        put_data(&self.client, pm, Payload::Data(data)).await
     }
 
     async fn del(&self, pm: &PathMap) -> Result<()> {
-        if pm.keys.is_empty() {
-            self.client
-                .delete_secret()
-                .secret_id(&pm.path)
-                .send()
-                .await
-                .map_or_else(|e| handle_del_err(e, pm), |_| Ok(()))?;
+        let payload = if pm.keys.is_empty() {
+            None
         } else {
-            let mut data = get_data(&Mode::Del, &self.client, pm)
-                .await?
-                .unwrap_or_default();
-            for k in pm.keys.keys() {
-                data.remove(k);
+            get_data(&Mode::Del, &self.client, pm).await?
+        };
+        match payload {
+            Some(Payload::Data(mut data)) => {
+                for k in pm.keys.keys() {
+                    data.remove(k);
+                }
+                put_data(&self.client, pm, Payload::Data(data)).await?;
+            }
+            None if !pm.keys.is_empty() => {
+                put_data(&self.client, pm, Payload::Data(BTreeMap::new())).await?;
+            }
+            // deleting all keys, or the single value of a binary secret
+            _ => {
+                self.client
+                    .delete_secret()
+                    .secret_id(&pm.path)
+                    .send()
+                    .await
+                    .map_or_else(|e| handle_del_err(e, pm), |_| Ok(()))?;
             }
-            put_data(&self.client, pm, &data).await?;
         }
         Ok(())
     }
@@ -268,7 +338,7 @@ mod tests {
     use dockertest_server::servers::cloud::LocalStackServerConfig;
     use dockertest_server::Test;
 
-    use crate::{providers::test_utils, Provider};
+    use crate::{testkit, Provider};
 
     #[test]
     #[cfg(not(windows))]
@@ -312,7 +382,7 @@ mod tests {
                 .unwrap(),
             ) as Box<dyn Provider + Send + Sync>;
 
-            test_utils::ProviderTest::new(p).run().await;
+            testkit::ProviderTest::new(p).run().await;
         });
     }
 }
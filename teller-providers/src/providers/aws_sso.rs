@@ -0,0 +1,146 @@
+//! AWS IAM Identity Center (SSO) sign in
+//!
+//! Not a provider: the device authorization flow `teller login --sso` signs in
+//! with, for short-lived credentials of a role, used by the `ssm` and
+//! `aws_secretsmanager` providers through their `access_key_id`,
+//! `secret_access_key` and `session_token` options.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aws_config::{self, BehaviorVersion, Region};
+use aws_sdk_sso as sso;
+use aws_sdk_ssooidc as ssooidc;
+use ssooidc::operation::create_token::CreateTokenError;
+
+use crate::{Error, Result};
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Where to sign in, and the role to get credentials of
+#[derive(Debug, Clone)]
+pub struct SsoOptions {
+    /// The AWS access portal URL, e.g. `https://my-org.awsapps.com/start`
+    pub start_url: String,
+    /// Region of the IAM Identity Center instance
+    pub region: String,
+    pub account_id: String,
+    pub role_name: String,
+}
+
+/// What the user opens to approve the sign in
+#[derive(Debug, Clone)]
+pub struct DeviceAuthorization {
+    pub verification_uri: String,
+    pub user_code: String,
+}
+
+/// Temporary credentials of the role
+#[derive(Debug, Clone)]
+pub struct RoleCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: String,
+    /// Unix time, in seconds
+    pub expires_at: u64,
+}
+
+fn sso_err(err: impl std::fmt::Display) -> Error {
+    Error::Message(format!("aws sso login: {err}"))
+}
+
+/// Sign in with the device authorization flow. `prompt` shows the user where
+/// to approve the sign in, which is then waited for.
+///
+/// # Errors
+///
+/// This function will return an error if the sign in is denied or expires,
+/// or the role's credentials cannot be fetched
+pub async fn login(
+    opts: &SsoOptions,
+    prompt: impl FnOnce(&DeviceAuthorization) + Send,
+) -> Result<RoleCredentials> {
+    let config = aws_config::defaults(BehaviorVersion::v2023_11_09())
+        .region(Region::new(opts.region.clone()))
+        .no_credentials()
+        .load()
+        .await;
+
+    let oidc = ssooidc::Client::new(&config);
+    let registration = oidc
+        .register_client()
+        .client_name("teller")
+        .client_type("public")
+        .send()
+        .await
+        .map_err(|e| sso_err(e.into_service_error()))?;
+    let client_id = registration.client_id().unwrap_or_default();
+    let client_secret = registration.client_secret().unwrap_or_default();
+
+    let device = oidc
+        .start_device_authorization()
+        .client_id(client_id)
+        .client_secret(client_secret)
+        .start_url(&opts.start_url)
+        .send()
+        .await
+        .map_err(|e| sso_err(e.into_service_error()))?;
+    prompt(&DeviceAuthorization {
+        verification_uri: device
+            .verification_uri_complete()
+            .or(device.verification_uri())
+            .unwrap_or_default()
+            .to_string(),
+        user_code: device.user_code().unwrap_or_default().to_string(),
+    });
+
+    let mut interval = Duration::from_secs(u64::try_from(device.interval()).unwrap_or(0).max(1));
+    let expires_in = Duration::from_secs(u64::try_from(device.expires_in()).unwrap_or(0));
+    let mut waited = Duration::ZERO;
+    let token = loop {
+        if waited >= expires_in {
+            return Err(sso_err("sign in was not approved in time"));
+        }
+        tokio::time::sleep(interval).await;
+        waited += interval;
+        match oidc
+            .create_token()
+            .client_id(client_id)
+            .client_secret(client_secret)
+            .grant_type(DEVICE_CODE_GRANT)
+            .device_code(device.device_code().unwrap_or_default())
+            .send()
+            .await
+            .map_err(ssooidc::error::SdkError::into_service_error)
+        {
+            Ok(token) => break token,
+            Err(CreateTokenError::AuthorizationPendingException(_)) => {}
+            Err(CreateTokenError::SlowDownException(_)) => interval += Duration::from_secs(5),
+            Err(err) => return Err(sso_err(err)),
+        }
+    };
+
+    let credentials = sso::Client::new(&config)
+        .get_role_credentials()
+        .account_id(&opts.account_id)
+        .role_name(&opts.role_name)
+        .access_token(token.access_token().unwrap_or_default())
+        .send()
+        .await
+        .map_err(|e| sso_err(e.into_service_error()))?;
+    let credentials = credentials
+        .role_credentials()
+        .ok_or_else(|| sso_err("no credentials returned for the role"))?;
+    let expires_at = u64::try_from(credentials.expiration() / 1000).unwrap_or_else(|_| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs())
+    });
+    Ok(RoleCredentials {
+        access_key_id: credentials.access_key_id().unwrap_or_default().to_string(),
+        secret_access_key: credentials
+            .secret_access_key()
+            .unwrap_or_default()
+            .to_string(),
+        session_token: credentials.session_token().unwrap_or_default().to_string(),
+        expires_at,
+    })
+}
@@ -17,11 +17,7 @@
 #![allow(clippy::borrowed_box)]
 use std::fs::File;
 use std::io::prelude::*;
-use std::{
-    collections::{BTreeMap, HashMap},
-    io,
-    path::Path,
-};
+use std::{collections::BTreeMap, io, path::Path};
 
 use async_trait::async_trait;
 use dotenvy::{self};
@@ -32,7 +28,7 @@ use super::ProviderKind;
 use crate::config::ProviderInfo;
 use crate::{
     config::{PathMap, KV},
-    Error, Provider, Result,
+    envfile, Error, Provider, Result,
 };
 
 #[derive(PartialEq)]
@@ -100,21 +96,7 @@ fn load(path: &Path, mode: &Mode) -> Result<BTreeMap<String, String>> {
 fn save(path: &Path, data: &BTreeMap<String, String>) -> Result<String> {
     let mut out = String::new();
     for (k, v) in data {
-        let maybe_json: serde_json::Result<HashMap<String, serde_json::Value>> =
-            serde_json::from_str(v);
-
-        let json_value = if maybe_json.is_ok() {
-            serde_json::to_string(&v).map(Some).unwrap_or_default()
-        } else {
-            None
-        };
-
-        let value = json_value.unwrap_or_else(|| v.to_string());
-        if value.chars().any(char::is_whitespace) && !value.starts_with(['"', '\'']) {
-            out.push_str(&format!("{k}=\"{value}\"\n"));
-        } else {
-            out.push_str(&format!("{k}={value}\n"));
-        }
+        out.push_str(&format!("{k}={}\n", envfile::quote(v)));
     }
 
     fs::write(path, &out)?;
@@ -141,7 +123,7 @@ impl Provider for Dotenv {
             pm,
             |data| {
                 for kv in kvs {
-                    data.insert(kv.key.to_string(), kv.value.to_string());
+                    data.insert(kv.key.to_string(), kv.value.expose().to_string());
                 }
             },
             &Mode::Put,
@@ -202,7 +184,7 @@ mod tests {
     use tokio::test;
 
     use super::*;
-    use crate::providers::test_utils;
+    use crate::testkit;
 
     #[test]
     async fn sanity_test() {
@@ -214,7 +196,7 @@ mod tests {
             super::Dotenv::new("dotenv", Some(serde_json::from_value(opts).unwrap())).unwrap(),
         ) as Box<dyn Provider + Send + Sync>;
 
-        test_utils::ProviderTest::new(p)
+        testkit::ProviderTest::new(p)
             .with_root_prefix("tmp/dotenv/")
             .run()
             .await;
@@ -13,15 +13,36 @@
 //!
 //! See [`EtcdOptions`] for more.
 //!
+//! ## Expiring values
+//!
+//! Values put to a map with a `ttl` (in seconds) are attached to a lease and
+//! expire with it. `teller agent` and `teller serve` keep such leases alive
+//! while they run.
+//!
+//! Changes under a map's path can be watched, see [`Provider::watch`].
+//!
+//! ```yaml
+//! providers:
+//!  etcd1:
+//!    kind: etcd
+//!    maps:
+//!      - id: dynamic
+//!        path: /config/dynamic
+//!        ttl: 60
+//! ```
+//!
+
+use std::collections::BTreeSet;
 
 use async_trait::async_trait;
-use etcd_client::{Client, ConnectOptions, DeleteOptions, GetOptions};
+use etcd_client::{Client, ConnectOptions, DeleteOptions, GetOptions, PutOptions, WatchOptions};
 use serde_derive::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 use super::ProviderKind;
 use crate::{
     config::{PathMap, ProviderInfo, KV},
+    secret::SecretValue,
     Error, Provider, Result,
 };
 
@@ -143,22 +164,34 @@ impl Provider for Etcd {
                 .strip_prefix(&pm.path)
                 .map_or(key, |s| s.trim_start_matches('/'));
 
-            let val = kv_pair.value_str().map_err(|err| to_err(pm, err))?;
+            let val = SecretValue::from_bytes(kv_pair.value().to_vec());
 
-            results.push(KV::from_value(val, key, key, pm, self.kind()));
+            results.push(KV::from_secret(val, key, key, pm, self.kind()));
         }
 
         Ok(results)
     }
 
     async fn put(&self, pm: &PathMap, kvs: &[KV]) -> Result<()> {
+        // one lease for all values of the map, so they expire together
+        let lease = if let Some(ttl) = pm.ttl {
+            let mut lease_client = self.client.lock().await.lease_client();
+            let lease = lease_client
+                .grant(i64::try_from(ttl).unwrap_or(i64::MAX), None)
+                .await
+                .map_err(|e| to_err(pm, e))?;
+            Some(lease.id())
+        } else {
+            None
+        };
+
         let mut client = self.client.lock().await.kv_client();
         for kv in kvs {
             client
                 .put(
                     format!("{}/{}", pm.path, kv.key).as_str(),
                     kv.value.as_bytes().to_vec(),
-                    None,
+                    lease.map(|id| PutOptions::new().with_lease(id)),
                 )
                 .await
                 .map_err(|e| to_err(pm, e))?;
@@ -190,13 +223,62 @@ impl Provider for Etcd {
 
         Ok(())
     }
+
+    async fn keep_alive(&self, pm: &PathMap) -> Result<()> {
+        if pm.ttl.is_none() {
+            return Ok(());
+        }
+
+        let mut client = self.client.lock().await.kv_client();
+        let leases = client
+            .get(
+                pm.path.as_str(),
+                Some(GetOptions::new().with_prefix().with_keys_only()),
+            )
+            .await
+            .map_err(|err| to_err(pm, err))?
+            .kvs()
+            .iter()
+            .map(etcd_client::KeyValue::lease)
+            .filter(|lease| *lease != 0)
+            .collect::<BTreeSet<_>>();
+        drop(client);
+
+        let mut lease_client = self.client.lock().await.lease_client();
+        for lease in leases {
+            let (mut keeper, mut responses) = lease_client
+                .keep_alive(lease)
+                .await
+                .map_err(|err| to_err(pm, err))?;
+            keeper.keep_alive().await.map_err(|err| to_err(pm, err))?;
+            responses.message().await.map_err(|err| to_err(pm, err))?;
+        }
+
+        Ok(())
+    }
+
+    async fn watch(&self, pm: &PathMap) -> Result<()> {
+        let mut watch_client = self.client.lock().await.watch_client();
+        let (_watcher, mut stream) = watch_client
+            .watch(pm.path.as_str(), Some(WatchOptions::new().with_prefix()))
+            .await
+            .map_err(|err| to_err(pm, err))?;
+
+        // the first response confirms the watch, without events
+        while let Some(res) = stream.message().await.map_err(|err| to_err(pm, err))? {
+            if !res.events().is_empty() {
+                return Ok(());
+            }
+        }
+        Err(Error::Message(format!("watch on {} was closed", pm.path)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use crate::providers::test_utils;
+    use crate::testkit;
 
     const PORT: u32 = 2379;
 
@@ -257,7 +339,7 @@ mod tests {
                 .unwrap(),
             ) as Box<dyn Provider + Send + Sync>;
 
-            test_utils::ProviderTest::new(p).run().await;
+            testkit::ProviderTest::new(p).run().await;
         });
     }
 }
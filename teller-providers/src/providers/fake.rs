@@ -0,0 +1,316 @@
+//! Fake Store, a test double with fault injection
+//!
+//! An in-memory store like `inmem`, whose calls can be slowed down, fail at
+//! random or for their first attempts, and whose paths can read as not found.
+//! Integration-test a configuration, and how its callers cope with a flaky
+//! backend, without running one.
+//!
+//! ## Example configuration
+//!
+//! ```yaml
+//! providers:
+//!  flaky:
+//!    kind: fake
+//!    options:
+//!      data:
+//!        prod/app:
+//!          DB_PASS: s3cret
+//!      latency_ms: 200
+//!      jitter_ms: 50
+//!      failure_rate: 0.1
+//!      fail_first: 2
+//!      not_found: [prod/gone]
+//!      seed: 42
+//! ```
+//! ## Options
+//!
+//! See [`FakeOptions`]
+//!
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{inmem::Inmem, ProviderKind};
+use crate::{
+    config::{PathMap, ProviderInfo, KV},
+    Error, Provider, Result,
+};
+
+/// What injected failures fail with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// the operation's error, e.g. a `GET` error for reads
+    #[default]
+    Error,
+    /// rejected credentials
+    Auth,
+    /// a missing path
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FakeOptions {
+    /// initial data, by path, as for `inmem`
+    #[serde(default)]
+    pub data: BTreeMap<String, BTreeMap<String, String>>,
+    /// delay of every call, in milliseconds
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// random delay added to `latency_ms`, up to this many milliseconds
+    #[serde(default)]
+    pub jitter_ms: u64,
+    /// share of calls failing at random, from 0 to 1
+    #[serde(default)]
+    pub failure_rate: f64,
+    /// the first calls fail, whatever `failure_rate` is
+    #[serde(default)]
+    pub fail_first: u32,
+    /// what failing calls fail with
+    #[serde(default)]
+    pub fail_with: Fault,
+    /// paths read as not found, whatever their data
+    #[serde(default)]
+    pub not_found: Vec<String>,
+    /// seed of random failures and jitter, for repeatable runs. Seeded from
+    /// the clock if none given.
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Get,
+    Put,
+    Del,
+}
+
+pub struct Fake {
+    inner: Inmem,
+    opts: FakeOptions,
+    calls: AtomicU32,
+    rng: Mutex<u64>,
+}
+
+impl Fake {
+    /// Create a fake provider
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `failure_rate` is not between 0
+    /// and 1
+    pub fn new(name: &str, opts: Option<FakeOptions>) -> Result<Self> {
+        let mut opts = opts.unwrap_or_default();
+        if !(0.0..=1.0).contains(&opts.failure_rate) {
+            return Err(Error::Message(format!(
+                "fake provider {name}: failure_rate must be between 0 and 1, got {}",
+                opts.failure_rate
+            )));
+        }
+        let seed = opts.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| {
+                    elapsed.as_secs() ^ u64::from(elapsed.subsec_nanos())
+                })
+        });
+        let data = std::mem::take(&mut opts.data);
+        Ok(Self {
+            inner: Inmem::new(name, Some(serde_json::to_value(data)?))?,
+            opts,
+            calls: AtomicU32::new(0),
+            rng: Mutex::new(seed),
+        })
+    }
+
+    /// How many calls were made, failed ones included
+    pub fn calls(&self) -> u32 {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// splitmix64, good enough for faults and light on dependencies
+    fn random(&self) -> u64 {
+        let mut z = {
+            let mut state = self.rng.lock().expect("fake rng lock");
+            *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            *state
+        };
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A random number in `[0, 1)`
+    #[allow(clippy::cast_precision_loss)]
+    fn uniform(&self) -> f64 {
+        (self.random() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Delay the call, then fail it if it is one of the first or at random
+    async fn inject(&self, operation: Operation, pm: &PathMap) -> Result<()> {
+        let jitter = if self.opts.jitter_ms > 0 {
+            self.random() % (self.opts.jitter_ms + 1)
+        } else {
+            0
+        };
+        let delay = self.opts.latency_ms + jitter;
+        if delay > 0 {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        let failing = call < self.opts.fail_first
+            || (self.opts.failure_rate > 0.0 && self.uniform() < self.opts.failure_rate);
+        if failing {
+            return Err(self.fault(operation, pm));
+        }
+        Ok(())
+    }
+
+    fn fault(&self, operation: Operation, pm: &PathMap) -> Error {
+        let path = pm.path.clone();
+        let msg = "injected failure".to_string();
+        match (self.opts.fail_with, operation) {
+            (Fault::Error, Operation::Get) => Error::GetError { path, msg },
+            (Fault::Error, Operation::Put) => Error::PutError { path, msg },
+            (Fault::Error, Operation::Del) => Error::DeleteError { path, msg },
+            (Fault::Auth, _) => Error::Auth { path, msg },
+            (Fault::NotFound, _) => Error::NotFound { path, msg },
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for Fake {
+    fn kind(&self) -> ProviderInfo {
+        ProviderInfo {
+            kind: ProviderKind::Fake,
+            name: self.inner.kind().name,
+        }
+    }
+
+    async fn get(&self, pm: &PathMap) -> Result<Vec<KV>> {
+        self.inject(Operation::Get, pm).await?;
+        if self.opts.not_found.contains(&pm.path) {
+            return Err(Error::NotFound {
+                path: pm.path.clone(),
+                msg: "not found".to_string(),
+            });
+        }
+        let kind = self.kind();
+        Ok(self
+            .inner
+            .get(pm)
+            .await?
+            .into_iter()
+            .map(|mut kv| {
+                kv.provider = Some(kind.clone());
+                kv
+            })
+            .collect())
+    }
+
+    async fn put(&self, pm: &PathMap, kvs: &[KV]) -> Result<()> {
+        self.inject(Operation::Put, pm).await?;
+        self.inner.put(pm, kvs).await
+    }
+
+    async fn del(&self, pm: &PathMap) -> Result<()> {
+        self.inject(Operation::Del, pm).await?;
+        self.inner.del(pm).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+
+    use super::*;
+    use crate::testkit;
+
+    #[test]
+    async fn sanity_test() {
+        let p = Box::new(Fake::new("test", None).unwrap()) as Box<dyn Provider + Send + Sync>;
+
+        testkit::ProviderTest::new(p).run().await;
+    }
+
+    #[test]
+    async fn injected_faults() {
+        let opts: FakeOptions = serde_yaml::from_str(
+            r"
+data:
+  prod/app:
+    DB_PASS: s3cret
+  prod/gone:
+    DB_PASS: old
+fail_first: 2
+fail_with: auth
+not_found: [prod/gone]
+",
+        )
+        .unwrap();
+        let fake = Fake::new("flaky", Some(opts)).unwrap();
+        let app = PathMap::from_path("prod/app");
+        assert!(matches!(fake.get(&app).await, Err(Error::Auth { .. })));
+        assert!(matches!(fake.put(&app, &[]).await, Err(Error::Auth { .. })));
+        let kvs = fake.get(&app).await.unwrap();
+        assert_eq!(kvs[0].value.expose(), "s3cret");
+        assert_eq!(kvs[0].provider.as_ref().unwrap().kind, ProviderKind::Fake);
+        assert!(matches!(
+            fake.get(&PathMap::from_path("prod/gone")).await,
+            Err(Error::NotFound { .. })
+        ));
+        assert_eq!(fake.calls(), 4);
+
+        let always = Fake::new(
+            "down",
+            Some(FakeOptions {
+                failure_rate: 1.0,
+                ..FakeOptions::default()
+            }),
+        )
+        .unwrap();
+        assert!(matches!(
+            always.get(&app).await,
+            Err(Error::GetError { .. })
+        ));
+
+        // the same seed fails the same calls
+        let outcomes = || async {
+            let fake = Fake::new(
+                "seeded",
+                Some(FakeOptions {
+                    failure_rate: 0.5,
+                    seed: Some(7),
+                    ..FakeOptions::default()
+                }),
+            )
+            .unwrap();
+            let mut outcomes = vec![];
+            for _ in 0..16 {
+                outcomes.push(fake.put(&app, &[]).await.is_ok());
+            }
+            outcomes
+        };
+        let first = outcomes().await;
+        assert_eq!(first, outcomes().await);
+        assert!(first.contains(&true) && first.contains(&false));
+
+        assert!(Fake::new(
+            "bad",
+            Some(FakeOptions {
+                failure_rate: 2.0,
+                ..FakeOptions::default()
+            })
+        )
+        .is_err());
+    }
+}
@@ -11,14 +11,42 @@
 //! ```
 //! ## Options
 //!
+//! See [`GoogleSecretManagerOptions`] for more.
+//!
 //! Uses default GSM options location strategy (by order):
 //!
 //! * Use `GOOGLE_APPLICATION_CREDENTIALS`
 //! * Try `$HOME/.config/gcloud/application_default_credentials.json`
 //!
-//! If you need specific configuration options for this provider, please request via opening an issue.
+//! ## Paths
+//!
+//! Paths are project resource names, e.g. `projects/123`, with keys being the
+//! secrets of the project. With the `project` option set, a path can be a
+//! simple name instead: `app/dev` stands for secrets named `app_dev_<key>` in
+//! that project (secret ids cannot contain `/`).
+//!
+//! ## Labels
+//!
+//! `labels` and `annotations` are set on secrets created by `put`, and
+//! `filter` (e.g. `labels.managed-by=teller`) narrows listing, so secrets
+//! teller manages in a shared project are told apart from others without
+//! accessing each of them:
+//!
+//! ```yaml
+//! providers:
+//!  gsm1:
+//!    kind: google_secretmanager
+//!    options:
+//!      project: my-project
+//!      labels:
+//!        managed-by: teller
+//!      filter: labels.managed-by=teller
+//! ```
 //!
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
+use futures::{stream, StreamExt, TryStreamExt};
 use google_secretmanager1::{
     api::{AddSecretVersionRequest, Automatic, Replication, Secret, SecretPayload},
     hyper::{self, client::HttpConnector},
@@ -30,22 +58,32 @@ use google_secretmanager1::{
     },
     SecretManager,
 };
+use serde_derive::{Deserialize, Serialize};
 
 use super::ProviderKind;
 use crate::{
     config::{PathMap, ProviderInfo, KV},
+    secret::SecretValue,
     Error, Provider, Result,
 };
 
 #[async_trait]
 pub trait GSM {
     fn get_hub(&self) -> Option<&SecretManager<HttpsConnector<HttpConnector>>>;
-    async fn list(&self, name: &str) -> Result<Vec<(String, String)>>;
-    async fn get(&self, name: &str) -> Result<Option<String>>;
-    async fn put(&self, name: &str, value: &str) -> Result<()>;
+    /// List secrets under `name`, only those matching `filter` if given
+    async fn list(&self, name: &str, filter: Option<&str>) -> Result<Vec<(String, Vec<u8>)>>;
+    async fn get(&self, name: &str) -> Result<Option<Vec<u8>>>;
+    /// Add a value, creating the secret with the labels and annotations of
+    /// `opts` if missing
+    async fn put(&self, name: &str, value: &[u8], opts: &GoogleSecretManagerOptions) -> Result<()>;
     async fn del(&self, name: &str) -> Result<()>;
 }
 
+/// Secrets requested per page when listing, the API maximum
+const LIST_PAGE_SIZE: i32 = 25000;
+/// Secret versions accessed at once when listing
+const LIST_CONCURRENCY: usize = 16;
+
 pub struct GSMClient {
     hub: SecretManager<HttpsConnector<HttpConnector>>,
 }
@@ -79,36 +117,53 @@ impl GSM for GSMClient {
         Some(&self.hub)
     }
 
-    async fn list(&self, name: &str) -> Result<Vec<(String, String)>> {
+    async fn list(&self, name: &str, filter: Option<&str>) -> Result<Vec<(String, Vec<u8>)>> {
         let hub = self.get_hub().expect("hub");
 
-        let (_, secret) = hub
-            .projects()
-            .secrets_list(name)
-            .doit()
-            .await
-            .map_err(|e| Error::ListError {
+        let mut names = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut call = hub.projects().secrets_list(name).page_size(LIST_PAGE_SIZE);
+            if let Some(filter) = filter {
+                call = call.filter(filter);
+            }
+            if let Some(token) = &page_token {
+                call = call.page_token(token);
+            }
+            let (_, page) = call.doit().await.map_err(|e| Error::ListError {
                 path: name.to_string(),
                 msg: e.to_string(),
             })?;
 
-        let mut out = Vec::new();
-        if let Some(secrets) = secret.secrets {
-            for secret in &secrets {
-                let secret_name = secret
-                    .name
-                    .as_ref()
-                    .expect("secretmanager API should output a secret resource name");
-
-                if let Some(value) = self.get(secret_name).await? {
-                    out.push((secret_name.clone(), value));
-                }
+            for secret in page.secrets.unwrap_or_default() {
+                names.push(
+                    secret
+                        .name
+                        .expect("secretmanager API should output a secret resource name"),
+                );
+            }
+
+            page_token = page.next_page_token.filter(|token| !token.is_empty());
+            if page_token.is_none() {
+                break;
             }
         }
-        Ok(out)
+
+        // access latest versions a few at a time, keeping the listing order
+        let values = stream::iter(&names)
+            .map(|secret_name| self.get(secret_name))
+            .buffered(LIST_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(names
+            .into_iter()
+            .zip(values)
+            .filter_map(|(secret_name, value)| value.map(|value| (secret_name, value)))
+            .collect())
     }
 
-    async fn get(&self, name: &str) -> Result<Option<String>> {
+    async fn get(&self, name: &str) -> Result<Option<Vec<u8>>> {
         let hub = self.get_hub().expect("hub");
         let resource = if name.contains("/versions") {
             name.to_string()
@@ -128,15 +183,13 @@ impl GSM for GSMClient {
                 .payload
                 .ok_or_else(|| Error::Message(format!("no secret payload found in {resource}")))?;
 
-            Ok(payload
-                .data
-                .map(|d| String::from_utf8_lossy(&d).to_string()))
+            Ok(payload.data)
         } else {
             Ok(None)
         }
     }
 
-    async fn put(&self, name: &str, value: &str) -> Result<()> {
+    async fn put(&self, name: &str, value: &[u8], opts: &GoogleSecretManagerOptions) -> Result<()> {
         let hub = self.get_hub().expect("hub");
 
         let res = hub.projects().secrets_get(name).doit().await;
@@ -157,6 +210,10 @@ impl GSM for GSMClient {
                                     automatic: Some(Automatic::default()),
                                     user_managed: None,
                                 }),
+                                labels: (!opts.labels.is_empty())
+                                    .then(|| opts.labels.clone().into_iter().collect()),
+                                annotations: (!opts.annotations.is_empty())
+                                    .then(|| opts.annotations.clone().into_iter().collect()),
                                 ..Secret::default()
                             },
                             project,
@@ -177,8 +234,8 @@ impl GSM for GSMClient {
             .secrets_add_version(
                 AddSecretVersionRequest {
                     payload: Some(SecretPayload {
-                        data: Some(value.as_bytes().to_vec()),
-                        data_crc32c: Some(i64::from(crc32c::crc32c(value.as_bytes()))),
+                        data: Some(value.to_vec()),
+                        data_crc32c: Some(i64::from(crc32c::crc32c(value))),
                     }),
                 },
                 name,
@@ -250,17 +307,59 @@ async fn resolve_auth() -> Result<Authenticator<oauth2::hyper_rustls::HttpsConne
         .map_err(Box::from)?)
 }
 
+/// # Google Secret Manager options
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GoogleSecretManagerOptions {
+    /// Project id, lets paths be simple names instead of `projects/<id>`
+    pub project: Option<String>,
+    /// Labels set on created secrets
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Annotations set on created secrets
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+    /// Filter for listing secrets, e.g. `labels.managed-by=teller`
+    pub filter: Option<String>,
+}
+
 pub struct GoogleSecretManager {
     client: Box<dyn GSM + Send + Sync>,
     pub name: String,
+    opts: GoogleSecretManagerOptions,
 }
 
 impl GoogleSecretManager {
     #[must_use]
-    pub fn new(name: &str, client: Box<dyn GSM + Send + Sync>) -> Self {
+    pub fn new(
+        name: &str,
+        opts: Option<GoogleSecretManagerOptions>,
+        client: Box<dyn GSM + Send + Sync>,
+    ) -> Self {
         Self {
             client,
             name: name.to_string(),
+            opts: opts.unwrap_or_default(),
+        }
+    }
+
+    /// The parent resource of a path, and the prefix of its secret ids
+    ///
+    /// `projects/123` is used as is, with the `project` option `app/dev`
+    /// becomes `projects/<project>` and `app_dev_`.
+    fn resolve(&self, path: &str) -> (String, String) {
+        match &self.opts.project {
+            Some(project) if !path.starts_with("projects/") => {
+                let prefix = path.trim_matches('/').replace('/', "_");
+                (
+                    format!("projects/{project}"),
+                    if prefix.is_empty() {
+                        prefix
+                    } else {
+                        format!("{prefix}_")
+                    },
+                )
+            }
+            _ => (path.to_string(), String::new()),
         }
     }
 }
@@ -275,27 +374,46 @@ impl Provider for GoogleSecretManager {
     }
 
     async fn get(&self, pm: &PathMap) -> Result<Vec<KV>> {
+        let (parent, prefix) = self.resolve(&pm.path);
         let mut out = Vec::new();
         if pm.keys.is_empty() {
             // get parameters by path
             // ("projects/1xxx34/secrets/DSN4", "foobar")
-            let values = self.client.list(&pm.path).await?;
+            let values = self
+                .client
+                .list(&parent, self.opts.filter.as_deref())
+                .await?;
 
             for (resource, v) in values {
                 // projects/123/secrets/FOOBAR -> FOOBAR
                 //                     ^-<--<--< rsplit
-                if let Some((_, key)) = resource.rsplit_once('/') {
-                    out.push(KV::from_value(&v, key, key, pm, self.kind()));
+                if let Some(key) = resource
+                    .rsplit_once('/')
+                    .and_then(|(_, id)| id.strip_prefix(&prefix))
+                {
+                    out.push(KV::from_secret(
+                        SecretValue::from_bytes(v),
+                        key,
+                        key,
+                        pm,
+                        self.kind(),
+                    ));
                 }
             }
         } else {
             for (k, v) in &pm.keys {
                 let resp = self
                     .client
-                    .get(&format!("{}/secrets/{}", pm.path, k))
+                    .get(&format!("{parent}/secrets/{prefix}{k}"))
                     .await?;
                 if let Some(val) = resp {
-                    out.push(KV::from_value(&val, k, v, pm, self.kind()));
+                    out.push(KV::from_secret(
+                        SecretValue::from_bytes(val),
+                        k,
+                        v,
+                        pm,
+                        self.kind(),
+                    ));
                 }
             }
         }
@@ -310,25 +428,39 @@ impl Provider for GoogleSecretManager {
     }
 
     async fn put(&self, pm: &PathMap, kvs: &[KV]) -> Result<()> {
+        let (parent, prefix) = self.resolve(&pm.path);
         for kv in kvs {
             self.client
-                .put(&format!("{}/secrets/{}", pm.path, kv.key), &kv.value)
+                .put(
+                    &format!("{parent}/secrets/{prefix}{}", kv.key),
+                    kv.value.as_bytes(),
+                    &self.opts,
+                )
                 .await?;
         }
         Ok(())
     }
 
     async fn del(&self, pm: &PathMap) -> Result<()> {
+        let (parent, prefix) = self.resolve(&pm.path);
         if pm.keys.is_empty() {
-            let values = self.client.list(&pm.path).await?;
+            let values = self
+                .client
+                .list(&parent, self.opts.filter.as_deref())
+                .await?;
 
             for (resource, _) in values {
-                self.client.del(&resource).await?;
+                if resource
+                    .rsplit_once('/')
+                    .is_some_and(|(_, id)| id.starts_with(&prefix))
+                {
+                    self.client.del(&resource).await?;
+                }
             }
         } else {
             for k in pm.keys.keys() {
                 self.client
-                    .del(&format!("{}/secrets/{}", pm.path, k))
+                    .del(&format!("{parent}/secrets/{prefix}{k}"))
                     .await?;
             }
         }
@@ -349,12 +481,15 @@ mod tests {
     use google_secretmanager1::SecretManager;
 
     use crate::{
-        providers::{google_secretmanager::GSM, test_utils},
-        Provider, Result,
+        config::{PathMap, KV},
+        providers::google_secretmanager::{GoogleSecretManagerOptions, GSM},
+        secret::SecretValue,
+        testkit, Provider, Result,
     };
 
     struct MockClient {
-        data: Arc<Mutex<BTreeMap<String, String>>>,
+        data: Arc<Mutex<BTreeMap<String, Vec<u8>>>>,
+        labels: Arc<Mutex<BTreeMap<String, BTreeMap<String, String>>>>,
     }
 
     impl MockClient {
@@ -365,6 +500,7 @@ mod tests {
         pub fn new() -> Self {
             Self {
                 data: Arc::new(Mutex::new(BTreeMap::new())),
+                labels: Arc::new(Mutex::new(BTreeMap::new())),
             }
         }
     }
@@ -375,26 +511,47 @@ mod tests {
             None
         }
 
-        async fn list(&self, name: &str) -> Result<Vec<(String, String)>> {
+        async fn list(&self, name: &str, filter: Option<&str>) -> Result<Vec<(String, Vec<u8>)>> {
+            // supports `labels.<key>=<value>` filters only
+            let wanted = filter.and_then(|f| f.strip_prefix("labels.")?.split_once('='));
+            let labels = self.labels.lock().unwrap();
             Ok(self
                 .data
                 .lock()
                 .unwrap()
                 .iter()
                 .filter(|(k, _)| k.starts_with(name))
+                .filter(|(k, _)| {
+                    wanted.is_none_or(|(key, value)| {
+                        labels
+                            .get(*k)
+                            .and_then(|l| l.get(key))
+                            .is_some_and(|v| v == value)
+                    })
+                })
                 .map(|(k, v)| (k.clone(), v.clone()))
                 .collect::<Vec<_>>())
         }
 
-        async fn get(&self, name: &str) -> Result<Option<String>> {
+        async fn get(&self, name: &str) -> Result<Option<Vec<u8>>> {
             Ok(self.data.lock().unwrap().get(name).cloned())
         }
 
-        async fn put(&self, name: &str, value: &str) -> Result<()> {
+        async fn put(
+            &self,
+            name: &str,
+            value: &[u8],
+            opts: &GoogleSecretManagerOptions,
+        ) -> Result<()> {
+            self.labels
+                .lock()
+                .unwrap()
+                .entry(name.to_string())
+                .or_insert_with(|| opts.labels.clone());
             self.data
                 .lock()
                 .unwrap()
-                .insert(name.to_string(), value.to_string());
+                .insert(name.to_string(), value.to_vec());
             Ok(())
         }
 
@@ -409,9 +566,106 @@ mod tests {
         let mock_client = MockClient::new();
         let p = Box::new(super::GoogleSecretManager::new(
             "test",
+            None,
             Box::new(mock_client) as Box<dyn GSM + Send + Sync>,
         )) as Box<dyn Provider + Send + Sync>;
 
-        test_utils::ProviderTest::new(p).run().await;
+        testkit::ProviderTest::new(p).run().await;
+    }
+
+    #[tokio::test]
+    async fn binary_roundtrip() {
+        let p = super::GoogleSecretManager::new("test", None, Box::new(MockClient::new()));
+        let pm = PathMap::from_path("projects/1");
+        let keystore = SecretValue::from_bytes(vec![0xfe, 0xed, 0xfe, 0xed, 0x00]);
+        p.put(
+            &pm,
+            &[KV::from_secret(
+                keystore.clone(),
+                "KEYSTORE",
+                "KEYSTORE",
+                &pm,
+                p.kind(),
+            )],
+        )
+        .await
+        .unwrap();
+
+        let kvs = p.get(&pm).await.unwrap();
+        assert_eq!(kvs[0].value, keystore);
+        assert!(kvs[0].value.is_binary());
+    }
+
+    #[tokio::test]
+    async fn project_shorthand() {
+        let client = MockClient::new();
+        let data = client.data.clone();
+        let p = super::GoogleSecretManager::new(
+            "test",
+            Some(GoogleSecretManagerOptions {
+                project: Some("p1".to_string()),
+                ..Default::default()
+            }),
+            Box::new(client),
+        );
+        let dev = PathMap::from_path("app/dev");
+        p.put(&dev, &[KV::from_kv("DB_PASS", "dev-pass")])
+            .await
+            .unwrap();
+        p.put(
+            &PathMap::from_path("projects/p1"),
+            &[KV::from_kv("app_prod_DB_PASS", "prod-pass")],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            data.lock().unwrap().keys().collect::<Vec<_>>(),
+            vec![
+                "projects/p1/secrets/app_dev_DB_PASS",
+                "projects/p1/secrets/app_prod_DB_PASS"
+            ]
+        );
+
+        let kvs = p.get(&dev).await.unwrap();
+        assert_eq!(kvs.len(), 1);
+        assert_eq!(kvs[0].key, "DB_PASS");
+        assert_eq!(kvs[0].value.expose(), "dev-pass");
+
+        p.del(&dev).await.unwrap();
+        assert_eq!(data.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn labels_and_filter() {
+        let client = MockClient::new();
+        let data = client.data.clone();
+        let p = super::GoogleSecretManager::new(
+            "test",
+            Some(GoogleSecretManagerOptions {
+                labels: BTreeMap::from([("managed-by".to_string(), "teller".to_string())]),
+                filter: Some("labels.managed-by=teller".to_string()),
+                ..Default::default()
+            }),
+            Box::new(client),
+        );
+        let pm = PathMap::from_path("projects/1");
+        data.lock()
+            .unwrap()
+            .insert("projects/1/secrets/OTHER".to_string(), b"other".to_vec());
+        p.put(&pm, &[KV::from_kv("MANAGED", "managed")])
+            .await
+            .unwrap();
+
+        let kvs = p.get(&pm).await.unwrap();
+        assert_eq!(
+            kvs.iter().map(|kv| kv.key.as_str()).collect::<Vec<_>>(),
+            vec!["MANAGED"]
+        );
+
+        p.del(&pm).await.unwrap();
+        assert_eq!(
+            data.lock().unwrap().keys().collect::<Vec<_>>(),
+            vec!["projects/1/secrets/OTHER"]
+        );
     }
 }
@@ -13,10 +13,22 @@
 //!
 //! See [`HashiCorpConsulOptions`] for more.
 //!
+//! Writing and deleting up to 64 keys goes through Consul's transaction API, so
+//! a map is updated as a whole or not at all. Consul takes no more operations
+//! per transaction, so more keys are written or deleted one by one, and a
+//! failure can leave such a map partially updated.
+//!
+//! Changes under a map's path can be watched with blocking queries, see
+//! [`Provider::watch`].
+//!
 #![allow(clippy::borrowed_box)]
-use std::env;
+use std::{collections::BTreeSet, env, time::Duration};
 
 use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hyper::client::HttpConnector;
+use hyper_rustls::HttpsConnector;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use rs_consul::{Consul, ConsulError};
 use serde_derive::{Deserialize, Serialize};
 
@@ -44,23 +56,110 @@ fn to_err(pm: &PathMap, e: ConsulError) -> Error {
                 msg: "not found".to_string(),
             }
         }
+        ConsulError::UnexpectedResponseCode(
+            code @ (hyper::http::StatusCode::FORBIDDEN | hyper::http::StatusCode::UNAUTHORIZED),
+            body,
+        ) => Error::Auth {
+            path: pm.path.clone(),
+            msg: format!("{code}: {body}"),
+        },
         _ => Error::Any(Box::from(e)),
     }
 }
 
+/// How long a blocking query waits for changes before answering anyway
+const WATCH_WAIT: Duration = Duration::from_mins(5);
+/// Time between reads while a watched path does not exist
+const WATCH_MISSING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Consul's limit of operations in a single transaction
+const TXN_MAX_OPS: usize = 64;
+
+/// Client for Consul's transaction API, which `rs_consul` does not cover
+struct Txn {
+    client: hyper::Client<HttpsConnector<HttpConnector>>,
+    address: String,
+    token: String,
+}
+
+impl Txn {
+    fn set(key: &str, value: &[u8]) -> serde_json::Value {
+        serde_json::json!({ "KV": { "Verb": "set", "Key": key, "Value": STANDARD.encode(value) } })
+    }
+
+    fn delete(key: &str) -> serde_json::Value {
+        serde_json::json!({ "KV": { "Verb": "delete", "Key": key } })
+    }
+
+    /// Build the request applying operations in one transaction
+    fn request(
+        &self,
+        datacenter: &str,
+        ops: &[serde_json::Value],
+    ) -> std::result::Result<hyper::Request<hyper::Body>, String> {
+        if ops.len() > TXN_MAX_OPS {
+            return Err(format!(
+                "{} keys cannot be changed in one transaction, Consul takes at most {TXN_MAX_OPS}",
+                ops.len()
+            ));
+        }
+        let mut uri = format!("{}/v1/txn", self.address.trim_end_matches('/'));
+        if !datacenter.is_empty() {
+            uri.push_str("?dc=");
+            uri.extend(utf8_percent_encode(datacenter, NON_ALPHANUMERIC));
+        }
+        let mut req = hyper::Request::put(uri);
+        if !self.token.is_empty() {
+            req = req.header("X-Consul-Token", &self.token);
+        }
+        let body = serde_json::to_vec(ops).map_err(|e| e.to_string())?;
+        req.body(hyper::Body::from(body)).map_err(|e| e.to_string())
+    }
+
+    /// Apply operations atomically, returning Consul's response on failure
+    async fn apply(
+        &self,
+        datacenter: &str,
+        ops: &[serde_json::Value],
+    ) -> std::result::Result<(), String> {
+        let req = self.request(datacenter, ops)?;
+        let res = self.client.request(req).await.map_err(|e| e.to_string())?;
+        let status = res.status();
+        if !status.is_success() {
+            let body = hyper::body::to_bytes(res.into_body())
+                .await
+                .map_err(|e| e.to_string())?;
+            return Err(format!(
+                "transaction failed ({status}): {}",
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        Ok(())
+    }
+}
+
 pub struct HashiCorpConsul {
     pub consul: Consul,
     opts: HashiCorpConsulOptions,
     pub name: String,
+    /// not available with [`HashiCorpConsul::with_client`], keys are then
+    /// written one by one
+    txn: Option<Txn>,
 }
 
 impl HashiCorpConsul {
+    /// The transaction client, when `ops` operations fit in one transaction
+    fn txn(&self, ops: usize) -> Option<&Txn> {
+        self.txn.as_ref().filter(|_| ops <= TXN_MAX_OPS)
+    }
+
     #[must_use]
     pub fn with_client(name: &str, client: Consul) -> Self {
         Self {
             consul: client,
             opts: HashiCorpConsulOptions::default(),
             name: name.to_string(),
+            txn: None,
         }
     }
 
@@ -90,6 +189,18 @@ impl HashiCorpConsul {
             )
             .unwrap_or_default();
 
+        let txn = Txn {
+            client: hyper::Client::builder().build(
+                hyper_rustls::HttpsConnectorBuilder::new()
+                    .with_native_roots()
+                    .https_or_http()
+                    .enable_http1()
+                    .build(),
+            ),
+            address: address.clone(),
+            token: token.clone(),
+        };
+
         Ok(Self {
             consul: Consul::new(rs_consul::Config {
                 address,
@@ -99,6 +210,7 @@ impl HashiCorpConsul {
             }),
             opts,
             name: name.to_string(),
+            txn: Some(txn),
         })
     }
 }
@@ -113,36 +225,86 @@ impl Provider for HashiCorpConsul {
     }
 
     async fn get(&self, pm: &PathMap) -> Result<Vec<KV>> {
-        let res = self
-            .consul
-            .read_key(rs_consul::ReadKeyRequest {
-                key: &pm.path,
-                datacenter: &self.opts.dc.clone().unwrap_or_default(),
-                recurse: false,
-                ..Default::default()
-            })
-            .await
-            .map_err(|e| to_err(pm, e))?;
-
+        let datacenter = self.opts.dc.clone().unwrap_or_default();
         let mut results = vec![];
-        for kv_pair in res {
-            let val = kv_pair.value.ok_or_else(|| Error::NotFound {
-                path: pm.path.to_string(),
-                msg: "value not found".to_string(),
-            })?;
-
-            let (_, key) = kv_pair.key.rsplit_once('/').unwrap_or(("", &kv_pair.key));
+        if pm.keys.is_empty() {
+            // everything under the path, keyed relative to it
+            let res = self
+                .consul
+                .read_key(rs_consul::ReadKeyRequest {
+                    key: &pm.path,
+                    datacenter: &datacenter,
+                    recurse: true,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| to_err(pm, e))?;
 
-            // take all or slice the requested keys
-            if pm.keys.is_empty() || pm.keys.contains_key(key) {
+            for kv_pair in res {
+                // folders have no value
+                let Some(val) = kv_pair.value else {
+                    continue;
+                };
+                let key = match kv_pair.key.strip_prefix(&pm.path) {
+                    Some("") => kv_pair
+                        .key
+                        .rsplit_once('/')
+                        .map_or(kv_pair.key.as_str(), |(_, key)| key),
+                    Some(rest) if rest.starts_with('/') || pm.path.ends_with('/') => {
+                        rest.trim_start_matches('/')
+                    }
+                    // a sibling sharing the path as a prefix, e.g. `app-2` for `app`
+                    _ => continue,
+                };
                 results.push(KV::from_value(&val, key, key, pm, self.kind()));
             }
+        } else {
+            for (from_key, to_key) in &pm.keys {
+                let res = self
+                    .consul
+                    .read_key(rs_consul::ReadKeyRequest {
+                        key: &format!("{}/{from_key}", pm.path),
+                        datacenter: &datacenter,
+                        recurse: false,
+                        ..Default::default()
+                    })
+                    .await
+                    .map_err(|e| to_err(pm, e));
+                let res = match res {
+                    Err(Error::NotFound { .. }) => continue,
+                    res => res?,
+                };
+                for val in res.into_iter().filter_map(|kv_pair| kv_pair.value) {
+                    results.push(KV::from_value(&val, from_key, to_key, pm, self.kind()));
+                }
+            }
+        }
+
+        if results.is_empty() {
+            return Err(Error::NotFound {
+                path: pm.path.to_string(),
+                msg: "not found".to_string(),
+            });
         }
 
         Ok(results)
     }
 
     async fn put(&self, pm: &PathMap, kvs: &[KV]) -> Result<()> {
+        if let Some(txn) = self.txn(kvs.len()) {
+            let ops = kvs
+                .iter()
+                .map(|kv| Txn::set(&format!("{}/{}", pm.path, kv.key), kv.value.as_bytes()))
+                .collect::<Vec<_>>();
+            return txn
+                .apply(&self.opts.dc.clone().unwrap_or_default(), &ops)
+                .await
+                .map_err(|msg| Error::PutError {
+                    path: pm.path.clone(),
+                    msg,
+                });
+        }
+
         for kv in kvs {
             self.consul
                 .create_or_update_key(
@@ -159,6 +321,46 @@ impl Provider for HashiCorpConsul {
         Ok(())
     }
 
+    async fn watch(&self, pm: &PathMap) -> Result<()> {
+        let datacenter = self.opts.dc.clone().unwrap_or_default();
+        let mut seen: Option<BTreeSet<(String, i64)>> = None;
+        loop {
+            // block until the index moves past the newest key we have seen
+            let index = seen
+                .as_ref()
+                .and_then(|seen| seen.iter().map(|(_, index)| *index).max())
+                .and_then(|index| u64::try_from(index).ok());
+            let res = self
+                .consul
+                .read_key(rs_consul::ReadKeyRequest {
+                    key: &pm.path,
+                    datacenter: &datacenter,
+                    recurse: true,
+                    index,
+                    wait: WATCH_WAIT,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| to_err(pm, e));
+            let current = match res {
+                Ok(res) => res
+                    .into_iter()
+                    .map(|kv_pair| (kv_pair.key, kv_pair.modify_index))
+                    .collect::<BTreeSet<_>>(),
+                // missing paths cannot block, poll them instead
+                Err(Error::NotFound { .. }) => {
+                    tokio::time::sleep(WATCH_MISSING_INTERVAL).await;
+                    BTreeSet::new()
+                }
+                Err(err) => return Err(err),
+            };
+            if seen.as_ref().is_some_and(|seen| *seen != current) {
+                return Ok(());
+            }
+            seen = Some(current);
+        }
+    }
+
     async fn del(&self, pm: &PathMap) -> Result<()> {
         let keys = if pm.keys.is_empty() {
             self.consul
@@ -180,6 +382,17 @@ impl Provider for HashiCorpConsul {
                 .collect::<Vec<_>>()
         };
 
+        if let Some(txn) = self.txn(keys.len()) {
+            let ops = keys.iter().map(|key| Txn::delete(key)).collect::<Vec<_>>();
+            return txn
+                .apply(&self.opts.dc.clone().unwrap_or_default(), &ops)
+                .await
+                .map_err(|msg| Error::DeleteError {
+                    path: pm.path.clone(),
+                    msg,
+                });
+        }
+
         for key in keys {
             self.consul
                 .delete_key(rs_consul::DeleteKeyRequest {
@@ -202,10 +415,44 @@ mod tests {
     use dockertest_server::Test;
 
     use super::*;
-    use crate::providers::test_utils;
+    use crate::testkit;
 
     const PORT: u32 = 8501;
 
+    #[test]
+    fn txn_requests() {
+        let consul = HashiCorpConsul::new(
+            "consul",
+            Some(HashiCorpConsulOptions {
+                address: Some("http://localhost:8500/".to_string()),
+                ..HashiCorpConsulOptions::default()
+            }),
+        )
+        .unwrap();
+        assert!(consul.txn(TXN_MAX_OPS).is_some());
+        // more keys are written one by one instead
+        assert!(consul.txn(testkit::PAGING_KEYS).is_none());
+        let txn = consul.txn.as_ref().unwrap();
+        let ops = |n: usize| {
+            (0..n)
+                .map(|i| Txn::set(&format!("app/KEY_{i}"), b"v"))
+                .collect::<Vec<_>>()
+        };
+
+        let req = txn.request("", &ops(TXN_MAX_OPS)).unwrap();
+        assert_eq!(req.uri(), "http://localhost:8500/v1/txn");
+        assert_eq!(
+            txn.request("", &ops(TXN_MAX_OPS + 1)).unwrap_err(),
+            "65 keys cannot be changed in one transaction, Consul takes at most 64"
+        );
+
+        let req = txn.request("eu west&x=1", &ops(1)).unwrap();
+        assert_eq!(
+            req.uri(),
+            "http://localhost:8500/v1/txn?dc=eu%20west%26x%3D1"
+        );
+    }
+
     #[test]
     #[cfg(not(windows))]
     fn sanity_test() {
@@ -241,7 +488,7 @@ mod tests {
                 .unwrap(),
             ) as Box<dyn Provider + Send + Sync>;
 
-            test_utils::ProviderTest::new(p).run().await;
+            testkit::ProviderTest::new(p).run().await;
         });
     }
 }
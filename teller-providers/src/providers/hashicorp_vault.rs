@@ -13,16 +13,56 @@
 //!
 //! See [`HashivaultOptions`] for more.
 //!
+//! ## Wildcard paths
+//!
+//! A kv2 path ending with `/*` reads every secret directly under it, so new
+//! secrets are picked up without changing the configuration. With
+//! `child_prefix`, keys are prefixed by the name of the secret they come from.
+//! Wildcard paths are read only.
+//!
+//! ## Namespaces
+//!
+//! The provider's `namespace` option can be overridden per map, so maps of
+//! several enterprise namespaces share one provider:
+//!
+//! ```yaml
+//! providers:
+//!  vault1:
+//!    kind: hashicorp_vault
+//!    options:
+//!      namespace: dev
+//!    maps:
+//!      - id: dev
+//!        path: secret/app
+//!      - id: prod
+//!        path: secret/app
+//!        namespace: prod
+//! ```
+//!
+//! ```yaml
+//! providers:
+//!  vault1:
+//!    kind: hashicorp_vault
+//!    maps:
+//!      - id: apps
+//!        path: secret/apps/*
+//!        child_prefix: true
+//! ```
+//!
 #![allow(clippy::borrowed_box)]
 use std::{
     collections::{BTreeMap, HashMap},
     env,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use async_trait::async_trait;
+use reqwest::{Certificate, Identity, Proxy};
 use serde_derive::{Deserialize, Serialize};
 use vaultrs::{
-    client::{VaultClient, VaultClientSettingsBuilder},
+    client::{VaultClient, VaultClientSettings, VaultClientSettingsBuilder},
     error::ClientError,
     kv1, kv2,
 };
@@ -38,17 +78,84 @@ use crate::{
 /// If no options provided at all, will take `VAULT_ADDR` and `VAULT_TOKEN` env variables.
 /// If partial options provided, will only take what's provided.
 ///
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Certificate options not provided are taken from `VAULT_CACERT`,
+/// `VAULT_CLIENT_CERT`, `VAULT_CLIENT_KEY` and `VAULT_SKIP_VERIFY`.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct HashivaultOptions {
     /// Vault address
     pub address: Option<String>,
     /// Vault token
     pub token: Option<String>,
+    /// Path of a CA certificate (PEM) Vault's certificate is verified with
+    pub ca_cert: Option<String>,
+    /// Path of a client certificate (PEM), to authenticate with `client_key`
+    pub client_cert: Option<String>,
+    /// Path of the client certificate's key (PEM)
+    pub client_key: Option<String>,
+    /// Do not verify Vault's certificate. Insecure, for testing only
+    #[serde(default)]
+    pub tls_skip_verify: bool,
+    /// Proxy requests to Vault go through, e.g. `http://proxy.internal:3128`
+    pub proxy: Option<String>,
+    /// Enterprise namespace, maps can override it with their own `namespace`
+    pub namespace: Option<String>,
+}
+
+impl HashivaultOptions {
+    /// The client certificate and its key, as one identity
+    fn identity(&self) -> Result<Option<Identity>> {
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => {
+                let mut pem = fs_err::read(cert)?;
+                pem.push(b'\n');
+                pem.extend(fs_err::read(key)?);
+                Ok(Some(Identity::from_pem(&pem).map_err(Box::from)?))
+            }
+            (None, None) => Ok(None),
+            _ => Err(Error::Message(
+                "client_cert and client_key must be set together".to_string(),
+            )),
+        }
+    }
+}
+
+fn build_client(settings: VaultClientSettings, proxy: Option<&str>) -> Result<VaultClient> {
+    let mut client = VaultClient::new(settings).map_err(Box::from)?;
+    if let Some(proxy) = proxy {
+        client.http = rustify::clients::reqwest::Client::new(
+            client.settings.address.as_str(),
+            proxied_http_client(&client.settings, proxy)?,
+        );
+    }
+    Ok(client)
+}
+
+/// The HTTP client [`VaultClient::new`] builds from its settings, going
+/// through a proxy
+fn proxied_http_client(settings: &VaultClientSettings, proxy: &str) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .danger_accept_invalid_certs(!settings.verify)
+        .proxy(Proxy::all(proxy).map_err(Box::from)?);
+    if let Some(timeout) = settings.timeout {
+        builder = builder.timeout(timeout);
+    }
+    for path in &settings.ca_certs {
+        builder = builder
+            .add_root_certificate(Certificate::from_pem(&fs_err::read(path)?).map_err(Box::from)?);
+    }
+    if let Some(identity) = &settings.identity {
+        builder = builder.identity(identity.clone());
+    }
+    Ok(builder.build().map_err(Box::from)?)
 }
 
 pub struct Hashivault {
     pub client: VaultClient,
     pub name: String,
+    proxy: Option<String>,
+    /// clients of namespaces maps override the provider's with
+    namespaced: Mutex<HashMap<String, Arc<VaultClient>>>,
 }
 
 impl Hashivault {
@@ -58,17 +165,33 @@ impl Hashivault {
     ///
     /// This function will return an error if cannot create a provider
     pub fn new(name: &str, opts: Option<HashivaultOptions>) -> Result<Self> {
-        let settings = if let Some(opts) = opts {
+        let settings = if let Some(opts) = &opts {
             let mut settings = VaultClientSettingsBuilder::default();
 
-            if let Some(address) = opts.address {
+            if let Some(address) = &opts.address {
                 settings.address(address);
             }
 
-            if let Some(token) = opts.token {
+            if let Some(token) = &opts.token {
                 settings.token(token);
             }
 
+            if let Some(ca_cert) = &opts.ca_cert {
+                settings.ca_certs(vec![ca_cert.clone()]);
+            }
+
+            if let Some(identity) = opts.identity()? {
+                settings.identity(Some(identity));
+            }
+
+            if opts.tls_skip_verify {
+                settings.verify(false);
+            }
+
+            if let Some(namespace) = &opts.namespace {
+                settings.namespace(Some(namespace.clone()));
+            }
+
             settings.build().map_err(Box::from)?
         } else {
             VaultClientSettingsBuilder::default()
@@ -78,13 +201,84 @@ impl Hashivault {
                 .map_err(Box::from)?
         };
 
-        let client = VaultClient::new(settings).map_err(Box::from)?;
-
+        let proxy = opts.and_then(|opts| opts.proxy);
         Ok(Self {
-            client,
+            client: build_client(settings, proxy.as_deref())?,
             name: name.to_string(),
+            proxy,
+            namespaced: Mutex::new(HashMap::new()),
         })
     }
+
+    /// A client for the map's namespace, `None` when it is the provider's own
+    fn namespaced(&self, pm: &PathMap) -> Result<Option<Arc<VaultClient>>> {
+        let Some(namespace) = pm
+            .namespace
+            .as_ref()
+            .filter(|namespace| self.client.settings.namespace.as_ref() != Some(*namespace))
+        else {
+            return Ok(None);
+        };
+
+        let mut clients = self
+            .namespaced
+            .lock()
+            .map_err(|_| Error::Message("vault clients lock is poisoned".to_string()))?;
+        if let Some(client) = clients.get(namespace) {
+            return Ok(Some(client.clone()));
+        }
+        let mut settings = self.client.settings.clone();
+        settings.namespace = Some(namespace.clone());
+        let client = Arc::new(build_client(settings, self.proxy.as_deref())?);
+        clients.insert(namespace.clone(), client.clone());
+        Ok(Some(client))
+    }
+}
+
+/// Redirect URI of the OIDC login, Vault CLI's default. The OIDC role must
+/// allow it in its `allowed_redirect_uris`.
+pub const OIDC_REDIRECT_URI: &str = "http://localhost:8250/oidc/callback";
+
+/// Sign in with Vault's OIDC auth method. `authorize` gets the identity
+/// provider's URL to open in a browser, and returns the `state` and `code` the
+/// browser is redirected to [`OIDC_REDIRECT_URI`] with.
+///
+/// Returns the new token, and how long it lasts when it expires.
+///
+/// # Errors
+///
+/// This function will return an error if Vault refuses the login
+pub async fn oidc_login<F, Fut>(
+    opts: Option<HashivaultOptions>,
+    mount: &str,
+    role: Option<String>,
+    authorize: F,
+) -> Result<(String, Option<Duration>)>
+where
+    F: FnOnce(String) -> Fut + Send,
+    Fut: Future<Output = Result<(String, String)>> + Send,
+{
+    let vault = Hashivault::new("login", Some(opts.unwrap_or_default()))?;
+    let login_err = |e: ClientError| Error::Message(format!("oidc login: {e}"));
+    let auth = vaultrs::auth::oidc::auth(&vault.client, mount, OIDC_REDIRECT_URI, role)
+        .await
+        .map_err(login_err)?;
+    let nonce = query_param(&auth.auth_url, "nonce").unwrap_or_default();
+    let (state, code) = authorize(auth.auth_url).await?;
+    let info = vaultrs::auth::oidc::callback(&vault.client, mount, &state, &nonce, &code)
+        .await
+        .map_err(login_err)?;
+    let lease = (info.lease_duration > 0).then(|| Duration::from_secs(info.lease_duration));
+    Ok((info.client_token, lease))
+}
+
+fn query_param(url: &str, name: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
 }
 
 fn parse_path(pm: &PathMap) -> Result<(&str, &str, &str)> {
@@ -114,6 +308,10 @@ fn xerr(pm: &PathMap, e: ClientError) -> Error {
                         path: pm.path.clone(),
                         msg: "not found".to_string(),
                     },
+                    (401 | 403, _) => Error::Auth {
+                        path: pm.path.clone(),
+                        msg: format!("code: {code}, {content:?}"),
+                    },
                     _ => Error::Message(format!("code: {code}, {content:?}")),
                 }
             }
@@ -126,6 +324,13 @@ fn xerr(pm: &PathMap, e: ClientError) -> Error {
             path: pm.path.clone(),
             msg: "not found".to_string(),
         },
+        ClientError::APIError {
+            code: code @ (401 | 403),
+            errors,
+        } => Error::Auth {
+            path: pm.path.clone(),
+            msg: format!("code: {code}, {}", errors.join(", ")),
+        },
         _ => Error::Any(Box::from(e)),
     }
 }
@@ -142,6 +347,70 @@ async fn get_data(client: &VaultClient, pm: &PathMap) -> Result<BTreeMap<String,
     Ok(data)
 }
 
+/// `secret/apps/*` -> `secret/apps`
+fn wildcard_parent(pm: &PathMap) -> Option<&str> {
+    pm.path.strip_suffix("/*")
+}
+
+fn reject_wildcard(pm: &PathMap) -> Result<()> {
+    if wildcard_parent(pm).is_some() {
+        return Err(Error::PathError(
+            pm.path.clone(),
+            "wildcard paths are read only".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+impl Hashivault {
+    /// Read every secret directly under a wildcard path
+    async fn get_wildcard(
+        &self,
+        client: &VaultClient,
+        pm: &PathMap,
+        parent: &str,
+    ) -> Result<Vec<KV>> {
+        let (engine, mount, path) = parse_path(pm)?;
+        if engine != "kv2" {
+            return Err(Error::PathError(
+                pm.path.clone(),
+                "wildcard paths need the kv2 protocol".to_string(),
+            ));
+        }
+        let children = kv2::list(
+            client,
+            mount,
+            path.strip_suffix('*').unwrap_or(path).trim_end_matches('/'),
+        )
+        .await
+        .map_err(|e| xerr(pm, e))?;
+
+        let mut out = Vec::new();
+        // names ending with `/` are folders, not secrets
+        for child in children.iter().filter(|child| !child.ends_with('/')) {
+            let mut child_pm = PathMap {
+                path: format!("{parent}/{child}"),
+                ..pm.clone()
+            };
+            let data = get_data(client, &child_pm).await?;
+            if pm.child_prefix {
+                child_pm.keys = if pm.keys.is_empty() {
+                    data.keys()
+                        .map(|k| (k.clone(), format!("{child}_{k}")))
+                        .collect()
+                } else {
+                    pm.keys
+                        .iter()
+                        .map(|(k, v)| (k.clone(), format!("{child}_{v}")))
+                        .collect()
+                };
+            }
+            out.extend(KV::from_data(&data, &child_pm, &self.kind()));
+        }
+        Ok(out)
+    }
+}
+
 async fn get_data_or_empty(client: &VaultClient, pm: &PathMap) -> Result<BTreeMap<String, String>> {
     let data = match get_data(client, pm).await {
         Ok(data) => data,
@@ -187,30 +456,42 @@ impl Provider for Hashivault {
     }
 
     async fn get(&self, pm: &PathMap) -> Result<Vec<KV>> {
+        let namespaced = self.namespaced(pm)?;
+        let client = namespaced.as_deref().unwrap_or(&self.client);
+        let to_get_err = |e: Error| match e {
+            Error::NotFound { path, msg } => Error::NotFound { path, msg },
+            _ => Error::GetError {
+                path: pm.path.to_string(),
+                msg: e.to_string(),
+            },
+        };
+        if let Some(parent) = wildcard_parent(pm) {
+            return self
+                .get_wildcard(client, pm, parent)
+                .await
+                .map_err(to_get_err);
+        }
         Ok(KV::from_data(
-            &get_data(&self.client, pm).await.map_err(|e| match e {
-                Error::NotFound { path, msg } => Error::NotFound { path, msg },
-                _ => Error::GetError {
-                    path: pm.path.to_string(),
-                    msg: e.to_string(),
-                },
-            })?,
+            &get_data(client, pm).await.map_err(to_get_err)?,
             pm,
             &self.kind(),
         ))
     }
 
     async fn put(&self, pm: &PathMap, kvs: &[KV]) -> Result<()> {
-        let mut data = get_data_or_empty(&self.client, pm)
+        reject_wildcard(pm)?;
+        let namespaced = self.namespaced(pm)?;
+        let client = namespaced.as_deref().unwrap_or(&self.client);
+        let mut data = get_data_or_empty(client, pm)
             .await
             .map_err(|e| Error::PutError {
                 path: pm.path.to_string(),
                 msg: e.to_string(),
             })?;
         for kv in kvs {
-            data.insert(kv.key.clone(), kv.value.clone());
+            data.insert(kv.key.clone(), kv.value.expose().to_string());
         }
-        put_data(&self.client, pm, &data)
+        put_data(client, pm, &data)
             .await
             .map_err(|e| Error::PutError {
                 path: pm.path.to_string(),
@@ -220,21 +501,23 @@ impl Provider for Hashivault {
     }
 
     async fn del(&self, pm: &PathMap) -> Result<()> {
+        reject_wildcard(pm)?;
+        let namespaced = self.namespaced(pm)?;
+        let client = namespaced.as_deref().unwrap_or(&self.client);
         // if pm contains specific keys, we cannot delete the path,
         // deleting a complete path may drop everything under it (a path stores a dictionary of k/v)
         // we want to remove the keys from the secret object and re-write it into its path.
         if !pm.keys.is_empty() {
-            let mut data =
-                get_data_or_empty(&self.client, pm)
-                    .await
-                    .map_err(|e| Error::DeleteError {
-                        path: pm.path.to_string(),
-                        msg: e.to_string(),
-                    })?;
+            let mut data = get_data_or_empty(client, pm)
+                .await
+                .map_err(|e| Error::DeleteError {
+                    path: pm.path.to_string(),
+                    msg: e.to_string(),
+                })?;
             for key in pm.keys.keys() {
                 data.remove(key);
             }
-            put_data(&self.client, pm, &data)
+            put_data(client, pm, &data)
                 .await
                 .map_err(|e| Error::DeleteError {
                     path: pm.path.to_string(),
@@ -246,7 +529,7 @@ impl Provider for Hashivault {
         // otherwise, delete the whole path
         let (engine, mount, path) = parse_path(pm)?;
         if engine == "kv2" {
-            kv2::delete_latest(&self.client, mount, path)
+            kv2::delete_latest(client, mount, path)
                 .await
                 .map_err(|e| xerr(pm, e))
                 .map_err(|e| Error::DeleteError {
@@ -254,7 +537,7 @@ impl Provider for Hashivault {
                     msg: e.to_string(),
                 })?;
         } else {
-            kv1::delete(&self.client, mount, path)
+            kv1::delete(client, mount, path)
                 .await
                 .map_err(|e| xerr(pm, e))
                 .map_err(|e| Error::DeleteError {
@@ -273,7 +556,14 @@ mod tests {
     use dockertest_server::Test;
 
     use super::*;
-    use crate::providers::test_utils;
+    use crate::testkit;
+
+    #[test]
+    fn oidc_nonce() {
+        let url = "https://idp.example.com/auth?client_id=vault&nonce=n-123&state=st";
+        assert_eq!(query_param(url, "nonce").as_deref(), Some("n-123"));
+        assert_eq!(query_param(url, "code"), None);
+    }
 
     #[test]
     #[cfg(not(windows))]
@@ -305,12 +595,38 @@ mod tests {
             let p = Box::new(
                 super::Hashivault::new(
                     "hashicorp_vault",
-                    Some(serde_json::from_value(data).unwrap()),
+                    Some(serde_json::from_value(data.clone()).unwrap()),
                 )
                 .unwrap(),
             ) as Box<dyn Provider + Send + Sync>;
 
-            test_utils::ProviderTest::new(p).run().await;
+            testkit::ProviderTest::new(p).run().await;
+
+            let p = super::Hashivault::new(
+                "hashicorp_vault",
+                Some(serde_json::from_value(data).unwrap()),
+            )
+            .unwrap();
+            for (app, pass) in [("billing", "b1"), ("search", "s1")] {
+                p.put(
+                    &PathMap::from_path(&format!("secret/apps/{app}")),
+                    &[KV::from_kv("DB_PASS", pass)],
+                )
+                .await
+                .unwrap();
+            }
+            let wildcard = PathMap {
+                child_prefix: true,
+                ..PathMap::from_path("secret/apps/*")
+            };
+            let kvs = p.get(&wildcard).await.unwrap();
+            assert_eq!(
+                kvs.iter()
+                    .map(|kv| (kv.key.as_str(), kv.value.expose()))
+                    .collect::<Vec<_>>(),
+                vec![("billing_DB_PASS", "b1"), ("search_DB_PASS", "s1")]
+            );
+            assert!(p.del(&wildcard).await.is_err());
         });
     }
 }
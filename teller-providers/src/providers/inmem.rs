@@ -108,7 +108,7 @@ impl Provider for Inmem {
         let mut store = self.store.lock().unwrap();
         let mut data = store.get(&pm.path).cloned().unwrap_or_default();
         for kv in kvs {
-            data.insert(kv.key.clone(), kv.value.clone());
+            data.insert(kv.key.clone(), kv.value.expose().to_string());
         }
         store.insert(pm.path.clone(), data);
         Ok(())
@@ -134,7 +134,7 @@ impl Provider for Inmem {
 mod tests {
     use tokio::test;
 
-    use crate::providers::test_utils;
+    use crate::testkit;
     use crate::Provider;
 
     #[test]
@@ -142,6 +142,6 @@ mod tests {
         let p =
             Box::new(super::Inmem::new("test", None).unwrap()) as Box<dyn Provider + Send + Sync>;
 
-        test_utils::ProviderTest::new(p).run().await;
+        testkit::ProviderTest::new(p).run().await;
     }
 }
@@ -5,11 +5,9 @@ use serde::{Deserialize, Serialize};
 use serde_variant::to_variant_name;
 use strum::{EnumIter, IntoEnumIterator};
 
-#[cfg(test)]
-mod test_utils;
-
 #[cfg(feature = "dotenv")]
 pub mod dotenv;
+pub mod fake;
 pub mod inmem;
 
 #[cfg(feature = "hashicorp_vault")]
@@ -21,6 +19,9 @@ pub mod ssm;
 #[cfg(feature = "aws_secretsmanager")]
 pub mod aws_secretsmanager;
 
+#[cfg(feature = "aws_sso")]
+pub mod aws_sso;
+
 #[cfg(feature = "google_secretmanager")]
 pub mod google_secretmanager;
 
@@ -38,14 +39,15 @@ lazy_static! {
         providers.join(", ")
     };
 }
-#[derive(
-    Serialize, Deserialize, Debug, Clone, Default, PartialOrd, Ord, PartialEq, Eq, EnumIter,
-)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialOrd, Ord, PartialEq, Eq, EnumIter)]
 pub enum ProviderKind {
     #[serde(rename = "inmem")]
     Inmem,
 
-    #[default]
+    /// An in-memory test double with fault injection
+    #[serde(rename = "fake")]
+    Fake,
+
     #[cfg(feature = "dotenv")]
     #[serde(rename = "dotenv")]
     Dotenv,
@@ -73,6 +75,24 @@ pub enum ProviderKind {
     #[cfg(feature = "etcd")]
     #[serde(rename = "etcd")]
     Etcd,
+
+    /// Implemented outside of teller, created by the factory named in
+    /// [`crate::config::ProviderCfg::factory`]
+    #[serde(rename = "custom")]
+    Custom,
+}
+
+/// `dotenv`, or `inmem` when built without it
+impl Default for ProviderKind {
+    #[cfg(feature = "dotenv")]
+    fn default() -> Self {
+        Self::Dotenv
+    }
+
+    #[cfg(not(feature = "dotenv"))]
+    fn default() -> Self {
+        Self::Inmem
+    }
 }
 
 impl std::fmt::Display for ProviderKind {
@@ -15,32 +15,22 @@
 //!
 //!
 #![allow(clippy::borrowed_box)]
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
 use aws_config::{self, BehaviorVersion};
 use aws_sdk_ssm as ssm;
 use serde_derive::{Deserialize, Serialize};
 use ssm::config::{Credentials, Region};
-use ssm::{
-    error::SdkError, operation::delete_parameter::DeleteParameterError, types::ParameterType,
-};
+use ssm::types::{ParameterTier, ParameterType, ResourceTypeForTagging, Tag};
 
 use super::ProviderKind;
 use crate::config::{PathMap, ProviderInfo, KV};
 use crate::Provider;
 use crate::{Error, Result};
 
-fn handle_delete(e: SdkError<DeleteParameterError>, pm: &PathMap) -> Result<()> {
-    match e.into_service_error() {
-        DeleteParameterError::ParameterNotFound(_) => {
-            // we're ok
-            Ok(())
-        }
-        e => Err(Error::DeleteError {
-            path: pm.path.to_string(),
-            msg: e.to_string(),
-        }),
-    }
-}
+/// `delete_parameters` takes at most this many names per call
+const DELETE_BATCH_SIZE: usize = 10;
 
 fn join_path(left: &str, right: &str) -> String {
     format!(
@@ -59,17 +49,48 @@ fn join_path(left: &str, right: &str) -> String {
 /// If you need an additional parameter from the AWS SDK included in our simplified configuration,
 /// open an issue in Teller and request to add it.
 ///
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SSMOptions {
     pub region: Option<String>,
     pub access_key_id: Option<String>,
     pub secret_access_key: Option<String>,
+    /// Session token of temporary credentials, e.g. from `teller login --sso`
+    pub session_token: Option<String>,
     pub endpoint_url: Option<String>,
+    /// Tags set on every parameter written
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// Description set on every parameter written
+    pub description: Option<String>,
+    /// Tier of parameters written, `Standard` if not set
+    pub tier: Option<SSMTier>,
+}
+
+/// Parameter tiers, see [AWS parameter tiers](https://docs.aws.amazon.com/systems-manager/latest/userguide/parameter-store-advanced-parameters.html)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SSMTier {
+    #[serde(rename = "Standard")]
+    Standard,
+    #[serde(rename = "Advanced")]
+    Advanced,
+    #[serde(rename = "Intelligent-Tiering")]
+    IntelligentTiering,
+}
+
+impl From<SSMTier> for ParameterTier {
+    fn from(tier: SSMTier) -> Self {
+        match tier {
+            SSMTier::Standard => Self::Standard,
+            SSMTier::Advanced => Self::Advanced,
+            SSMTier::IntelligentTiering => Self::IntelligentTiering,
+        }
+    }
 }
 
 pub struct SSM {
     pub name: String,
     pub client: ssm::Client,
+    pub opts: SSMOptions,
 }
 impl SSM {
     #[must_use]
@@ -77,6 +98,7 @@ impl SSM {
         Self {
             name: name.to_string(),
             client,
+            opts: SSMOptions::default(),
         }
     }
 
@@ -86,19 +108,23 @@ impl SSM {
     ///
     /// This function will return an error if cannot create a provider
     pub async fn new(name: &str, opts: Option<serde_json::Value>) -> Result<Self> {
-        let client = if let Some(opts) = opts {
-            let opts: SSMOptions = serde_json::from_value(opts)?;
-
+        let opts = opts.map(serde_json::from_value::<SSMOptions>).transpose()?;
+        let client = if let Some(opts) = &opts {
             let mut config = aws_config::defaults(BehaviorVersion::v2023_11_09());
-            if let (Some(key), Some(secret)) = (opts.access_key_id, opts.secret_access_key) {
-                config = config
-                    .credentials_provider(Credentials::new(key, secret, None, None, "teller"));
+            if let (Some(key), Some(secret)) = (&opts.access_key_id, &opts.secret_access_key) {
+                config = config.credentials_provider(Credentials::new(
+                    key.clone(),
+                    secret.clone(),
+                    opts.session_token.clone(),
+                    None,
+                    "teller",
+                ));
             }
-            if let Some(endpoint_url) = opts.endpoint_url {
-                config = config.endpoint_url(endpoint_url);
+            if let Some(endpoint_url) = &opts.endpoint_url {
+                config = config.endpoint_url(endpoint_url.clone());
             }
-            if let Some(region) = opts.region {
-                config = config.region(Region::new(region));
+            if let Some(region) = &opts.region {
+                config = config.region(Region::new(region.clone()));
             }
             let ssmconf = ssm::config::Builder::from(&config.load().await).build();
             ssm::Client::from_conf(ssmconf)
@@ -110,6 +136,7 @@ impl SSM {
         Ok(Self {
             client,
             name: name.to_string(),
+            opts: opts.unwrap_or_default(),
         })
     }
 }
@@ -213,15 +240,42 @@ impl Provider for SSM {
             self.client
                 .put_parameter()
                 .name(&path)
-                .value(&kv.value)
+                .value(kv.value.expose())
                 .overwrite(true)
                 .r#type(ParameterType::String)
+                .set_description(self.opts.description.clone())
+                .set_tier(self.opts.tier.map(ParameterTier::from))
                 .send()
                 .await
                 .map_err(|e| Error::PutError {
                     msg: e.to_string(),
-                    path,
+                    path: path.clone(),
                 })?;
+
+            // tags cannot be given along with `overwrite`, they are added after
+            if !self.opts.tags.is_empty() {
+                let tags = self
+                    .opts
+                    .tags
+                    .iter()
+                    .map(|(k, v)| Tag::builder().key(k).value(v).build())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|e| Error::PutError {
+                        msg: e.to_string(),
+                        path: path.clone(),
+                    })?;
+                self.client
+                    .add_tags_to_resource()
+                    .resource_type(ResourceTypeForTagging::Parameter)
+                    .resource_id(&path)
+                    .set_tags(Some(tags))
+                    .send()
+                    .await
+                    .map_err(|e| Error::PutError {
+                        msg: e.to_string(),
+                        path,
+                    })?;
+            }
         }
         Ok(())
     }
@@ -230,7 +284,7 @@ impl Provider for SSM {
         let paths = if pm.keys.is_empty() {
             let kvs = self.get(pm).await?;
             kvs.iter()
-                .map(|kv| join_path(&pm.path, &kv.key))
+                .map(|kv| join_path(&pm.path, &kv.from_key))
                 .collect::<Vec<_>>()
         } else {
             pm.keys
@@ -239,9 +293,17 @@ impl Provider for SSM {
                 .collect::<Vec<_>>()
         };
 
-        for path in paths {
-            let res = self.client.delete_parameter().name(path).send().await;
-            res.map_or_else(|e| handle_delete(e, pm), |_| Ok(()))?;
+        // parameters that are not found come back as invalid, which is fine
+        for batch in paths.chunks(DELETE_BATCH_SIZE) {
+            self.client
+                .delete_parameters()
+                .set_names(Some(batch.to_vec()))
+                .send()
+                .await
+                .map_err(|e| Error::DeleteError {
+                    path: pm.path.to_string(),
+                    msg: e.to_string(),
+                })?;
         }
 
         Ok(())
@@ -258,7 +320,7 @@ mod tests {
     use dockertest_server::Test;
 
     use super::*;
-    use crate::providers::test_utils;
+    use crate::testkit;
 
     #[test]
     #[cfg(not(windows))]
@@ -287,12 +349,15 @@ mod tests {
                 "secret_access_key": "stub",
                 "provider_name": "faked",
                 "endpoint_url": server.external_url(),
+                "tags": { "team": "platform" },
+                "description": "managed by teller",
+                "tier": "Standard",
             });
 
             let p = Box::new(super::SSM::new("ssm", Some(data)).await.unwrap())
                 as Box<dyn Provider + Send + Sync>;
 
-            test_utils::ProviderTest::new(p)
+            testkit::ProviderTest::new(p)
                 .with_root_prefix("/")
                 .run()
                 .await;
@@ -1,8 +1,46 @@
 use std::collections::{BTreeMap, HashMap};
 
 use crate::providers::ProviderKind;
-use crate::Result;
+use crate::traced::Traced;
 use crate::{config::ProviderCfg, Provider};
+use crate::{Error, Result};
+
+/// Builds a `custom` provider from its name and configured options
+pub type ProviderFactory = Box<
+    dyn Fn(&str, Option<serde_json::Value>) -> Result<Box<dyn Provider + Sync + Send>>
+        + Sync
+        + Send,
+>;
+
+/// Replace `{from_env: NAME}` values in provider options with the value of the
+/// `NAME` env variable, so tokens never need to appear in configuration
+fn resolve_env(value: serde_json::Value, provider: &str) -> Result<serde_json::Value> {
+    use serde_json::Value;
+
+    Ok(match value {
+        Value::Object(map) => {
+            if let (1, Some(Value::String(var))) = (map.len(), map.get("from_env")) {
+                return std::env::var(var).map(Value::String).map_err(|_| {
+                    Error::CreateProviderError(format!(
+                        "provider '{provider}': env variable {var} is not set"
+                    ))
+                });
+            }
+            Value::Object(
+                map.into_iter()
+                    .map(|(k, v)| Ok((k, resolve_env(v, provider)?)))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|v| resolve_env(v, provider))
+                .collect::<Result<_>>()?,
+        ),
+        value => value,
+    })
+}
 
 pub struct Registry {
     providers: HashMap<String, Box<dyn Provider + Sync + Send>>,
@@ -15,47 +53,53 @@ impl Registry {
     ///
     /// This function will return an error if any provider loading failed
     pub async fn new(providers: &BTreeMap<String, ProviderCfg>) -> Result<Self> {
+        Self::with_factories(providers, &HashMap::new()).await
+    }
+
+    /// Create a registry from config, building `custom` providers with the
+    /// factory their `factory` field names
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any provider loading failed, or a
+    /// `custom` provider names no registered factory
+    pub async fn with_factories(
+        providers: &BTreeMap<String, ProviderCfg>,
+        factories: &HashMap<String, ProviderFactory>,
+    ) -> Result<Self> {
         let mut loaded_providers = HashMap::new();
         for (k, provider) in providers {
+            let options = provider
+                .options
+                .clone()
+                .map(|options| resolve_env(options, k))
+                .transpose()?;
             let provider: Box<dyn Provider + Sync + Send> = match provider.kind {
-                ProviderKind::Inmem => Box::new(crate::providers::inmem::Inmem::new(
+                ProviderKind::Inmem => Box::new(crate::providers::inmem::Inmem::new(k, options)?),
+                ProviderKind::Fake => Box::new(crate::providers::fake::Fake::new(
                     k,
-                    provider.options.clone(),
+                    options.map(serde_json::from_value).transpose()?,
                 )?),
 
                 #[cfg(feature = "dotenv")]
                 ProviderKind::Dotenv => Box::new(crate::providers::dotenv::Dotenv::new(
                     k,
-                    provider
-                        .options
-                        .clone()
-                        .map(serde_json::from_value)
-                        .transpose()?,
+                    options.map(serde_json::from_value).transpose()?,
                 )?),
                 #[cfg(feature = "hashicorp_vault")]
                 ProviderKind::Hashicorp => {
                     Box::new(crate::providers::hashicorp_vault::Hashivault::new(
                         k,
-                        provider
-                            .options
-                            .clone()
-                            .map(serde_json::from_value)
-                            .transpose()?,
+                        options.map(serde_json::from_value).transpose()?,
                     )?)
                 }
                 #[cfg(feature = "ssm")]
-                ProviderKind::SSM => {
-                    Box::new(crate::providers::ssm::SSM::new(k, provider.options.clone()).await?)
-                }
+                ProviderKind::SSM => Box::new(crate::providers::ssm::SSM::new(k, options).await?),
                 #[cfg(feature = "aws_secretsmanager")]
                 ProviderKind::AWSSecretsManager => Box::new(
                     crate::providers::aws_secretsmanager::AWSSecretsManager::new(
                         k,
-                        provider
-                            .options
-                            .clone()
-                            .map(serde_json::from_value)
-                            .transpose()?,
+                        options.map(serde_json::from_value).transpose()?,
                     )
                     .await?,
                 ),
@@ -63,6 +107,7 @@ impl Registry {
                 ProviderKind::GoogleSecretManager => Box::new(
                     crate::providers::google_secretmanager::GoogleSecretManager::new(
                         k,
+                        options.map(serde_json::from_value).transpose()?,
                         Box::new(crate::providers::google_secretmanager::GSMClient::new().await?)
                             as Box<dyn crate::providers::google_secretmanager::GSM + Send + Sync>,
                     ),
@@ -71,35 +116,114 @@ impl Registry {
                 ProviderKind::HashiCorpConsul => {
                     Box::new(crate::providers::hashicorp_consul::HashiCorpConsul::new(
                         k,
-                        provider
-                            .options
-                            .clone()
-                            .map(serde_json::from_value)
-                            .transpose()?,
+                        options.map(serde_json::from_value).transpose()?,
                     )?)
                 }
                 #[cfg(feature = "etcd")]
                 ProviderKind::Etcd => Box::new(
                     crate::providers::etcd::Etcd::new(
                         k,
-                        provider
-                            .options
-                            .clone()
-                            .map(serde_json::from_value)
-                            .transpose()?,
+                        options.map(serde_json::from_value).transpose()?,
                     )
                     .await?,
                 ),
+                ProviderKind::Custom => {
+                    let factory = provider
+                        .factory
+                        .as_ref()
+                        .and_then(|factory| factories.get(factory))
+                        .ok_or_else(|| {
+                            Error::CreateProviderError(format!(
+                                "no factory {:?} for custom provider '{k}'",
+                                provider.factory.as_deref().unwrap_or_default()
+                            ))
+                        })?;
+                    factory(k, options)?
+                }
             };
-            loaded_providers.insert(k.clone(), provider);
+            loaded_providers.insert(k.clone(), Box::new(Traced::new(provider)) as _);
         }
         Ok(Self {
             providers: loaded_providers,
         })
     }
+    /// Add a provider under `name`, replacing any provider already registered with it.
+    /// This is how providers built in code (not from configuration) get in.
+    pub fn insert(&mut self, name: &str, provider: Box<dyn Provider + Sync + Send>) {
+        self.providers
+            .insert(name.to_string(), Box::new(Traced::new(provider)));
+    }
+
     #[must_use]
     #[allow(clippy::borrowed_box)]
     pub fn get(&self, name: &str) -> Option<&Box<dyn Provider + Sync + Send>> {
         self.providers.get(name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::inmem::Inmem;
+
+    fn custom(factory: &str) -> BTreeMap<String, ProviderCfg> {
+        BTreeMap::from([(
+            "acme1".to_string(),
+            ProviderCfg {
+                kind: ProviderKind::Custom,
+                factory: Some(factory.to_string()),
+                ..ProviderCfg::default()
+            },
+        )])
+    }
+
+    #[tokio::test]
+    async fn custom_providers() {
+        let factories = HashMap::from([(
+            "acme".to_string(),
+            Box::new(|name: &str, opts| {
+                Ok(Box::new(Inmem::new(name, opts)?) as Box<dyn Provider + Sync + Send>)
+            }) as ProviderFactory,
+        )]);
+
+        let registry = Registry::with_factories(&custom("acme"), &factories)
+            .await
+            .unwrap();
+        assert_eq!(registry.get("acme1").unwrap().kind().name, "acme1");
+
+        let err = Registry::with_factories(&custom("other"), &factories)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("no factory \"other\""));
+        assert!(Registry::new(&custom("acme")).await.is_err());
+    }
+
+    #[test]
+    fn options_from_env() {
+        let options = serde_json::json!({
+            "address": "http://localhost:8200",
+            "token": { "from_env": "PATH" },
+            "nested": [{ "from_env": "PATH" }],
+        });
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(
+            resolve_env(options, "vault").unwrap(),
+            serde_json::json!({
+                "address": "http://localhost:8200",
+                "token": path,
+                "nested": [path],
+            })
+        );
+
+        let err = resolve_env(
+            serde_json::json!({ "token": { "from_env": "TELLER_TEST_UNSET_VAR" } }),
+            "vault",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "provider 'vault': env variable TELLER_TEST_UNSET_VAR is not set"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_derive::{Deserialize as DeriveDeserialize, Serialize as DeriveSerialize};
+use zeroize::Zeroize;
+
+/// A secret value, wiped from memory when dropped
+///
+/// `Debug` and `Display` never show the value, reading it takes an explicit
+/// [`SecretValue::expose`]. Serializing exposes it, serialization is how values
+/// are exported.
+///
+/// Values that are not valid UTF-8, such as keystores, are kept as bytes.
+/// Text outputs get them base64 encoded, providers with binary payloads write
+/// them as they are with [`SecretValue::as_bytes`].
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SecretValue(Inner);
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Inner {
+    Text(String),
+    Bytes {
+        bytes: Vec<u8>,
+        /// the base64 encoding, for [`SecretValue::expose`]
+        encoded: String,
+    },
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+/// How binary values serialize, to tell them from text
+#[derive(DeriveSerialize, DeriveDeserialize)]
+#[serde(untagged)]
+enum Repr {
+    Text(String),
+    Bytes { base64: String },
+}
+
+impl SecretValue {
+    #[must_use]
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(Inner::Text(value.into()))
+    }
+
+    /// A value from a binary payload, kept as text if it is valid UTF-8
+    #[must_use]
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Self::new(text),
+            Err(err) => {
+                let bytes = err.into_bytes();
+                let encoded = STANDARD.encode(&bytes);
+                Self(Inner::Bytes { bytes, encoded })
+            }
+        }
+    }
+
+    /// The value itself, for output boundaries (exporting, running a process,
+    /// writing to a provider). Binary values are base64 encoded.
+    #[must_use]
+    pub fn expose(&self) -> &str {
+        match &self.0 {
+            Inner::Text(text) => text,
+            Inner::Bytes { encoded, .. } => encoded,
+        }
+    }
+
+    /// The value as bytes, for providers that store binary payloads
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.0 {
+            Inner::Text(text) => text.as_bytes(),
+            Inner::Bytes { bytes, .. } => bytes,
+        }
+    }
+
+    /// Whether the value is not valid UTF-8
+    #[must_use]
+    pub const fn is_binary(&self) -> bool {
+        matches!(self.0, Inner::Bytes { .. })
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.as_bytes().is_empty()
+    }
+}
+
+impl Drop for SecretValue {
+    fn drop(&mut self) {
+        match &mut self.0 {
+            Inner::Text(text) => text.zeroize(),
+            Inner::Bytes { bytes, encoded } => {
+                bytes.zeroize();
+                encoded.zeroize();
+            }
+        }
+    }
+}
+
+impl From<String> for SecretValue {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretValue {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl std::fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretValue(***)")
+    }
+}
+
+impl std::fmt::Display for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("***")
+    }
+}
+
+/// Text values serialize as strings, binary values as `{"base64": "..."}` so
+/// they deserialize back to bytes
+impl Serialize for SecretValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.0 {
+            Inner::Text(text) => serializer.serialize_str(text),
+            Inner::Bytes { encoded, .. } => Repr::Bytes {
+                base64: encoded.clone(),
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => Ok(Self::new(text)),
+            Repr::Bytes { base64 } => STANDARD
+                .decode(&base64)
+                .map(Self::from_bytes)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hide_value() {
+        let secret = SecretValue::from("s3cret");
+        assert_eq!(format!("{secret} {secret:?}"), "*** SecretValue(***)");
+        assert_eq!(secret.expose(), "s3cret");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"s3cret\"");
+        assert_eq!(
+            serde_json::from_str::<SecretValue>("\"s3cret\"").unwrap(),
+            secret
+        );
+    }
+
+    #[test]
+    fn binary_value() {
+        assert!(!SecretValue::from_bytes(b"text".to_vec()).is_binary());
+
+        let secret = SecretValue::from_bytes(vec![0xfe, 0xed, 0x00, 0x01]);
+        assert!(secret.is_binary());
+        assert_eq!(secret.as_bytes(), [0xfe, 0xed, 0x00, 0x01]);
+        assert_eq!(secret.expose(), "/u0AAQ==");
+        let json = serde_json::to_string(&secret).unwrap();
+        assert_eq!(json, r#"{"base64":"/u0AAQ=="}"#);
+        assert_eq!(serde_json::from_str::<SecretValue>(&json).unwrap(), secret);
+    }
+}
@@ -0,0 +1,365 @@
+//! Conformance suite for [`Provider`] implementations, the one built-in
+//! providers are tested with, for authors of custom providers.
+//!
+//! The suite writes, reads, updates and deletes a tree of test paths, so run it
+//! against a scratch backend (a container, a mock client, a test account).
+//! Behaviors a backend does not have are turned off with [`Capabilities`].
+//!
+//! Enable the `testkit` feature, as a dev-dependency:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! teller-providers = { version = "2", features = ["testkit"] }
+//! ```
+//!
+//! ```no_run
+//! use teller_providers::providers::inmem::Inmem;
+//! use teller_providers::testkit::{Capabilities, ProviderTest};
+//!
+//! # async fn conformance() {
+//! let provider = Box::new(Inmem::new("test", None).unwrap());
+//! ProviderTest::new(provider)
+//!     .with_capabilities(Capabilities {
+//!         multiline: false,
+//!         ..Capabilities::all()
+//!     })
+//!     .run()
+//!     .await;
+//! # }
+//! ```
+//!
+//! Failed checks panic, as assertions do.
+use std::collections::BTreeMap;
+
+use crate::config::{MetaInfo, PathMap, KV};
+use crate::{Error, Provider};
+
+pub const ROOT_PATH_A: &str = "secret/development";
+pub const ROOT_PATH_B: &str = "secret/multiple/app-1";
+pub const ROOT_PATH_C: &str = "secret/multiple/app-2";
+pub const ROOT_PATH_PAGING: &str = "secret/multiple/lotsakeys";
+pub const ROOT_PATH_MULTILINE: &str = "secret/multiline";
+const PATH_A_KEY_1: &str = "db";
+const PATH_A_KEY_2: &str = "log_level";
+const PATH_A_KEY_3: &str = "app";
+const PATH_A_VALUE_1: &str = "{\"DB_PASS\": \"1234\",\"DB_NAME\": \"FOO\"}";
+const PATH_A_VALUE_2: &str = "DEBUG";
+const PATH_A_VALUE_3: &str = "Teller";
+const PATH_B_KEY_1: &str = "log_level";
+const PATH_B_VALUE_1: &str = "DEBUG";
+const PATH_C_KEY_1: &str = "foo";
+const PATH_C_VALUE_1: &str = "bar";
+const PATH_C_VALUE_1_UPDATE: &str = "baz";
+/// keys put at once by the paging test, more than some backends take per request
+pub(crate) const PAGING_KEYS: usize = 100;
+const MULTILINE_VALUES: &[(&str, &str)] = &[
+    (
+        "cert",
+        "-----BEGIN CERTIFICATE-----\nMIIBszCCAVmgAwIBAgIUX\n-----END CERTIFICATE-----\n",
+    ),
+    ("crlf", "first\r\nsecond"),
+    ("quoted", "it's \"$HOME\" \\ # not a comment"),
+];
+
+/// What a provider can do, selecting the checks of the suite. Reading a path
+/// that does not exist is always checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Capabilities {
+    /// writing values, and reading them back. Without it, nothing else can be
+    /// checked.
+    pub put: bool,
+    /// reading only the keys a map lists
+    pub selective_get: bool,
+    /// deleting a whole path, which then reads as [`Error::NotFound`]
+    pub delete: bool,
+    /// deleting single keys of a path
+    pub delete_keys: bool,
+    /// paths with many keys, which backends often page through
+    pub many_keys: bool,
+    /// values with newlines, quotes and escapes
+    pub multiline: bool,
+}
+
+impl Capabilities {
+    /// Everything, the built-in providers' capabilities
+    #[must_use]
+    pub const fn all() -> Self {
+        Self {
+            put: true,
+            selective_get: true,
+            delete: true,
+            delete_keys: true,
+            many_keys: true,
+            multiline: true,
+        }
+    }
+
+    /// Reading only, for read-only backends
+    #[must_use]
+    pub const fn read_only() -> Self {
+        Self {
+            put: false,
+            selective_get: false,
+            delete: false,
+            delete_keys: false,
+            many_keys: false,
+            multiline: false,
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// The suite, run against one provider
+pub struct ProviderTest {
+    /// Prefix of all test paths, for providers whose paths must start with
+    /// something, e.g. `/` for SSM. Returned paths may carry it or not.
+    pub root_prefix: Option<String>,
+    pub capabilities: Capabilities,
+    pub provider: Box<dyn Provider + Send + Sync>,
+}
+
+impl ProviderTest {
+    #[must_use]
+    pub fn new(provider: Box<dyn Provider + Send + Sync>) -> Self {
+        Self {
+            root_prefix: None,
+            capabilities: Capabilities::all(),
+            provider,
+        }
+    }
+
+    #[must_use]
+    pub fn with_root_prefix(mut self, root_prefix: &str) -> Self {
+        self.root_prefix = Some(root_prefix.to_string());
+        self
+    }
+
+    #[must_use]
+    pub const fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Run the checks the provider's capabilities select
+    ///
+    /// # Panics
+    ///
+    /// Panics when the provider fails a check
+    pub async fn run(&self) {
+        let caps = self.capabilities;
+        self.validate_get_unexisting_key().await;
+        if !caps.put {
+            return;
+        }
+        let path_tree = self.get_tree();
+        self.validate_put(&path_tree).await;
+        self.validate_get(&path_tree).await;
+        if caps.selective_get {
+            self.validate_get_selective().await;
+        }
+        self.validate_update().await;
+        if caps.delete {
+            self.validate_delete().await;
+        }
+        if caps.delete_keys {
+            self.validate_delete_keys().await;
+        }
+        if caps.multiline {
+            self.validate_multiline().await;
+        }
+    }
+
+    fn kv(&self, key: &str, value: &str) -> KV {
+        KV::from_literal("", key, value, self.provider.kind())
+    }
+
+    /// Test paths, with the key-values written to them
+    fn get_tree(&self) -> BTreeMap<&'static str, Vec<KV>> {
+        let mut tree = BTreeMap::from([
+            (
+                ROOT_PATH_A,
+                vec![
+                    self.kv(PATH_A_KEY_1, PATH_A_VALUE_1),
+                    self.kv(PATH_A_KEY_2, PATH_A_VALUE_2),
+                    self.kv(PATH_A_KEY_3, PATH_A_VALUE_3),
+                ],
+            ),
+            (ROOT_PATH_B, vec![self.kv(PATH_B_KEY_1, PATH_B_VALUE_1)]),
+            (ROOT_PATH_C, vec![self.kv(PATH_C_KEY_1, PATH_C_VALUE_1)]),
+        ]);
+        if self.capabilities.many_keys {
+            tree.insert(
+                ROOT_PATH_PAGING,
+                (0..PAGING_KEYS)
+                    .map(|idx| self.kv(&format!("{PATH_C_KEY_1}_{idx}"), PATH_C_VALUE_1))
+                    .collect(),
+            );
+        }
+        tree
+    }
+
+    fn get_key_path(&self, root_path: &str) -> String {
+        self.root_prefix.as_ref().map_or_else(
+            || root_path.to_string(),
+            |prefix| format!("{prefix}{root_path}"),
+        )
+    }
+
+    /// Check key-values read from `root_path` are `expected`, as key-value
+    /// pairs in any order, and tell where they were read from
+    fn assert_read(&self, root_path: &str, got: &[KV], expected: &[(&str, &str)]) {
+        let mut pairs = got
+            .iter()
+            .map(|kv| (kv.key.clone(), kv.value.expose().to_string()))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        let mut expected = expected
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(pairs, expected, "key-values read from {root_path}");
+
+        let kind = self.provider.kind();
+        for kv in got {
+            assert_eq!(kv.from_key, kv.key, "from_key of {} in {root_path}", kv.key);
+            let path = kv.path.as_ref().map(|path| path.path.as_str());
+            assert!(
+                path.is_some_and(|path| path.ends_with(root_path)),
+                "path of {} read from {root_path}: {path:?}",
+                kv.key
+            );
+            assert_eq!(kv.provider.as_ref(), Some(&kind), "provider of {}", kv.key);
+            assert_eq!(
+                kv.meta,
+                Some(MetaInfo::default()),
+                "meta of {} in {root_path}",
+                kv.key
+            );
+        }
+    }
+
+    /// Reading a path that does not exist fails
+    async fn validate_get_unexisting_key(&self) {
+        let res = self
+            .provider
+            .get(&PathMap::from_path(&format!("{ROOT_PATH_A}/invalid-path")))
+            .await;
+        assert!(res.is_err(), "get of a missing path: {res:?}");
+    }
+
+    /// The whole test tree can be written
+    async fn validate_put(&self, path_tree: &BTreeMap<&str, Vec<KV>>) {
+        for (root_path, kvs) in path_tree {
+            let path_map = PathMap::from_path(&self.get_key_path(root_path));
+            let res = self.provider.put(&path_map, kvs).await;
+            assert!(res.is_ok(), "put {root_path}: {res:?}");
+        }
+    }
+
+    /// The whole test tree reads back as written
+    async fn validate_get(&self, path_tree: &BTreeMap<&str, Vec<KV>>) {
+        for (root_path, kvs) in path_tree {
+            let res = self
+                .provider
+                .get(&PathMap::from_path(&self.get_key_path(root_path)))
+                .await;
+            let got = res.unwrap_or_else(|err| panic!("get {root_path}: {err}"));
+            let expected = kvs
+                .iter()
+                .map(|kv| (kv.key.as_str(), kv.value.expose()))
+                .collect::<Vec<_>>();
+            self.assert_read(root_path, &got, &expected);
+        }
+    }
+
+    /// A map listing keys only reads those
+    async fn validate_get_selective(&self) {
+        let mut selective_pm = PathMap::from_path(&self.get_key_path(ROOT_PATH_A));
+        selective_pm
+            .keys
+            .insert(PATH_A_KEY_1.to_string(), PATH_A_KEY_1.to_string());
+
+        let got = self
+            .provider
+            .get(&selective_pm)
+            .await
+            .unwrap_or_else(|err| panic!("selective get {ROOT_PATH_A}: {err}"));
+        self.assert_read(ROOT_PATH_A, &got, &[(PATH_A_KEY_1, PATH_A_VALUE_1)]);
+    }
+
+    /// Writing a key again updates its value
+    async fn validate_update(&self) {
+        let pm = PathMap::from_path(&self.get_key_path(ROOT_PATH_C));
+        let res = self
+            .provider
+            .put(&pm, &[self.kv(PATH_C_KEY_1, PATH_C_VALUE_1_UPDATE)])
+            .await;
+        assert!(res.is_ok(), "update {ROOT_PATH_C}: {res:?}");
+
+        let got = self
+            .provider
+            .get(&pm)
+            .await
+            .unwrap_or_else(|err| panic!("get {ROOT_PATH_C} after update: {err}"));
+        self.assert_read(ROOT_PATH_C, &got, &[(PATH_C_KEY_1, PATH_C_VALUE_1_UPDATE)]);
+    }
+
+    /// A deleted path reads as not found
+    async fn validate_delete(&self) {
+        let pm = PathMap::from_path(&self.get_key_path(ROOT_PATH_B));
+        let res = self.provider.del(&pm).await;
+        assert!(res.is_ok(), "delete {ROOT_PATH_B}: {res:?}");
+
+        let res = self.provider.get(&pm).await;
+        assert!(
+            matches!(res, Err(Error::NotFound { .. })),
+            "get {ROOT_PATH_B} after delete: {res:?}"
+        );
+    }
+
+    /// Deleting keys of a path leaves its other keys
+    async fn validate_delete_keys(&self) {
+        let mut pm = PathMap::from_path(&self.get_key_path(ROOT_PATH_A));
+        pm.keys = BTreeMap::from([
+            (PATH_A_KEY_2.to_string(), String::new()),
+            (PATH_A_KEY_3.to_string(), String::new()),
+        ]);
+        let res = self.provider.del(&pm).await;
+        assert!(res.is_ok(), "delete keys of {ROOT_PATH_A}: {res:?}");
+
+        let got = self
+            .provider
+            .get(&PathMap::from_path(&self.get_key_path(ROOT_PATH_A)))
+            .await
+            .unwrap_or_else(|err| panic!("get {ROOT_PATH_A} after deleting keys: {err}"));
+        self.assert_read(ROOT_PATH_A, &got, &[(PATH_A_KEY_1, PATH_A_VALUE_1)]);
+    }
+
+    /// Values with newlines, quotes and escapes read back exactly as they
+    /// were written, then are removed
+    async fn validate_multiline(&self) {
+        let pm = PathMap::from_path(&self.get_key_path(ROOT_PATH_MULTILINE));
+        let kvs = MULTILINE_VALUES
+            .iter()
+            .map(|(k, v)| self.kv(k, v))
+            .collect::<Vec<_>>();
+        let res = self.provider.put(&pm, &kvs).await;
+        assert!(res.is_ok(), "put {ROOT_PATH_MULTILINE}: {res:?}");
+
+        let got = self
+            .provider
+            .get(&pm)
+            .await
+            .unwrap_or_else(|err| panic!("get {ROOT_PATH_MULTILINE}: {err}"));
+        self.assert_read(ROOT_PATH_MULTILINE, &got, MULTILINE_VALUES);
+
+        assert!(self.provider.del(&pm).await.is_ok());
+    }
+}
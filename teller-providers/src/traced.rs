@@ -0,0 +1,87 @@
+use std::future::Future;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use tracing::Instrument;
+
+use crate::config::{PathMap, ProviderInfo, KV};
+use crate::{Provider, Result};
+
+/// Wraps a provider with a span per operation, with its kind, name and path, and
+/// records how long operations took as the `teller.provider.duration_ms`
+/// histogram
+pub struct Traced {
+    inner: Box<dyn Provider + Sync + Send>,
+}
+
+impl Traced {
+    #[must_use]
+    pub fn new(inner: Box<dyn Provider + Sync + Send>) -> Self {
+        Self { inner }
+    }
+
+    async fn trace<T>(
+        &self,
+        op: &'static str,
+        pm: &PathMap,
+        fut: impl Future<Output = Result<T>> + Send,
+    ) -> Result<T> {
+        let info = self.inner.kind();
+        let span = tracing::info_span!(
+            "provider",
+            op,
+            kind = %info.kind,
+            name = %info.name,
+            path = %pm.path,
+        );
+        let start = Instant::now();
+        let res = fut.instrument(span.clone()).await;
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+        span.in_scope(|| match &res {
+            Ok(_) => tracing::info!(
+                histogram.teller.provider.duration_ms = elapsed,
+                op,
+                kind = %info.kind,
+                ok = true,
+                "done"
+            ),
+            Err(err) => tracing::warn!(
+                histogram.teller.provider.duration_ms = elapsed,
+                op,
+                kind = %info.kind,
+                ok = false,
+                error = %err,
+                "failed"
+            ),
+        });
+        res
+    }
+}
+
+#[async_trait]
+impl Provider for Traced {
+    fn kind(&self) -> ProviderInfo {
+        self.inner.kind()
+    }
+
+    async fn get(&self, pm: &PathMap) -> Result<Vec<KV>> {
+        self.trace("get", pm, self.inner.get(pm)).await
+    }
+
+    async fn put(&self, pm: &PathMap, kvs: &[KV]) -> Result<()> {
+        self.trace("put", pm, self.inner.put(pm, kvs)).await
+    }
+
+    async fn del(&self, pm: &PathMap) -> Result<()> {
+        self.trace("del", pm, self.inner.del(pm)).await
+    }
+
+    async fn keep_alive(&self, pm: &PathMap) -> Result<()> {
+        self.trace("keep_alive", pm, self.inner.keep_alive(pm))
+            .await
+    }
+
+    async fn watch(&self, pm: &PathMap) -> Result<()> {
+        self.trace("watch", pm, self.inner.watch(pm)).await
+    }
+}